@@ -17,6 +17,7 @@ fn main() -> Result<(), eframe::Error> {
             .with_decorations(true)
             .with_min_inner_size(egui::vec2(250.0, 160.0))
             .with_resizable(true)
+            .with_drag_and_drop(true)
             .with_icon(Arc::new(egui::viewport::IconData {
                 rgba: img.into_bytes(),
                 width: 288,
@@ -0,0 +1,415 @@
+use super::{
+    forward::{empty, full, ProxyBody},
+    settings::ProxyBlockPage,
+};
+use hyper::{
+    header::{ACCEPT, CONTENT_TYPE},
+    HeaderMap, Response, StatusCode, Uri,
+};
+
+/// The host the exception link points to, the Proxy answers requests to it itself.
+/// The .invalid top level domain never resolves, so the link only works through the Proxy.
+pub const EXCEPTION_HOST: &str = "a-bean-sieve.invalid";
+
+/// The path the exception link points to.
+const EXCEPTION_PATH: &str = "/exception";
+
+/// Added to a block response's extensions when the client connection should be reset instead.
+#[derive(Clone, Copy, Debug)]
+pub struct ResetConnection;
+
+/// The enum that carries which kind of body a client would prefer a response in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BlockFormat {
+    Html,
+    Json,
+    Text,
+}
+
+impl BlockFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            BlockFormat::Html => "text/html; charset=utf-8",
+            BlockFormat::Json => "application/json",
+            BlockFormat::Text => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// Contains the details of a blocked request shown on the block page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockedRequest {
+    pub url: String,
+    pub rule: Option<String>,
+    pub profile: String,
+    pub time: chrono::DateTime<chrono::Local>,
+}
+
+impl BlockedRequest {
+    /// Returns the matched rule, or why the request was blocked when no rule matched.
+    fn rule_description(&self) -> String {
+        self.rule
+            .clone()
+            .unwrap_or_else(|| format!("Not in the {}", self.profile))
+    }
+
+    /// Returns the link that queues an exception for this request.
+    ///
+    /// # Arguments:
+    /// * `token` - The token of the current Proxy run, which the link has to carry to be accepted.
+    fn exception_url(&self, token: &str) -> String {
+        let mut url = format!(
+            "http://{}{}?url={}&profile={}&token={}",
+            EXCEPTION_HOST,
+            EXCEPTION_PATH,
+            encode_query_value(&self.url),
+            encode_query_value(&self.profile),
+            encode_query_value(token)
+        );
+
+        if let Some(rule) = &self.rule {
+            url.push_str(&format!("&rule={}", encode_query_value(rule)));
+        }
+
+        url
+    }
+}
+
+/// Contains a request, made from a block page, to allow a blocked URL.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ProxyExceptionRequest {
+    pub url: String,
+    pub rule: Option<String>,
+    pub profile: String,
+    pub client: String,
+    pub requested: chrono::DateTime<chrono::Local>,
+}
+
+/// The enum that carries why a request made to the exception link wasn't queued.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExceptionLinkError {
+    /// The link isn't an exception link, or is missing the URL to allow.
+    Malformed,
+    /// The link doesn't carry the token of the current Proxy run, e.g. it was made by another page.
+    InvalidToken,
+}
+
+/// Creates the response to a blocked request, in the format the client prefers.
+///
+/// The HTML template may contain the placeholders: {url}, {rule}, {profile}, {time} and {exception_link}.
+///
+/// # Arguments:
+/// * `page` - The ProxyBlockPage settings to respond with.
+/// * `blocked` - The details of the blocked request.
+/// * `headers` - The headers of the blocked request.
+/// * `token` - The token of the current Proxy run, added to the exception link.
+pub fn block_response(
+    page: &ProxyBlockPage,
+    blocked: &BlockedRequest,
+    headers: &HeaderMap,
+    token: &str,
+) -> Response<ProxyBody> {
+    let Some(status) = page.status.status_code() else {
+        let mut response = Response::new(empty());
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        response.extensions_mut().insert(ResetConnection);
+        return response;
+    };
+
+    if status == StatusCode::NO_CONTENT {
+        let mut response = Response::new(empty());
+        *response.status_mut() = status;
+        return response;
+    }
+
+    let format = preferred_format(headers);
+    let exception_url = page.exception_link.then(|| blocked.exception_url(token));
+    let time = blocked.time.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let body = match format {
+        BlockFormat::Html => {
+            let exception_link = exception_url
+                .map(|url| format!("<a href=\"{}\">Request an exception</a>", escape_html(&url)))
+                .unwrap_or_default();
+
+            fill_template(
+                &page.template,
+                &[
+                    ("url", escape_html(&blocked.url)),
+                    ("rule", escape_html(&blocked.rule_description())),
+                    ("profile", escape_html(&blocked.profile)),
+                    ("time", time),
+                    ("exception_link", exception_link),
+                ],
+            )
+        }
+        BlockFormat::Json => serde_json::json!({
+            "blocked": true,
+            "url": blocked.url,
+            "rule": blocked.rule,
+            "profile": blocked.profile,
+            "time": blocked.time.to_rfc3339(),
+            "exception_url": exception_url,
+        })
+        .to_string(),
+        BlockFormat::Text => {
+            let mut body = format!(
+                "Request blocked: {}\nRule: {}\nProfile: {}\nTime: {}\n",
+                blocked.url,
+                blocked.rule_description(),
+                blocked.profile,
+                time
+            );
+            if let Some(url) = exception_url {
+                body.push_str(&format!("Request an exception: {}\n", url));
+            }
+            body
+        }
+    };
+
+    text_response(status, format, body)
+}
+
+/// Returns whether a request was made to the exception link, rather than an upstream server.
+///
+/// # Arguments:
+/// * `uri` - The URI being requested.
+pub fn is_exception_request(uri: &Uri) -> bool {
+    uri.host()
+        .is_some_and(|host| host.eq_ignore_ascii_case(EXCEPTION_HOST))
+}
+
+/// Reads an exception request from the query of an exception link.
+///
+/// # Arguments:
+/// * `uri` - The URI of the exception link.
+/// * `client` - The address of the client asking for the exception.
+/// * `token` - The token of the current Proxy run, which the link has to carry.
+pub fn parse_exception_request(
+    uri: &Uri,
+    client: String,
+    token: &str,
+) -> Result<ProxyExceptionRequest, ExceptionLinkError> {
+    if uri.path() != EXCEPTION_PATH {
+        return Err(ExceptionLinkError::Malformed);
+    }
+
+    let (mut url, mut rule, mut profile, mut link_token) = (None, None, String::default(), None);
+    for (name, value) in uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| {
+            pair.split_once('=')
+                .map(|(name, value)| (name, decode_query_value(value)))
+        })
+    {
+        match name {
+            "url" => url = Some(value),
+            "rule" => rule = Some(value),
+            "profile" => profile = value,
+            "token" => link_token = Some(value),
+            _ => {}
+        }
+    }
+
+    // Any page can link to the exception host, only block pages shown during this run know the token
+    if link_token.as_deref() != Some(token) {
+        return Err(ExceptionLinkError::InvalidToken);
+    }
+
+    Ok(ProxyExceptionRequest {
+        url: url
+            .filter(|url| !url.is_empty())
+            .ok_or(ExceptionLinkError::Malformed)?,
+        rule,
+        profile,
+        client,
+        requested: chrono::Local::now(),
+    })
+}
+
+/// Creates the response to a request made to the exception link.
+///
+/// # Arguments:
+/// * `request` - The exception request that was queued, or why the link wasn't accepted.
+/// * `headers` - The headers of the request made to the exception link.
+pub fn exception_response(
+    request: Result<&ProxyExceptionRequest, ExceptionLinkError>,
+    headers: &HeaderMap,
+) -> Response<ProxyBody> {
+    let format = preferred_format(headers);
+
+    let request = match request {
+        Ok(request) => request,
+        Err(ExceptionLinkError::Malformed) => {
+            return text_response(
+                StatusCode::BAD_REQUEST,
+                BlockFormat::Text,
+                String::from("The exception link is missing the URL to allow."),
+            );
+        }
+        Err(ExceptionLinkError::InvalidToken) => {
+            return text_response(
+                StatusCode::FORBIDDEN,
+                BlockFormat::Text,
+                String::from(
+                    "The exception link has expired, reload the blocked page for a new one.",
+                ),
+            );
+        }
+    };
+
+    let body = match format {
+        BlockFormat::Html => format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Exception Requested</title></head>\
+             <body><h1>Exception Requested</h1><p>A request to allow {} has been sent for review.</p></body></html>\n",
+            escape_html(&request.url)
+        ),
+        BlockFormat::Json => serde_json::json!({
+            "requested": true,
+            "url": request.url,
+        })
+        .to_string(),
+        BlockFormat::Text => format!(
+            "A request to allow {} has been sent for review.\n",
+            request.url
+        ),
+    };
+
+    text_response(StatusCode::OK, format, body)
+}
+
+/// Creates a response with the given body and the content type of its format.
+fn text_response(status: StatusCode, format: BlockFormat, body: String) -> Response<ProxyBody> {
+    let mut response = Response::new(full(body));
+    *response.status_mut() = status;
+    if let Ok(content_type) = format.content_type().parse() {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// Returns the format with the highest quality in the request's Accept header, defaulting to plain text.
+///
+/// # Arguments:
+/// * `headers` - The headers of the request.
+fn preferred_format(headers: &HeaderMap) -> BlockFormat {
+    let accept = headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let mut preferred = (BlockFormat::Text, 0.0);
+    for media_range in accept.split(',') {
+        let mut parameters = media_range.split(';');
+        let media_type = parameters
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let quality = parameters
+            .find_map(|parameter| parameter.trim().strip_prefix("q="))
+            .and_then(|quality| quality.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        let format = match media_type.as_str() {
+            "text/html" | "application/xhtml+xml" => BlockFormat::Html,
+            "application/json" => BlockFormat::Json,
+            media_type if media_type.ends_with("+json") => BlockFormat::Json,
+            "text/plain" => BlockFormat::Text,
+            _ => continue,
+        };
+
+        // Earlier media ranges win ties
+        if quality > preferred.1 {
+            preferred = (format, quality);
+        }
+    }
+
+    preferred.0
+}
+
+/// Replaces each {name} placeholder in a template with its value, leaving other braces untouched.
+///
+/// # Arguments:
+/// * `template` - The template to fill.
+/// * `values` - The placeholder names and the values to replace them with.
+fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    // Values are never searched for placeholders, so a URL containing one is shown as is
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match values.iter().find(|(name, _)| {
+            rest[1..].starts_with(name) && rest[1 + name.len()..].starts_with('}')
+        }) {
+            Some((name, value)) => {
+                filled.push_str(value);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+/// Escapes the characters that have a meaning in HTML.
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Percent-encodes a value so it can be used in a query string.
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Decodes a percent-encoded query string value.
+fn decode_query_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                match std::str::from_utf8(bytes.get(index + 1..index + 3).unwrap_or_default())
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        index += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
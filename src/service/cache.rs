@@ -0,0 +1,1328 @@
+use super::{
+    forward::{empty, full, ForwardError, ProxyBody},
+    settings::ProxyCacheSettings,
+};
+use chrono::{DateTime, Local, Utc};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body, Bytes, Frame, SizeHint},
+    header::{
+        HeaderName, HeaderValue, AGE, AUTHORIZATION, CACHE_CONTROL, DATE, ETAG, EXPIRES,
+        IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, PRAGMA, VARY,
+    },
+    HeaderMap, Method, Request, Response, StatusCode,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    thread,
+};
+
+/// The response statuses that may be stored without explicit freshness information.
+const CACHEABLE_STATUSES: [u16; 8] = [200, 203, 204, 300, 301, 308, 404, 410];
+
+/// The headers that only apply to a single connection, so they're never stored.
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "te",
+    "trailer",
+    "upgrade",
+    "content-length",
+];
+
+/// The headers that belong to the user the response was sent to, so they're never stored.
+const PRIVATE_HEADERS: [&str; 4] = [
+    "set-cookie",
+    "set-cookie2",
+    "authentication-info",
+    "proxy-authentication-info",
+];
+
+/// The enum that carries how the cache handled a request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProxyCacheStatus {
+    /// Answered from the cache without contacting the upstream server.
+    Hit,
+    /// Forwarded to the upstream server.
+    Miss,
+    /// Answered from the cache after the upstream server confirmed it was still valid.
+    Revalidated,
+}
+
+impl std::fmt::Display for ProxyCacheStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyCacheStatus::Hit => write!(f, "Hit"),
+            ProxyCacheStatus::Miss => write!(f, "Miss"),
+            ProxyCacheStatus::Revalidated => write!(f, "Revalidated"),
+        }
+    }
+}
+
+/// Contains a response stored in the cache, its body is kept in a separate file.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ProxyCacheEntry {
+    pub id: String,
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The request headers named by the response's Vary header, along with the values they were stored for.
+    pub vary: Vec<(String, String)>,
+    pub size: u64,
+    pub stored: DateTime<Local>,
+    /// The response's age, in seconds, when it was stored.
+    pub age: u64,
+    /// How long, in seconds, the response stays fresh for.
+    pub lifetime: u64,
+    /// Whether the response must be revalidated before every use.
+    pub revalidate: bool,
+    #[serde(skip)]
+    pub last_used: u64,
+}
+
+impl ProxyCacheEntry {
+    /// Returns the response's current age, in seconds.
+    pub fn current_age(&self) -> u64 {
+        let elapsed = (Local::now() - self.stored).num_seconds().max(0) as u64;
+        self.age + elapsed
+    }
+
+    /// Returns whether the response can be used without revalidating it.
+    pub fn is_fresh(&self) -> bool {
+        !self.revalidate && self.current_age() < self.lifetime
+    }
+
+    /// Returns whether the entry was stored for the given request headers.
+    ///
+    /// # Arguments:
+    /// * `headers` - The headers of the request being answered.
+    fn matches_vary(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| header_values(headers, name) == *value)
+    }
+
+    /// Returns the value of one of the stored response headers.
+    ///
+    /// # Arguments:
+    /// * `name` - The name of the header.
+    fn header(&self, name: &HeaderName) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name.as_str()))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Contains the statistics of the cache.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProxyCacheStats {
+    pub entries: usize,
+    pub size: u64,
+    pub hits: usize,
+    pub misses: usize,
+    pub revalidated: usize,
+}
+
+/// The enum that carries what should happen to a request after it has been looked up in the cache.
+pub enum CacheLookup {
+    /// The cache is disabled, or the request can't use it.
+    Bypass,
+    /// The request can be answered with the stored response.
+    Hit(ProxyCacheEntry),
+    /// The request must be forwarded, and its response may be stored.
+    Forward(CacheRequest),
+}
+
+/// Contains what's needed to store the response to a forwarded request.
+pub struct CacheRequest {
+    url: String,
+    headers: HeaderMap,
+    /// The stale entry being revalidated by the forwarded request, if any.
+    stale: Option<ProxyCacheEntry>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    settings: ProxyCacheSettings,
+    /// The stored responses, keyed by their URL.
+    entries: HashMap<String, Vec<ProxyCacheEntry>>,
+    size: u64,
+    clock: u64,
+    hits: usize,
+    misses: usize,
+    revalidated: usize,
+}
+
+impl CacheState {
+    /// Marks an entry as the most recently used.
+    fn touch(&mut self, url: &str, id: &str) {
+        self.clock += 1;
+        let clock = self.clock;
+
+        if let Some(entry) = self
+            .entries
+            .get_mut(url)
+            .and_then(|entries| entries.iter_mut().find(|entry| entry.id == id))
+        {
+            entry.last_used = clock;
+        }
+    }
+
+    /// Removes an entry from the index, returning it if it was there.
+    fn remove(&mut self, id: &str) -> Option<ProxyCacheEntry> {
+        let url = self
+            .entries
+            .iter()
+            .find(|(_, entries)| entries.iter().any(|entry| entry.id == id))
+            .map(|(url, _)| url.clone())?;
+
+        let entries = self.entries.get_mut(&url)?;
+        let index = entries.iter().position(|entry| entry.id == id)?;
+        let entry = entries.remove(index);
+        if entries.is_empty() {
+            self.entries.remove(&url);
+        }
+
+        self.size = self.size.saturating_sub(entry.size);
+        Some(entry)
+    }
+
+    /// Removes the least recently used entries until the cache fits in its size limit.
+    ///
+    /// Returns the identifiers of the removed entries, whose files are left to the caller.
+    fn evict(&mut self) -> Vec<String> {
+        let limit = self.settings.max_size_bytes();
+        let mut removed = Vec::new();
+
+        while self.size > limit {
+            let Some(id) = self
+                .entries
+                .values()
+                .flatten()
+                .min_by_key(|entry| entry.last_used)
+                .map(|entry| entry.id.clone())
+            else {
+                break;
+            };
+
+            if self.remove(&id).is_some() {
+                removed.push(id);
+            }
+        }
+
+        removed
+    }
+
+    /// Adds an entry to the index, replacing any stored for the same request.
+    ///
+    /// Returns the identifiers of the replaced entries, whose files are left to the caller.
+    fn insert(&mut self, mut entry: ProxyCacheEntry) -> Vec<String> {
+        let replaced: Vec<String> = self
+            .entries
+            .get(&entry.url)
+            .into_iter()
+            .flatten()
+            .filter(|stored| stored.vary == entry.vary)
+            .map(|stored| stored.id.clone())
+            .collect();
+        for id in &replaced {
+            self.remove(id);
+        }
+
+        self.size += entry.size;
+        self.clock += 1;
+        entry.last_used = self.clock;
+        self.entries
+            .entry(entry.url.clone())
+            .or_default()
+            .push(entry);
+
+        replaced
+    }
+}
+
+/// Stores responses on disk, so repeated requests can be answered without contacting the upstream server.
+#[derive(Clone, Default)]
+pub struct HttpCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl HttpCache {
+    /// Applies new cache settings, loading the entries stored in the cache's directory if it changed.
+    ///
+    /// # Arguments:
+    /// * `settings` - The ProxyCacheSettings to use.
+    pub fn configure(&self, settings: &ProxyCacheSettings) {
+        let directory = settings.directory();
+        let reload = {
+            let mut state = self.state.lock().unwrap();
+            let reload = state.entries.is_empty() || state.settings.directory() != directory;
+            state.settings = settings.clone();
+
+            if reload {
+                state.entries.clear();
+                state.size = 0;
+            }
+            reload
+        };
+
+        // Reading and removing files can take a while in a large cache, so it's done in the background
+        let cache = self.clone();
+        thread::spawn(move || {
+            let entries = match reload {
+                true => load_entries(&directory),
+                false => Vec::new(),
+            };
+
+            let mut state = cache.state.lock().unwrap();
+
+            // The directory may have changed again while its entries were being read
+            if state.settings.directory() != directory {
+                return;
+            }
+
+            // Responses stored meanwhile are newer than the ones on disk for the same request
+            let stored: HashSet<String> = state
+                .entries
+                .values()
+                .flatten()
+                .map(|entry| entry.id.clone())
+                .collect();
+
+            let mut removed = Vec::new();
+            for entry in entries {
+                if !stored.contains(&entry_id(&entry.url, &entry.vary)) {
+                    removed.extend(state.insert(entry));
+                } else if !stored.contains(&entry.id) {
+                    removed.push(entry.id);
+                }
+            }
+            removed.extend(state.evict());
+            drop(state);
+
+            for id in &removed {
+                remove_files(&directory, id);
+            }
+        });
+    }
+
+    /// Looks a request up in the cache, adding revalidation headers to it if a stale response is stored.
+    ///
+    /// # Arguments:
+    /// * `request` - The request being forwarded.
+    pub fn lookup<B>(&self, request: &mut Request<B>) -> CacheLookup {
+        let mut state = self.state.lock().unwrap();
+
+        // Only plain GET requests, without credentials or a refusal to use a cache, can be stored
+        let directives = cache_control(request.headers());
+        if !state.settings.enabled
+            || request.method() != Method::GET
+            || request.headers().contains_key(AUTHORIZATION)
+            || directives.contains_key("no-store")
+        {
+            return CacheLookup::Bypass;
+        }
+
+        let url = request.uri().to_string();
+        let entry = state.entries.get(&url).and_then(|entries| {
+            entries
+                .iter()
+                .find(|entry| entry.matches_vary(request.headers()))
+                .cloned()
+        });
+
+        let Some(entry) = entry else {
+            return CacheLookup::Forward(CacheRequest {
+                url,
+                headers: request.headers().clone(),
+                stale: None,
+            });
+        };
+
+        state.touch(&url, &entry.id);
+
+        let must_revalidate = directives.contains_key("no-cache")
+            || directives
+                .get("max-age")
+                .is_some_and(|age| age.as_deref() == Some("0"))
+            || header_values(request.headers(), PRAGMA.as_str()).contains("no-cache");
+
+        if entry.is_fresh() && !must_revalidate {
+            return CacheLookup::Hit(entry);
+        }
+
+        // Conditional requests from the client are left alone, their answer is meant for the client's own cache
+        let conditional = request.headers().contains_key(IF_NONE_MATCH)
+            || request.headers().contains_key(IF_MODIFIED_SINCE);
+        let mut stale = None;
+
+        if !conditional {
+            if let Some(etag) = entry.header(&ETAG).and_then(|etag| etag.parse().ok()) {
+                request.headers_mut().insert(IF_NONE_MATCH, etag);
+                stale = Some(entry.clone());
+            }
+
+            if let Some(modified) = entry
+                .header(&LAST_MODIFIED)
+                .and_then(|modified| modified.parse().ok())
+            {
+                request.headers_mut().insert(IF_MODIFIED_SINCE, modified);
+                stale = Some(entry.clone());
+            }
+        }
+
+        CacheLookup::Forward(CacheRequest {
+            url,
+            headers: request.headers().clone(),
+            stale,
+        })
+    }
+
+    /// Returns the stored response for a cache hit, or None if its body can no longer be read.
+    ///
+    /// # Arguments:
+    /// * `entry` - The ProxyCacheEntry to respond with.
+    pub async fn respond(&self, entry: &ProxyCacheEntry) -> Option<Response<ProxyBody>> {
+        let directory = self.state.lock().unwrap().settings.directory();
+
+        let Ok(body) = tokio::fs::read(body_path(&directory, &entry.id)).await else {
+            self.state.lock().unwrap().remove(&entry.id);
+            return None;
+        };
+
+        self.state.lock().unwrap().hits += 1;
+        Some(entry_response(entry, body))
+    }
+
+    /// Stores the upstream server's response to a forwarded request when it may be cached.
+    ///
+    /// A 304 answer to a revalidated request is replaced with the stored response. When the stored response
+    /// can no longer be read, the request is sent again without the validators the cache added, as the client
+    /// didn't ask for a 304.
+    ///
+    /// # Arguments:
+    /// * `request` - The CacheRequest returned when the request was looked up.
+    /// * `response` - The upstream server's response.
+    /// * `resend` - Sends a request to the upstream server again.
+    pub async fn complete<F, R>(
+        &self,
+        mut request: CacheRequest,
+        mut response: Response<ProxyBody>,
+        resend: F,
+    ) -> Result<(Response<ProxyBody>, ProxyCacheStatus), ForwardError>
+    where
+        F: FnOnce(Request<ProxyBody>) -> R,
+        R: Future<Output = Result<Response<ProxyBody>, ForwardError>>,
+    {
+        if let Some(stale) = request.stale.take() {
+            if response.status() == StatusCode::NOT_MODIFIED {
+                if let Some(response) = self.refresh(&stale, response.headers()).await {
+                    return Ok((response, ProxyCacheStatus::Revalidated));
+                }
+
+                self.state.lock().unwrap().remove(&stale.id);
+                request.headers.remove(IF_NONE_MATCH);
+                request.headers.remove(IF_MODIFIED_SINCE);
+
+                let mut unconditional = Request::new(empty());
+                *unconditional.uri_mut() = request.url.parse()?;
+                *unconditional.headers_mut() = request.headers.clone();
+                response = resend(unconditional).await?;
+            }
+        }
+
+        self.state.lock().unwrap().misses += 1;
+
+        let Some(entry) = self.storable(&request, &response) else {
+            return Ok((response, ProxyCacheStatus::Miss));
+        };
+
+        let cache = self.clone();
+        let limit = self.state.lock().unwrap().settings.max_size_bytes();
+        let response = response.map(|body| {
+            CacheBody::new(body, limit, move |body| {
+                tokio::spawn(async move { cache.store(entry, body).await });
+            })
+            .boxed()
+        });
+
+        Ok((response, ProxyCacheStatus::Miss))
+    }
+
+    /// Returns the entry to store for a response, or None if it may not be cached.
+    ///
+    /// # Arguments:
+    /// * `request` - The CacheRequest the response answers.
+    /// * `response` - The upstream server's response.
+    fn storable(
+        &self,
+        request: &CacheRequest,
+        response: &Response<ProxyBody>,
+    ) -> Option<ProxyCacheEntry> {
+        let headers = response.headers();
+        let directives = cache_control(headers);
+
+        if !CACHEABLE_STATUSES.contains(&response.status().as_u16())
+            || directives.contains_key("no-store")
+            || directives.contains_key("private")
+        {
+            return None;
+        }
+
+        // A Vary of * means no two requests can be assumed to get the same response
+        let vary_names = header_values(headers, VARY.as_str());
+        if vary_names.split(',').any(|name| name.trim() == "*") {
+            return None;
+        }
+
+        let vary: Vec<(String, String)> = vary_names
+            .split(',')
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = header_values(&request.headers, &name);
+                (name, value)
+            })
+            .collect();
+
+        let lifetime = freshness_lifetime(headers);
+        let has_validator = headers.contains_key(ETAG) || headers.contains_key(LAST_MODIFIED);
+        if lifetime == 0 && !has_validator {
+            return None;
+        }
+
+        Some(ProxyCacheEntry {
+            id: entry_id(&request.url, &vary),
+            url: request.url.clone(),
+            status: response.status().as_u16(),
+            headers: stored_headers(headers),
+            vary,
+            size: 0,
+            stored: Local::now(),
+            age: header_values(headers, AGE.as_str())
+                .parse()
+                .unwrap_or_default(),
+            lifetime,
+            revalidate: directives.contains_key("no-cache"),
+            last_used: 0,
+        })
+    }
+
+    /// Writes a response to disk and adds it to the index, evicting older entries if the cache is full.
+    ///
+    /// # Arguments:
+    /// * `entry` - The ProxyCacheEntry describing the response.
+    /// * `body` - The response's complete body.
+    async fn store(&self, mut entry: ProxyCacheEntry, body: Bytes) {
+        entry.size = body.len() as u64;
+        let directory = self.state.lock().unwrap().settings.directory();
+
+        let written = async {
+            tokio::fs::create_dir_all(&directory).await?;
+            tokio::fs::write(body_path(&directory, &entry.id), &body).await?;
+            tokio::fs::write(
+                meta_path(&directory, &entry.id),
+                serde_json::to_vec(&entry).unwrap_or_default(),
+            )
+            .await
+        };
+
+        if written.await.is_err() {
+            remove_entry_files(directory, vec![entry.id]).await;
+            return;
+        }
+
+        let removed = {
+            let mut state = self.state.lock().unwrap();
+            let id = entry.id.clone();
+
+            // The files of a replaced entry with the same identifier were just written over
+            let mut removed: Vec<String> = state
+                .insert(entry)
+                .into_iter()
+                .filter(|replaced| *replaced != id)
+                .collect();
+            removed.extend(state.evict());
+            removed
+        };
+
+        remove_entry_files(directory, removed).await;
+    }
+
+    /// Updates a stored response with the headers of a 304 answer, returning the response to send the client.
+    ///
+    /// # Arguments:
+    /// * `stale` - The ProxyCacheEntry that was revalidated.
+    /// * `headers` - The headers of the 304 answer.
+    async fn refresh(
+        &self,
+        stale: &ProxyCacheEntry,
+        headers: &HeaderMap,
+    ) -> Option<Response<ProxyBody>> {
+        let directory = self.state.lock().unwrap().settings.directory();
+        let body = tokio::fs::read(body_path(&directory, &stale.id))
+            .await
+            .ok()?;
+
+        // Headers sent with the 304 replace the stored ones of the same name
+        let mut merged = HeaderMap::new();
+        for (name, value) in &stale.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                merged.append(name, value);
+            }
+        }
+        for name in headers.keys() {
+            merged.remove(name);
+        }
+        for (name, value) in headers {
+            merged.append(name, value.clone());
+        }
+
+        let entry = ProxyCacheEntry {
+            headers: stored_headers(&merged),
+            stored: Local::now(),
+            age: header_values(headers, AGE.as_str())
+                .parse()
+                .unwrap_or_default(),
+            lifetime: freshness_lifetime(&merged),
+            revalidate: cache_control(&merged).contains_key("no-cache"),
+            ..stale.clone()
+        };
+
+        let _ = tokio::fs::write(
+            meta_path(&directory, &entry.id),
+            serde_json::to_vec(&entry).unwrap_or_default(),
+        )
+        .await;
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(stored) = state
+            .entries
+            .get_mut(&entry.url)
+            .and_then(|entries| entries.iter_mut().find(|stored| stored.id == entry.id))
+        {
+            *stored = ProxyCacheEntry {
+                last_used: stored.last_used,
+                ..entry.clone()
+            };
+        }
+        state.revalidated += 1;
+
+        // The user's own headers sent with the 304 are still meant for them
+        let mut response = entry_response(&entry, body);
+        for (name, value) in headers {
+            if PRIVATE_HEADERS.contains(&name.as_str()) {
+                response.headers_mut().append(name, value.clone());
+            }
+        }
+
+        Some(response)
+    }
+
+    /// Returns every stored response, the most recently used first.
+    pub fn entries(&self) -> Vec<ProxyCacheEntry> {
+        let state = self.state.lock().unwrap();
+        let mut entries: Vec<ProxyCacheEntry> = state.entries.values().flatten().cloned().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+        entries
+    }
+
+    /// Returns the current statistics of the cache.
+    pub fn stats(&self) -> ProxyCacheStats {
+        let state = self.state.lock().unwrap();
+
+        ProxyCacheStats {
+            entries: state.entries.values().map(Vec::len).sum(),
+            size: state.size,
+            hits: state.hits,
+            misses: state.misses,
+            revalidated: state.revalidated,
+        }
+    }
+
+    /// Removes a stored response.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the ProxyCacheEntry to remove.
+    pub fn remove(&self, id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.remove(id).is_some() {
+            remove_files(&state.settings.directory(), id);
+        }
+    }
+
+    /// Removes every stored response.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        let directory = state.settings.directory();
+
+        for entry in state.entries.values().flatten() {
+            remove_files(&directory, &entry.id);
+        }
+
+        state.entries.clear();
+        state.size = 0;
+    }
+}
+
+/// Creates the response to send for a stored entry.
+///
+/// # Arguments:
+/// * `entry` - The stored ProxyCacheEntry.
+/// * `body` - The entry's body, read from disk.
+fn entry_response(entry: &ProxyCacheEntry, body: Vec<u8>) -> Response<ProxyBody> {
+    let mut response = Response::new(full(body));
+    *response.status_mut() = StatusCode::from_u16(entry.status).unwrap_or(StatusCode::OK);
+
+    for (name, value) in &entry.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            response.headers_mut().append(name, value);
+        }
+    }
+
+    response
+        .headers_mut()
+        .insert(AGE, HeaderValue::from(entry.current_age()));
+
+    response
+}
+
+/// Returns the directives of the Cache-Control headers, along with their values.
+///
+/// # Arguments:
+/// * `headers` - The headers to read the directives from.
+fn cache_control(headers: &HeaderMap) -> HashMap<String, Option<String>> {
+    header_values(headers, CACHE_CONTROL.as_str())
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (
+                name.trim().to_ascii_lowercase(),
+                Some(value.trim().trim_matches('"').to_string()),
+            ),
+            None => (directive.to_ascii_lowercase(), None),
+        })
+        .collect()
+}
+
+/// Returns how long, in seconds, a response stays fresh for, from its Cache-Control or Expires headers.
+///
+/// # Arguments:
+/// * `headers` - The response's headers.
+fn freshness_lifetime(headers: &HeaderMap) -> u64 {
+    let directives = cache_control(headers);
+
+    // The Proxy is a shared cache, so s-maxage takes priority over max-age
+    if let Some(max_age) = directives
+        .get("s-maxage")
+        .or(directives.get("max-age"))
+        .and_then(|value| value.as_deref()?.parse().ok())
+    {
+        return max_age;
+    }
+
+    let parse_date = |name: &HeaderName| {
+        DateTime::parse_from_rfc2822(&header_values(headers, name.as_str()))
+            .ok()
+            .map(|date| date.with_timezone(&Utc))
+    };
+
+    match parse_date(&EXPIRES) {
+        Some(expires) => {
+            let date = parse_date(&DATE).unwrap_or_else(Utc::now);
+            (expires - date).num_seconds().max(0) as u64
+        }
+        None => 0,
+    }
+}
+
+/// Returns every value of a header, joined with commas.
+///
+/// # Arguments:
+/// * `headers` - The headers to read from.
+/// * `name` - The name of the header.
+fn header_values(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the headers of a response that should be stored with it.
+///
+/// # Arguments:
+/// * `headers` - The response's headers.
+fn stored_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            !HOP_BY_HOP_HEADERS.contains(&name.as_str())
+                && !PRIVATE_HEADERS.contains(&name.as_str())
+                && *name != AGE
+        })
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Returns the identifier of an entry, which names its files.
+///
+/// It's a 64-bit FNV-1a hash, which unlike the standard library's hasher stays the same across Rust releases.
+///
+/// # Arguments:
+/// * `url` - The URL of the stored response.
+/// * `vary` - The request header values the response was stored for.
+fn entry_id(url: &str, vary: &[(String, String)]) -> String {
+    let fields = std::iter::once(url).chain(
+        vary.iter()
+            .flat_map(|(name, value)| [name.as_str(), value.as_str()]),
+    );
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in fields {
+        // Each field ends with a 0 byte, so text moved from one field to the next changes the hash
+        for byte in field.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    format!("{:016x}", hash)
+}
+
+fn body_path(directory: &Path, id: &str) -> PathBuf {
+    directory.join(format!("{}.body", id))
+}
+
+fn meta_path(directory: &Path, id: &str) -> PathBuf {
+    directory.join(format!("{}.json", id))
+}
+
+/// Removes the files of a stored entry.
+///
+/// # Arguments:
+/// * `directory` - The cache's directory.
+/// * `id` - The identifier of the entry.
+fn remove_files(directory: &Path, id: &str) {
+    let _ = std::fs::remove_file(body_path(directory, id));
+    let _ = std::fs::remove_file(meta_path(directory, id));
+}
+
+/// Removes the files of stored entries, without blocking the runtime.
+///
+/// # Arguments:
+/// * `directory` - The cache's directory.
+/// * `ids` - The identifiers of the entries.
+async fn remove_entry_files(directory: PathBuf, ids: Vec<String>) {
+    if ids.is_empty() {
+        return;
+    }
+
+    let _ = tokio::task::spawn_blocking(move || {
+        for id in &ids {
+            remove_files(&directory, id);
+        }
+    })
+    .await;
+}
+
+/// Returns the entries stored in a cache directory, oldest first, skipping any without a body.
+///
+/// # Arguments:
+/// * `directory` - The cache's directory.
+fn load_entries(directory: &Path) -> Vec<ProxyCacheEntry> {
+    let Ok(files) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<ProxyCacheEntry> = files
+        .flatten()
+        .filter(|file| {
+            file.path()
+                .extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|file| serde_json::from_slice(&std::fs::read(file.path()).ok()?).ok())
+        .filter(|entry: &ProxyCacheEntry| body_path(directory, &entry.id).exists())
+        .collect();
+
+    // Entries stored most recently are treated as the most recently used, as they're indexed in this order
+    entries.sort_by_key(|entry| entry.stored);
+
+    entries
+}
+
+type CacheCallback = Box<dyn FnOnce(Bytes) + Send + Sync>;
+
+/// A body that keeps a copy of its data, handing it to a callback if it finishes within the size limit.
+struct CacheBody<B> {
+    inner: B,
+    limit: u64,
+    data: Option<Vec<u8>>,
+    on_complete: Option<CacheCallback>,
+}
+
+impl<B> CacheBody<B> {
+    /// Creates a new CacheBody wrapping the given body.
+    ///
+    /// # Arguments:
+    /// * `inner` - The body to wrap.
+    /// * `limit` - The most bytes to keep, larger bodies aren't handed to the callback.
+    /// * `on_complete` - The callback to run with the complete body.
+    fn new(inner: B, limit: u64, on_complete: impl FnOnce(Bytes) + Send + Sync + 'static) -> Self {
+        Self {
+            inner,
+            limit,
+            data: Some(Vec::new()),
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+
+    /// Hands the kept data to the callback, unless the body was too large or failed.
+    fn complete(&mut self) {
+        if let (Some(data), Some(on_complete)) = (self.data.take(), self.on_complete.take()) {
+            on_complete(Bytes::from(data));
+        }
+    }
+}
+
+impl<B> Body for CacheBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = ready!(Pin::new(&mut self.inner).poll_frame(cx));
+
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    let limit = self.limit;
+                    let kept = self.data.as_ref().map_or(0, Vec::len) + data.len();
+
+                    // Bodies larger than the limit are passed through without being kept
+                    match kept as u64 > limit {
+                        true => self.data = None,
+                        false => {
+                            if let Some(kept) = self.data.as_mut() {
+                                kept.extend_from_slice(data);
+                            }
+                        }
+                    }
+                }
+            }
+            Some(Err(_)) => self.data = None,
+            None => self.complete(),
+        }
+
+        // Bodies of a known length aren't polled again once their last frame has been read
+        if self.inner.is_end_stream() {
+            self.complete();
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "http://example.com/page";
+
+    /// Returns a cache that stores its responses in a directory of its own, which is emptied first.
+    fn test_cache(name: &str) -> (HttpCache, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("a-bean-sieve-cache-test-{}", name));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let cache = HttpCache::default();
+        cache.state.lock().unwrap().settings = ProxyCacheSettings {
+            enabled: true,
+            directory: directory.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        (cache, directory)
+    }
+
+    /// Returns an entry for the test URL, stored now.
+    fn test_entry(
+        vary: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        lifetime: u64,
+    ) -> ProxyCacheEntry {
+        let vary: Vec<(String, String)> = vary
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        ProxyCacheEntry {
+            id: entry_id(URL, &vary),
+            url: URL.to_string(),
+            status: 200,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            vary,
+            size: 4,
+            stored: Local::now(),
+            age: 0,
+            lifetime,
+            revalidate: false,
+            last_used: 0,
+        }
+    }
+
+    /// Adds an entry to a cache, writing its body to the cache's directory.
+    fn store_entry(cache: &HttpCache, directory: &Path, entry: ProxyCacheEntry) {
+        std::fs::write(body_path(directory, &entry.id), "body").unwrap();
+        cache.state.lock().unwrap().insert(entry);
+    }
+
+    /// Returns a GET request for the test URL with the given headers.
+    fn test_request(headers: &[(&str, &str)]) -> Request<ProxyBody> {
+        let mut request = Request::get(URL).body(empty()).unwrap();
+        for (name, value) in headers {
+            request.headers_mut().append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        request
+    }
+
+    /// Returns a response with the given status and headers, and an empty body.
+    fn test_response(status: u16, headers: &[(&str, &str)]) -> Response<ProxyBody> {
+        let mut response = Response::builder().status(status).body(empty()).unwrap();
+        for (name, value) in headers {
+            response.headers_mut().append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        response
+    }
+
+    /// Returns a header map with the given headers.
+    fn header_map(headers: &[(&str, &str)]) -> HeaderMap {
+        test_response(200, headers).headers().clone()
+    }
+
+    #[test]
+    fn keys_entries_by_url_and_vary_values() {
+        let gzip = vec![(String::from("accept-encoding"), String::from("gzip"))];
+        let br = vec![(String::from("accept-encoding"), String::from("br"))];
+
+        assert_eq!(entry_id(URL, &gzip), entry_id(URL, &gzip));
+        assert_ne!(entry_id(URL, &gzip), entry_id(URL, &br));
+        assert_ne!(entry_id(URL, &gzip), entry_id(URL, &[]));
+        assert_ne!(
+            entry_id(URL, &[]),
+            entry_id("http://example.com/other", &[])
+        );
+
+        // Moving text between fields changes the identifier
+        let split = |name: &str, value: &str| vec![(name.to_string(), value.to_string())];
+        assert_ne!(
+            entry_id(URL, &split("ab", "c")),
+            entry_id(URL, &split("a", "bc"))
+        );
+    }
+
+    #[test]
+    fn looks_up_the_entry_stored_for_the_request_headers() {
+        let (cache, directory) = test_cache("vary");
+        store_entry(
+            &cache,
+            &directory,
+            test_entry(&[("accept-encoding", "gzip")], &[], 60),
+        );
+
+        let gzip = cache.lookup(&mut test_request(&[("Accept-Encoding", "gzip")]));
+        assert!(matches!(gzip, CacheLookup::Hit(_)));
+
+        let br = cache.lookup(&mut test_request(&[("Accept-Encoding", "br")]));
+        assert!(matches!(
+            br,
+            CacheLookup::Forward(CacheRequest { stale: None, .. })
+        ));
+
+        let missing = cache.lookup(&mut test_request(&[]));
+        assert!(matches!(
+            missing,
+            CacheLookup::Forward(CacheRequest { stale: None, .. })
+        ));
+
+        let credentials = cache.lookup(&mut test_request(&[
+            ("Accept-Encoding", "gzip"),
+            ("Authorization", "Basic dXNlcg=="),
+        ]));
+        assert!(matches!(credentials, CacheLookup::Bypass));
+
+        let mut post = test_request(&[("Accept-Encoding", "gzip")]);
+        *post.method_mut() = Method::POST;
+        assert!(matches!(cache.lookup(&mut post), CacheLookup::Bypass));
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn reads_the_freshness_lifetime() {
+        let cases = [
+            (vec![("Cache-Control", "max-age=60")], 60),
+            (vec![("Cache-Control", "max-age=60, s-maxage=120")], 120),
+            (
+                vec![
+                    ("Date", "Mon, 06 May 2024 10:00:00 GMT"),
+                    ("Expires", "Mon, 06 May 2024 10:01:30 GMT"),
+                ],
+                90,
+            ),
+            (
+                vec![
+                    ("Date", "Mon, 06 May 2024 10:00:00 GMT"),
+                    ("Expires", "Mon, 06 May 2024 09:00:00 GMT"),
+                ],
+                0,
+            ),
+            (
+                vec![
+                    ("Cache-Control", "max-age=30"),
+                    ("Expires", "Mon, 06 May 2024 10:01:30 GMT"),
+                ],
+                30,
+            ),
+            (vec![], 0),
+        ];
+
+        for (headers, lifetime) in cases {
+            assert_eq!(
+                freshness_lifetime(&header_map(&headers)),
+                lifetime,
+                "{:?}",
+                headers
+            );
+        }
+
+        let mut entry = test_entry(&[], &[], 60);
+        entry.age = 30;
+        assert!(entry.is_fresh());
+
+        entry.age = 60;
+        assert!(!entry.is_fresh());
+
+        entry.age = 0;
+        entry.revalidate = true;
+        assert!(!entry.is_fresh());
+    }
+
+    #[test]
+    fn only_stores_cacheable_responses() {
+        let (cache, directory) = test_cache("storable");
+        let request = CacheRequest {
+            url: URL.to_string(),
+            headers: header_map(&[("Accept-Encoding", "gzip")]),
+            stale: None,
+        };
+
+        let cases = [
+            (200, vec![("Cache-Control", "max-age=60")], true),
+            (200, vec![("ETag", "\"v1\"")], true),
+            (200, vec![], false),
+            (500, vec![("Cache-Control", "max-age=60")], false),
+            (200, vec![("Cache-Control", "max-age=60, no-store")], false),
+            (200, vec![("Cache-Control", "private, max-age=60")], false),
+            (
+                200,
+                vec![("Cache-Control", "max-age=60"), ("Vary", "*")],
+                false,
+            ),
+        ];
+
+        for (status, headers, storable) in cases {
+            let entry = cache.storable(&request, &test_response(status, &headers));
+            assert_eq!(entry.is_some(), storable, "{} {:?}", status, headers);
+        }
+
+        let entry = cache
+            .storable(
+                &request,
+                &test_response(
+                    200,
+                    &[
+                        ("Cache-Control", "max-age=60"),
+                        ("Vary", "Accept-Encoding"),
+                        ("Set-Cookie", "session=1"),
+                        ("Connection", "keep-alive"),
+                    ],
+                ),
+            )
+            .unwrap();
+        assert_eq!(
+            entry.vary,
+            [(String::from("accept-encoding"), String::from("gzip"))]
+        );
+        assert_eq!(entry.id, entry_id(URL, &entry.vary));
+        assert_eq!(
+            entry.headers,
+            [
+                (String::from("cache-control"), String::from("max-age=60")),
+                (String::from("vary"), String::from("Accept-Encoding")),
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[tokio::test]
+    async fn revalidates_a_stale_entry_with_a_304() {
+        let (cache, directory) = test_cache("revalidate");
+        store_entry(
+            &cache,
+            &directory,
+            test_entry(
+                &[],
+                &[("etag", "\"v1\""), ("content-type", "text/plain")],
+                0,
+            ),
+        );
+
+        let mut request = test_request(&[]);
+        let CacheLookup::Forward(forward) = cache.lookup(&mut request) else {
+            panic!("a stale entry must be revalidated");
+        };
+        assert!(forward.stale.is_some());
+        assert_eq!(request.headers()[IF_NONE_MATCH], "\"v1\"");
+
+        let not_modified = test_response(
+            304,
+            &[("Cache-Control", "max-age=60"), ("Set-Cookie", "session=1")],
+        );
+        let (response, status) = cache
+            .complete(forward, not_modified, |_| async {
+                panic!("a revalidated entry is not requested again")
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(status, ProxyCacheStatus::Revalidated);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CACHE_CONTROL], "max-age=60");
+        assert_eq!(response.headers()[ETAG], "\"v1\"");
+        assert_eq!(response.headers()["set-cookie"], "session=1");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "body");
+
+        // The refreshed entry is fresh again, and doesn't keep the user's cookie
+        let CacheLookup::Hit(entry) = cache.lookup(&mut test_request(&[])) else {
+            panic!("a refreshed entry must be fresh");
+        };
+        assert_eq!(entry.lifetime, 60);
+        assert!(entry.headers.iter().all(|(name, _)| name != "set-cookie"));
+        assert_eq!(cache.stats().revalidated, 1);
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[tokio::test]
+    async fn resends_a_revalidated_request_when_the_body_is_gone() {
+        let (cache, directory) = test_cache("resend");
+        store_entry(
+            &cache,
+            &directory,
+            test_entry(&[], &[("etag", "\"v1\"")], 0),
+        );
+        std::fs::remove_file(body_path(&directory, &entry_id(URL, &[]))).unwrap();
+
+        let CacheLookup::Forward(forward) = cache.lookup(&mut test_request(&[])) else {
+            panic!("a stale entry must be revalidated");
+        };
+
+        let (response, status) = cache
+            .complete(forward, test_response(304, &[]), |request| async move {
+                assert!(!request.headers().contains_key(IF_NONE_MATCH));
+                Ok(test_response(200, &[]))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(status, ProxyCacheStatus::Miss);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(cache.stats().entries, 0);
+
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entries() {
+        let mut state = CacheState {
+            settings: ProxyCacheSettings {
+                max_size: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let entry = |url: &str| ProxyCacheEntry {
+            id: entry_id(url, &[]),
+            url: url.to_string(),
+            size: 400 * 1024,
+            ..test_entry(&[], &[], 60)
+        };
+
+        state.insert(entry("http://example.com/a"));
+        state.insert(entry("http://example.com/b"));
+        state.touch(
+            "http://example.com/a",
+            &entry_id("http://example.com/a", &[]),
+        );
+        state.insert(entry("http://example.com/c"));
+
+        assert_eq!(state.evict(), [entry_id("http://example.com/b", &[])]);
+        assert_eq!(state.size, 800 * 1024);
+
+        // Storing a response again replaces the entry rather than adding to the size
+        let replaced = state.insert(entry("http://example.com/c"));
+        assert_eq!(replaced, [entry_id("http://example.com/c", &[])]);
+        assert_eq!(state.size, 800 * 1024);
+        assert!(state.evict().is_empty());
+
+        state.insert(entry("http://example.com/d"));
+        assert_eq!(state.evict(), [entry_id("http://example.com/a", &[])]);
+    }
+}
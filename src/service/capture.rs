@@ -0,0 +1,120 @@
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The maximum number of bytes kept from a captured body.
+pub const BODY_CAPTURE_LIMIT: usize = 64 * 1024;
+
+/// Contains the information regarding a request or response body.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProxyBodyLog {
+    pub size: u64,
+    pub preview: Option<Bytes>,
+    pub truncated: bool,
+}
+
+type CaptureCallback = Box<dyn FnOnce(ProxyBodyLog) + Send + Sync>;
+
+/// A body that counts, and optionally keeps, the data streamed through it.
+///
+/// The callback is run once the body has finished, failed or been dropped.
+pub struct CaptureBody<B> {
+    inner: B,
+    capture: bool,
+    size: u64,
+    preview: Vec<u8>,
+    truncated: bool,
+    on_complete: Option<CaptureCallback>,
+}
+
+impl<B> CaptureBody<B> {
+    /// Creates a new CaptureBody wrapping the given body.
+    ///
+    /// # Arguments:
+    /// * `inner` - The body to wrap.
+    /// * `capture` - Whether to keep a preview of the body's data.
+    /// * `on_complete` - The callback to run with the body's information once it has finished.
+    pub fn new(
+        inner: B,
+        capture: bool,
+        on_complete: impl FnOnce(ProxyBodyLog) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner,
+            capture,
+            size: 0,
+            preview: Vec::new(),
+            truncated: false,
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+
+    /// Records a chunk of data passing through the body.
+    fn record(&mut self, data: &Bytes) {
+        self.size += data.len() as u64;
+
+        if self.capture {
+            let remaining = BODY_CAPTURE_LIMIT.saturating_sub(self.preview.len());
+            self.truncated |= data.len() > remaining;
+            self.preview
+                .extend_from_slice(&data[..data.len().min(remaining)]);
+        }
+    }
+
+    /// Runs the completion callback, if it hasn't been run already.
+    fn complete(&mut self) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(ProxyBodyLog {
+                size: self.size,
+                preview: self
+                    .capture
+                    .then(|| Bytes::from(std::mem::take(&mut self.preview))),
+                truncated: self.truncated,
+            });
+        }
+    }
+}
+
+impl<B> Body for CaptureBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+
+        match &frame {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    this.record(data);
+                }
+            }
+            Poll::Ready(Some(Err(_))) | Poll::Ready(None) => this.complete(),
+            Poll::Pending => {}
+        }
+
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<B> Drop for CaptureBody<B> {
+    fn drop(&mut self) {
+        self.complete();
+    }
+}
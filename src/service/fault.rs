@@ -0,0 +1,304 @@
+use super::{
+    forward::{full, ProxyBody},
+    throttle::reset_response,
+    traffic_filter::TrafficRuleKind,
+};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body, Bytes, Frame, SizeHint},
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    Response, StatusCode, Uri,
+};
+use rand::Rng;
+use regex::Regex;
+use std::{
+    pin::Pin,
+    sync::OnceLock,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+/// The enum that controls which fault a rule injects.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum FaultKind {
+    /// Answer with an error status instead of forwarding the request.
+    #[default]
+    Status,
+    /// End the response body early.
+    Truncate,
+    /// Hold back the response headers.
+    Delay,
+    /// Reset the connection part way through the response body.
+    Close,
+}
+
+impl std::fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaultKind::Status => write!(f, "Error Status"),
+            FaultKind::Truncate => write!(f, "Truncate Body"),
+            FaultKind::Delay => write!(f, "Delay Headers"),
+            FaultKind::Close => write!(f, "Close Connection"),
+        }
+    }
+}
+
+/// A rule that injects a fault into a share of the requests matching its pattern.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct FaultRule {
+    pub enabled: bool,
+    pub pattern: String,
+    pub kind: TrafficRuleKind,
+    pub fault: FaultKind,
+    /// The percentage of matching requests to inject the fault into.
+    pub percentage: u8,
+    /// The most faults to inject, or None for no limit.
+    pub limit: Option<u32>,
+    /// The number of faults injected so far.
+    pub injected: u32,
+    pub status: u16,
+    /// Seconds sent in the Retry-After header of an error status, or 0 to leave it out.
+    pub retry_after: u64,
+    /// Bytes of the response body sent before it's truncated or the connection is closed.
+    pub bytes: u64,
+    /// Milliseconds the response headers are delayed by.
+    pub delay: u64,
+    #[serde(skip)]
+    compiled: OnceLock<Option<Regex>>,
+}
+
+impl Default for FaultRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pattern: String::default(),
+            kind: TrafficRuleKind::default(),
+            fault: FaultKind::default(),
+            percentage: 100,
+            limit: None,
+            injected: 0,
+            status: 503,
+            retry_after: 30,
+            bytes: 1024,
+            delay: 5000,
+            compiled: OnceLock::new(),
+        }
+    }
+}
+
+impl PartialEq for FaultRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.enabled == other.enabled
+            && self.pattern == other.pattern
+            && self.kind == other.kind
+            && self.fault == other.fault
+            && self.percentage == other.percentage
+            && self.limit == other.limit
+            && self.injected == other.injected
+            && self.status == other.status
+            && self.retry_after == other.retry_after
+            && self.bytes == other.bytes
+            && self.delay == other.delay
+    }
+}
+
+impl FaultRule {
+    /// Returns the compiled pattern of a Regex rule, or None if it isn't a valid regular expression.
+    fn regex(&self) -> Option<&Regex> {
+        self.compiled
+            .get_or_init(|| Regex::new(&self.pattern).ok())
+            .as_ref()
+    }
+
+    /// Returns an error message if the rule's pattern or status is invalid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind == TrafficRuleKind::Regex {
+            Regex::new(&self.pattern).map_err(|error| error.to_string())?;
+        }
+
+        if self.fault == FaultKind::Status {
+            StatusCode::from_u16(self.status)
+                .map_err(|_| format!("Invalid status code: {}", self.status))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the rule applies to the URI.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI being requested.
+    pub fn matches(&self, uri: &str) -> bool {
+        if !self.enabled || self.pattern.is_empty() {
+            return false;
+        }
+
+        match self.kind {
+            TrafficRuleKind::Contains => uri.contains(&self.pattern),
+            TrafficRuleKind::Regex => self.regex().is_some_and(|regex| regex.is_match(uri)),
+        }
+    }
+
+    /// Replaces the rule's settings with those of an edited copy, keeping the faults injected since it was copied.
+    ///
+    /// # Arguments:
+    /// * `rule` - The edited FaultRule.
+    pub fn update(&mut self, rule: FaultRule) {
+        let injected = self.injected;
+        *self = rule;
+        self.injected = injected;
+    }
+
+    /// Returns whether the rule has injected as many faults as it's allowed to.
+    pub fn exhausted(&self) -> bool {
+        self.limit.is_some_and(|limit| self.injected >= limit)
+    }
+
+    /// Returns a readable description of the fault, for the request log.
+    pub fn describe(&self) -> String {
+        match self.fault {
+            FaultKind::Status => {
+                let status = StatusCode::from_u16(self.status)
+                    .map_or(self.status.to_string(), |status| status.to_string());
+
+                match self.retry_after {
+                    0 => status,
+                    retry_after => format!("{} (Retry-After: {}s)", status, retry_after),
+                }
+            }
+            FaultKind::Truncate => format!("Body truncated after {} bytes", self.bytes),
+            FaultKind::Delay => format!("Headers delayed by {}ms", self.delay),
+            FaultKind::Close => format!("Connection closed after {} bytes", self.bytes),
+        }
+    }
+}
+
+/// Returns the rule to inject a fault from for the URI, counting it against the rule's limit.
+///
+/// # Arguments:
+/// * `rules` - The fault rules to check, which are updated with the injected fault.
+/// * `uri` - The URI being requested.
+pub fn pick_fault(rules: &mut [FaultRule], uri: &Uri) -> Option<FaultRule> {
+    let uri = uri.to_string();
+
+    let rule = rules.iter_mut().find(|rule| {
+        rule.matches(&uri)
+            && !rule.exhausted()
+            && rule.validate().is_ok()
+            && rand::thread_rng().gen_range(0..100) < rule.percentage
+    })?;
+
+    rule.injected += 1;
+    Some(rule.clone())
+}
+
+/// Returns the response to answer with instead of forwarding the request, if the fault doesn't need one.
+///
+/// # Arguments:
+/// * `rule` - The rule injecting the fault.
+pub fn fault_response(rule: &FaultRule) -> Option<Response<ProxyBody>> {
+    if rule.fault != FaultKind::Status {
+        return None;
+    }
+
+    let status = StatusCode::from_u16(rule.status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+
+    let mut response = Response::new(full(format!("Fault injected by the Proxy: {}", status)));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "text/plain; charset=utf-8".parse().unwrap());
+
+    if rule.retry_after > 0 {
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, rule.retry_after.into());
+    }
+
+    Some(response)
+}
+
+/// Injects a fault into an upstream server's response.
+///
+/// # Arguments:
+/// * `rule` - The rule injecting the fault.
+/// * `response` - The response to inject the fault into.
+/// * `on_close` - The callback to run with a message if the connection is closed.
+pub async fn inject_fault(
+    rule: &FaultRule,
+    response: Response<ProxyBody>,
+    on_close: impl FnOnce(String) + Send + Sync + 'static,
+) -> Response<ProxyBody> {
+    match rule.fault {
+        FaultKind::Status => response,
+        FaultKind::Truncate => {
+            let bytes = rule.bytes;
+            response.map(|body| TruncatedBody::new(body, bytes).boxed())
+        }
+        FaultKind::Delay => {
+            tokio::time::sleep(Duration::from_millis(rule.delay)).await;
+            response
+        }
+        FaultKind::Close => reset_response(
+            response,
+            rule.bytes,
+            format!(
+                "Connection closed by an injected fault after {} bytes",
+                rule.bytes
+            ),
+            on_close,
+        ),
+    }
+}
+
+/// A body that ends once it has sent a number of bytes, leaving the rest unsent.
+struct TruncatedBody<B> {
+    inner: B,
+    remaining: u64,
+}
+
+impl<B> TruncatedBody<B> {
+    fn new(inner: B, remaining: u64) -> Self {
+        Self { inner, remaining }
+    }
+}
+
+impl<B> Body for TruncatedBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if self.remaining == 0 {
+            return Poll::Ready(None);
+        }
+
+        let frame = ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        Poll::Ready(frame.map(|frame| {
+            frame.map(|frame| {
+                frame.map_data(|mut data| {
+                    let kept = data.len().min(self.remaining as usize);
+                    self.remaining -= kept as u64;
+                    data.truncate(kept);
+                    data
+                })
+            })
+        }))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0 || self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = SizeHint::new();
+        hint.set_upper(self.remaining);
+        hint
+    }
+}
@@ -0,0 +1,309 @@
+use super::{
+    pool::{ConnectionPool, PoolKey},
+    settings::{ProxyHttpVersion, ProxySettings, ProxyTimeouts},
+    timeout::{with_timeout, ProxyTimeout},
+};
+use crate::utils::logger::Logger;
+use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
+use hyper::{
+    body::{Body, Bytes},
+    client::conn::{http1, http2},
+    header, Request, Response, Uri, Version,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// The body type used for every request and response passing through the Proxy.
+pub type ProxyBody = BoxBody<Bytes, hyper::Error>;
+
+/// The error type returned when a request can't be forwarded.
+pub type ForwardError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The settings forwarding a request depends on, copied out of the ProxySettings so they aren't locked meanwhile.
+#[derive(Clone, Debug)]
+pub struct ForwardSettings {
+    pub http_version: ProxyHttpVersion,
+    pub timeouts: ProxyTimeouts,
+    /// How long an upstream connection may be idle before it's closed.
+    pub pool_idle_timeout: Duration,
+    pub pool_max_connections: usize,
+}
+
+impl From<&ProxySettings> for ForwardSettings {
+    fn from(settings: &ProxySettings) -> Self {
+        Self {
+            http_version: settings.http_version,
+            timeouts: settings.timeouts.clone(),
+            pool_idle_timeout: settings.pool_idle_timeout(),
+            pool_max_connections: settings.pool_max_connections,
+        }
+    }
+}
+
+/// Forward a request to its upstream server, returning the server's response.
+///
+/// # Arguments:
+/// * `request` - The request to forward, its URI must contain a host.
+/// * `settings` - The current ForwardSettings, controlling the HTTP version and connection pooling.
+/// * `pool` - The ConnectionPool to reuse upstream connections from.
+/// * `logger` - The current logger to log connection events to.
+pub async fn forward_request(
+    request: Request<ProxyBody>,
+    settings: &ForwardSettings,
+    pool: &ConnectionPool,
+    logger: Logger,
+) -> Result<Response<ProxyBody>, ForwardError> {
+    let key = PoolKey {
+        host: request
+            .uri()
+            .host()
+            .ok_or("Request has no host")?
+            .to_string(),
+        port: request.uri().port_u16().unwrap_or(80),
+    };
+
+    let use_http2 = match settings.http_version {
+        ProxyHttpVersion::Auto => request.version() == Version::HTTP_2 && !pool.is_http1_only(&key),
+        ProxyHttpVersion::Http1 => false,
+        ProxyHttpVersion::Http2 => true,
+    };
+
+    if !use_http2 {
+        return send_http1(&key, request, settings, pool, logger).await;
+    }
+
+    // Requests without a body can be resent over HTTP/1.1 if the server doesn't speak HTTP/2
+    let auto = settings.http_version == ProxyHttpVersion::Auto;
+    let fallback = (auto && request.body().is_end_stream()).then(|| copy_request_head(&request));
+
+    match send_http2(&key, request, settings, pool, logger.clone()).await {
+        Ok(response) => Ok(response),
+        Err(message) => {
+            // Only protocol errors mean the server doesn't speak HTTP/2, rather than being unreachable
+            if auto && message.downcast_ref::<hyper::Error>().is_some() {
+                pool.set_http1_only(&key);
+            }
+
+            match fallback {
+                Some(request) => {
+                    logger.debug(
+                        format!(
+                            "{} could not be reached over HTTP/2, retrying with HTTP/1.1: {}",
+                            key, message
+                        )
+                        .as_str(),
+                    );
+                    send_http1(&key, request, settings, pool, logger).await
+                }
+                None => Err(message),
+            }
+        }
+    }
+}
+
+/// Send a request over a pooled HTTP/1.1 connection, opening a new one if none are idle.
+///
+/// # Arguments:
+/// * `key` - The server to send the request to.
+/// * `request` - The request to send.
+/// * `settings` - The current ForwardSettings.
+/// * `pool` - The ConnectionPool to reuse connections from.
+/// * `logger` - The current logger to log connection events to.
+async fn send_http1(
+    key: &PoolKey,
+    mut request: Request<ProxyBody>,
+    settings: &ForwardSettings,
+    pool: &ConnectionPool,
+    logger: Logger,
+) -> Result<Response<ProxyBody>, ForwardError> {
+    // Origin servers expect a path rather than the absolute URI sent to a proxy
+    if let Some(authority) = request.uri().authority().cloned() {
+        if !request.headers().contains_key(header::HOST) {
+            request
+                .headers_mut()
+                .insert(header::HOST, authority.as_str().parse()?);
+        }
+    }
+
+    let path = request
+        .uri()
+        .path_and_query()
+        .map_or("/", |path| path.as_str())
+        .parse::<Uri>()?;
+    *request.uri_mut() = path;
+    *request.version_mut() = Version::HTTP_11;
+
+    let connect_timeout = settings.timeouts.get(ProxyTimeout::Connect);
+    let response_timeout = settings.timeouts.get(ProxyTimeout::Response);
+
+    // Waiting for a free connection counts towards the connect timeout
+    let mut permit = with_timeout(
+        ProxyTimeout::Connect,
+        connect_timeout,
+        pool.reserve(key, settings.pool_max_connections),
+    )
+    .await?;
+
+    loop {
+        let mut connection = match pool.checkout(key, permit, settings.pool_idle_timeout) {
+            Ok(connection) => connection,
+            Err(permit) => {
+                let sender = with_timeout(
+                    ProxyTimeout::Connect,
+                    connect_timeout,
+                    connect_http1(key, logger.clone()),
+                )
+                .await??;
+
+                pool.opened(key, sender, permit)
+            }
+        };
+
+        let response = with_timeout(
+            ProxyTimeout::Response,
+            response_timeout,
+            connection.sender.try_send_request(request),
+        )
+        .await?;
+
+        match response {
+            Ok(response) => {
+                pool.release(connection);
+                return Ok(response.map(|b| b.boxed()));
+            }
+            Err(mut error) => match error.take_message() {
+                // The server closed the idle connection before the request could be written
+                Some(unsent) if connection.reused => {
+                    pool.record_unhealthy();
+                    request = unsent;
+                    permit = connection.into_permit();
+                }
+                _ => return Err(error.into_error().into()),
+            },
+        }
+    }
+}
+
+/// Send a request over a shared HTTP/2 connection, without negotiation (prior knowledge).
+///
+/// # Arguments:
+/// * `key` - The server to send the request to.
+/// * `request` - The request to send.
+/// * `settings` - The current ForwardSettings.
+/// * `pool` - The ConnectionPool to share connections from.
+/// * `logger` - The current logger to log connection events to.
+async fn send_http2(
+    key: &PoolKey,
+    mut request: Request<ProxyBody>,
+    settings: &ForwardSettings,
+    pool: &ConnectionPool,
+    logger: Logger,
+) -> Result<Response<ProxyBody>, ForwardError> {
+    // The host is sent as the :authority pseudo-header instead
+    request.headers_mut().remove(header::HOST);
+    *request.version_mut() = Version::HTTP_2;
+
+    let mut sender = match pool.shared(key, settings.pool_idle_timeout) {
+        Some(sender) => sender,
+        None => {
+            let sender = with_timeout(
+                ProxyTimeout::Connect,
+                settings.timeouts.get(ProxyTimeout::Connect),
+                connect_http2(key, logger),
+            )
+            .await??;
+
+            pool.share(key, sender.clone());
+            sender
+        }
+    };
+
+    let response = with_timeout(
+        ProxyTimeout::Response,
+        settings.timeouts.get(ProxyTimeout::Response),
+        sender.send_request(request),
+    )
+    .await??;
+
+    Ok(response.map(|b| b.boxed()))
+}
+
+/// Open a new HTTP/1.1 connection to an upstream server.
+///
+/// # Arguments:
+/// * `key` - The server to connect to.
+/// * `logger` - The current logger to log connection events to.
+async fn connect_http1(
+    key: &PoolKey,
+    logger: Logger,
+) -> Result<http1::SendRequest<ProxyBody>, ForwardError> {
+    let stream = TcpStream::connect((key.host.as_str(), key.port)).await?;
+    let io = TokioIo::new(stream);
+
+    let (sender, conn) = http1::Builder::new()
+        .preserve_header_case(true)
+        .title_case_headers(true)
+        .handshake(io)
+        .await?;
+
+    tokio::task::spawn(async move {
+        if let Err(message) = conn.await {
+            logger.warning(&message.to_string());
+        };
+    });
+
+    Ok(sender)
+}
+
+/// Open a new HTTP/2 connection to an upstream server, without negotiation (prior knowledge).
+///
+/// # Arguments:
+/// * `key` - The server to connect to.
+/// * `logger` - The current logger to log connection events to.
+async fn connect_http2(
+    key: &PoolKey,
+    logger: Logger,
+) -> Result<http2::SendRequest<ProxyBody>, ForwardError> {
+    let stream = TcpStream::connect((key.host.as_str(), key.port)).await?;
+    let io = TokioIo::new(stream);
+
+    let (sender, conn) = http2::Builder::new(TokioExecutor::new())
+        .handshake(io)
+        .await?;
+
+    tokio::task::spawn(async move {
+        if let Err(message) = conn.await {
+            logger.debug(&message.to_string());
+        };
+    });
+
+    Ok(sender)
+}
+
+/// Copy the method, URI and headers of a request that has no body.
+///
+/// # Arguments:
+/// * `request` - The request to copy.
+fn copy_request_head(request: &Request<ProxyBody>) -> Request<ProxyBody> {
+    let mut copy = Request::new(empty());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.headers_mut() = request.headers().clone();
+
+    copy
+}
+
+/// Create an empty response body.
+pub fn empty() -> ProxyBody {
+    Empty::<Bytes>::new()
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+/// Create an body from the given bytes.
+pub fn full<T: Into<Bytes>>(chunk: T) -> ProxyBody {
+    Full::new(chunk.into())
+        .map_err(|never| match never {})
+        .boxed()
+}
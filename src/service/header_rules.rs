@@ -0,0 +1,182 @@
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    HeaderMap,
+};
+
+/// The enum that controls whether a header rule applies to requests or responses.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum HeaderDirection {
+    #[default]
+    Request,
+    Response,
+}
+
+impl std::fmt::Display for HeaderDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderDirection::Request => write!(f, "Request"),
+            HeaderDirection::Response => write!(f, "Response"),
+        }
+    }
+}
+
+/// The enum that controls how a header rule changes a header.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum HeaderOperation {
+    /// Replace every value of the header with the rule's value.
+    #[default]
+    Set,
+    /// Add the rule's value, keeping any existing values.
+    Add,
+    /// Remove every value of the header.
+    Remove,
+}
+
+impl std::fmt::Display for HeaderOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderOperation::Set => write!(f, "Set"),
+            HeaderOperation::Add => write!(f, "Add"),
+            HeaderOperation::Remove => write!(f, "Remove"),
+        }
+    }
+}
+
+/// A rule that changes a header of the requests, or responses, for matching hosts.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct HeaderRule {
+    pub enabled: bool,
+    /// The host to apply the rule to, e.g. example.com, *.example.com, or * for every host.
+    pub host: String,
+    pub direction: HeaderDirection,
+    pub operation: HeaderOperation,
+    pub name: String,
+    pub value: String,
+}
+
+impl Default for HeaderRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: String::from("*"),
+            direction: HeaderDirection::default(),
+            operation: HeaderOperation::default(),
+            name: String::default(),
+            value: String::default(),
+        }
+    }
+}
+
+impl HeaderRule {
+    /// Returns whether the rule applies to the given host.
+    ///
+    /// # Arguments:
+    /// * `host` - The host a request is being sent to.
+    pub fn matches_host(&self, host: &str) -> bool {
+        host_matches(&self.host, host)
+    }
+
+    /// Returns the rule's header name and value, or an error message if they can't be sent.
+    pub fn validate(&self) -> Result<(HeaderName, HeaderValue), String> {
+        let name = HeaderName::from_bytes(self.name.trim().as_bytes())
+            .map_err(|_| format!("Invalid header name: {}", self.name))?;
+        let value = HeaderValue::from_str(&self.value)
+            .map_err(|_| format!("Invalid header value: {}", self.value))?;
+
+        Ok((name, value))
+    }
+}
+
+/// Returns whether a host pattern, e.g. example.com, *.example.com or *, matches the given host.
+///
+/// # Arguments:
+/// * `pattern` - The host pattern, an empty pattern matches every host.
+/// * `host` - The host a request is being sent to.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim();
+
+    if pattern.is_empty() || pattern == "*" {
+        return true;
+    }
+
+    match pattern.strip_prefix("*.") {
+        Some(domain) => {
+            host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+        }
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// Contains a change made to a request, or response, by a header rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyHeaderChange {
+    pub direction: HeaderDirection,
+    pub operation: HeaderOperation,
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl std::fmt::Display for ProxyHeaderChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{} {}: {}", self.operation, self.name, value),
+            None => write!(f, "{} {}", self.operation, self.name),
+        }
+    }
+}
+
+/// Applies the enabled header rules for a host, returning the changes that were made.
+///
+/// Rules with an invalid header name or value are skipped.
+///
+/// # Arguments:
+/// * `rules` - The header rules to apply.
+/// * `direction` - Whether the headers belong to a request or a response.
+/// * `host` - The host the request is being sent to.
+/// * `headers` - The headers to change.
+pub fn apply_header_rules(
+    rules: &[HeaderRule],
+    direction: HeaderDirection,
+    host: &str,
+    headers: &mut HeaderMap,
+) -> Vec<ProxyHeaderChange> {
+    let mut changes = Vec::new();
+
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.enabled && rule.direction == direction && rule.matches_host(host))
+    {
+        let Ok((name, value)) = rule.validate() else {
+            continue;
+        };
+
+        // Removing a header the request doesn't have isn't recorded as a change
+        let changed = match rule.operation {
+            HeaderOperation::Set => {
+                headers.insert(name.clone(), value.clone());
+                true
+            }
+            HeaderOperation::Add => {
+                headers.append(name.clone(), value.clone());
+                true
+            }
+            HeaderOperation::Remove => headers.remove(&name).is_some(),
+        };
+
+        if changed {
+            changes.push(ProxyHeaderChange {
+                direction,
+                operation: rule.operation,
+                name: rule.name.trim().to_string(),
+                value: (rule.operation != HeaderOperation::Remove)
+                    .then(|| String::from_utf8_lossy(value.as_bytes()).to_string()),
+            });
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,752 @@
+use super::{
+    schedule::TrafficSchedule,
+    traffic_filter::{
+        TrafficFilter, TrafficFilterType, TrafficRule, TrafficRuleAction, TrafficRuleKind,
+    },
+};
+use chrono::{DateTime, Duration, Local};
+use std::collections::HashMap;
+
+/// The most changes kept in the change log, the oldest are dropped first.
+const CHANGE_LOG_LIMIT: usize = 200;
+
+/// The most rules the stored change log keeps the diffs of, counting back from the latest change.
+///
+/// Older changes are stored without their diff, so a few large imports don't bloat the stored settings.
+const STORED_DIFF_RULES: usize = 5000;
+
+/// The most changes that can be undone.
+const UNDO_LIMIT: usize = 100;
+
+/// The most values listed in a change's before or after value, e.g. the rules of an import.
+const CHANGE_VALUE_LIMIT: usize = 10;
+
+/// The most seconds between two changes to the same schedule for them to be recorded as one.
+const MERGE_SECONDS: i64 = 5;
+
+/// The rules a change removed from, and added to, one of the exclusion lists.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct RuleListDiff {
+    /// The exclusion list that was changed.
+    pub list: TrafficFilterType,
+    /// The removed rules, along with their positions before the change.
+    pub removed: Vec<(usize, TrafficRule)>,
+    /// The positions, among the rules that weren't removed, of the rules in their new order, if they were reordered.
+    pub order: Option<Vec<usize>>,
+    /// The added rules, along with their positions after the change.
+    pub added: Vec<(usize, TrafficRule)>,
+}
+
+impl RuleListDiff {
+    /// Returns the difference between two versions of an exclusion list.
+    ///
+    /// Only the rules between those the versions start and end with are recorded.
+    ///
+    /// # Arguments:
+    /// * `list` - The exclusion list that was changed.
+    /// * `before` - The rules before the change.
+    /// * `after` - The rules after the change.
+    pub fn between(
+        list: TrafficFilterType,
+        before: Vec<TrafficRule>,
+        after: &[TrafficRule],
+    ) -> Self {
+        let start = before
+            .iter()
+            .zip(after)
+            .take_while(|(before, after)| before == after)
+            .count();
+        let end = before[start..]
+            .iter()
+            .rev()
+            .zip(after[start..].iter().rev())
+            .take_while(|(before, after)| before == after)
+            .count();
+
+        let removed_end = before.len() - end;
+        let added = after[start..after.len() - end]
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(index, rule)| (start + index, rule))
+            .collect();
+
+        Self {
+            list,
+            removed: before
+                .into_iter()
+                .enumerate()
+                .take(removed_end)
+                .skip(start)
+                .collect(),
+            order: None,
+            added,
+        }
+    }
+
+    /// Returns whether the exclusion list wasn't changed.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.order.is_none()
+    }
+
+    /// Returns the diff that undoes this one.
+    pub fn inverse(&self) -> Self {
+        let order = self.order.as_ref().map(|order| {
+            let mut inverse = vec![0; order.len()];
+            for (position, index) in order.iter().enumerate() {
+                if let Some(slot) = inverse.get_mut(*index) {
+                    *slot = position;
+                }
+            }
+            inverse
+        });
+
+        Self {
+            list: self.list,
+            removed: self.added.clone(),
+            order,
+            added: self.removed.clone(),
+        }
+    }
+
+    /// Applies the diff to the exclusion list it was recorded for.
+    ///
+    /// # Arguments:
+    /// * `rules` - The rules of the exclusion list, as they were before the change.
+    pub fn apply(&self, rules: &mut Vec<TrafficRule>) {
+        let mut removed = self.removed.iter().map(|(index, _)| *index).peekable();
+        let mut kept: Vec<TrafficRule> = std::mem::take(rules)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| match removed.peek() == Some(index) {
+                true => {
+                    removed.next();
+                    false
+                }
+                false => true,
+            })
+            .map(|(_, rule)| rule)
+            .collect();
+
+        if let Some(order) = &self.order {
+            let mut slots: Vec<Option<TrafficRule>> = kept.into_iter().map(Some).collect();
+            kept = order
+                .iter()
+                .filter_map(|index| slots.get_mut(*index)?.take())
+                .collect();
+
+            // Rules the order doesn't mention, if the list changed since, are kept at the end
+            kept.extend(slots.into_iter().flatten());
+        }
+
+        let mut kept = kept.into_iter();
+        let mut added = self.added.iter().peekable();
+        rules.reserve(kept.len() + added.len());
+
+        loop {
+            match added.peek() {
+                Some((index, rule)) if *index <= rules.len() => {
+                    rules.push(rule.clone());
+                    added.next();
+                }
+                _ => match kept.next() {
+                    Some(rule) => rules.push(rule),
+                    None => break,
+                },
+            }
+        }
+
+        // Positions past the end, if the list changed since, are added at the end
+        rules.extend(added.map(|(_, rule)| rule.clone()));
+    }
+}
+
+/// What a change did to the traffic filter, along with the values it changed, so it can be undone or redone.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct FilterDiff {
+    /// Whether filtering was enabled, before and after the change.
+    pub enabled: Option<(bool, bool)>,
+    /// The exclusion list in use, before and after the change.
+    pub filter_type: Option<(TrafficFilterType, TrafficFilterType)>,
+    /// The exclusion list whose schedule changed, along with the schedule before and after the change.
+    pub schedule: Option<(
+        TrafficFilterType,
+        Option<TrafficSchedule>,
+        Option<TrafficSchedule>,
+    )>,
+    /// The exclusion list whose audit mode changed, along with the mode before and after the change.
+    pub audit: Option<(TrafficFilterType, bool, bool)>,
+    /// The rules of each exclusion list that changed.
+    pub rules: Vec<RuleListDiff>,
+}
+
+impl FilterDiff {
+    /// Returns whether the change didn't change anything.
+    pub fn is_empty(&self) -> bool {
+        self.enabled.is_none_or(|(before, after)| before == after)
+            && self
+                .filter_type
+                .is_none_or(|(before, after)| before == after)
+            && self
+                .schedule
+                .as_ref()
+                .is_none_or(|(_, before, after)| before == after)
+            && self.audit.is_none_or(|(_, before, after)| before == after)
+            && self.rules.iter().all(RuleListDiff::is_empty)
+    }
+
+    /// Returns the number of rules the change added.
+    pub fn added_rules(&self) -> usize {
+        self.rules.iter().map(|rules| rules.added.len()).sum()
+    }
+
+    /// Returns the number of rules the change holds, counting each rule that was removed, added or reordered.
+    fn stored_rules(&self) -> usize {
+        self.rules
+            .iter()
+            .map(|rules| {
+                rules.removed.len() + rules.added.len() + rules.order.as_ref().map_or(0, Vec::len)
+            })
+            .sum()
+    }
+
+    /// Returns the diff that undoes this one.
+    pub fn inverse(&self) -> Self {
+        Self {
+            enabled: self.enabled.map(|(before, after)| (after, before)),
+            filter_type: self.filter_type.map(|(before, after)| (after, before)),
+            schedule: self
+                .schedule
+                .clone()
+                .map(|(list, before, after)| (list, after, before)),
+            audit: self
+                .audit
+                .map(|(list, before, after)| (list, after, before)),
+            rules: self.rules.iter().rev().map(RuleListDiff::inverse).collect(),
+        }
+    }
+
+    /// Adds a later change to the same schedule to this one, returning false if the later change isn't only that.
+    ///
+    /// # Arguments:
+    /// * `next` - The change made right after this one.
+    fn merge(&mut self, next: &FilterDiff) -> bool {
+        let only_schedule = |diff: &FilterDiff| {
+            diff.enabled.is_none()
+                && diff.filter_type.is_none()
+                && diff.audit.is_none()
+                && diff.rules.is_empty()
+        };
+
+        if !only_schedule(self) || !only_schedule(next) {
+            return false;
+        }
+
+        match (&mut self.schedule, &next.schedule) {
+            (Some((list, _, after)), Some((next_list, _, next_after))) if list == next_list => {
+                *after = next_after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A change made to the traffic filter, as it's recorded in the change log.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct FilterChange {
+    pub time: DateTime<Local>,
+    /// The user the application was running as.
+    pub author: String,
+    pub description: String,
+    pub before: String,
+    pub after: String,
+    /// What the change did, which can't be gone back past when it's None.
+    ///
+    /// It's None for changes logged before they were recorded, and for changes stored too long ago to keep it.
+    #[serde(default)]
+    pub diff: Option<FilterDiff>,
+}
+
+/// A change in the change log as it's stored, which is a FilterChange whose diff may have been left out.
+#[derive(serde::Serialize)]
+struct StoredChange<'a> {
+    time: &'a DateTime<Local>,
+    author: &'a str,
+    description: &'a str,
+    before: &'a str,
+    after: &'a str,
+    diff: Option<&'a FilterDiff>,
+}
+
+/// A change that can be undone or redone.
+#[derive(Clone, Debug)]
+struct FilterEdit {
+    description: String,
+    diff: FilterDiff,
+}
+
+/// Contains the undo and redo stacks of the traffic filter, and its change log.
+///
+/// Only the change log is stored, the stacks start empty every time the application does.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FilterHistory {
+    #[serde(serialize_with = "serialize_log")]
+    log: Vec<FilterChange>,
+    #[serde(skip)]
+    undo: Vec<FilterEdit>,
+    #[serde(skip)]
+    redo: Vec<FilterEdit>,
+}
+
+impl FilterHistory {
+    /// Records a change made to the traffic filter, which clears the changes that could be redone.
+    ///
+    /// Changes to the same schedule made in quick succession, e.g. while dragging its times, are recorded as one.
+    ///
+    /// # Arguments:
+    /// * `description` - What the change did, e.g. Removed a rule.
+    /// * `diff` - The FilterDiff of the change.
+    pub fn record(&mut self, description: &str, diff: FilterDiff) {
+        self.redo.clear();
+
+        if self.merge_last(description, &diff) {
+            return;
+        }
+
+        self.undo.push(FilterEdit {
+            description: description.to_string(),
+            diff: diff.clone(),
+        });
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+
+        self.log_change(description, diff);
+    }
+
+    /// Returns the change that undoes the last change, logging it.
+    pub fn undo(&mut self) -> Option<FilterDiff> {
+        let edit = self.undo.pop()?;
+        let diff = edit.diff.inverse();
+
+        self.log_change(&format!("Undo: {}", edit.description), diff.clone());
+        self.redo.push(edit);
+
+        Some(diff)
+    }
+
+    /// Returns the change that redoes the last undone change, logging it.
+    pub fn redo(&mut self) -> Option<FilterDiff> {
+        let edit = self.redo.pop()?;
+        let diff = edit.diff.clone();
+
+        self.log_change(&format!("Redo: {}", edit.description), diff.clone());
+        self.undo.push(edit);
+
+        Some(diff)
+    }
+
+    /// Returns what the next undo would undo, if anything.
+    pub fn next_undo(&self) -> Option<String> {
+        self.undo.last().map(|edit| edit.description.clone())
+    }
+
+    /// Returns what the next redo would redo, if anything.
+    pub fn next_redo(&self) -> Option<String> {
+        self.redo.last().map(|edit| edit.description.clone())
+    }
+
+    /// Returns the change log, oldest first.
+    pub fn log(&self) -> &[FilterChange] {
+        &self.log
+    }
+
+    /// Returns the traffic filter as it was right after a change in the change log, by undoing every later change.
+    ///
+    /// Returns None if a later change wasn't recorded with what it did.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the change in the change log.
+    /// * `current` - The traffic filter as it is now.
+    pub fn state_after(&self, index: usize, current: &TrafficFilter) -> Option<TrafficFilter> {
+        let mut state = current.clone();
+
+        for change in self.log.get(index + 1..)?.iter().rev() {
+            state.apply_diff(&change.diff.as_ref()?.inverse());
+        }
+
+        Some(state)
+    }
+
+    /// Removes every change from the change log.
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    /// Adds a change to the last one, when both only changed the same schedule shortly after each other.
+    ///
+    /// Returns whether the change was added to the last one.
+    fn merge_last(&mut self, description: &str, diff: &FilterDiff) -> bool {
+        let now = Local::now();
+        let (Some(change), Some(edit)) = (self.log.last_mut(), self.undo.last_mut()) else {
+            return false;
+        };
+
+        if change.description != description
+            || edit.description != description
+            || now - change.time > Duration::seconds(MERGE_SECONDS)
+            || !edit.diff.merge(diff)
+        {
+            return false;
+        }
+
+        // Dragging a value back to where it started leaves nothing to record
+        if edit.diff.is_empty() {
+            self.undo.pop();
+            self.log.pop();
+            return true;
+        }
+
+        (change.before, change.after) = describe_change(&edit.diff);
+        change.time = now;
+        change.diff = Some(edit.diff.clone());
+        true
+    }
+
+    /// Adds a change to the change log, describing the values it changed.
+    fn log_change(&mut self, description: &str, diff: FilterDiff) {
+        let (before, after) = describe_change(&diff);
+
+        self.log.push(FilterChange {
+            time: Local::now(),
+            author: current_user(),
+            description: description.to_string(),
+            before,
+            after,
+            diff: Some(diff),
+        });
+
+        if self.log.len() > CHANGE_LOG_LIMIT {
+            self.log.remove(0);
+        }
+    }
+}
+
+/// Stores the change log, keeping the diffs of the latest changes that hold at most STORED_DIFF_RULES rules.
+///
+/// # Arguments:
+/// * `log` - The change log, oldest first.
+/// * `serializer` - The Serializer the change log is stored with.
+fn serialize_log<S: serde::Serializer>(
+    log: &[FilterChange],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    // Going back past a change needs the diffs of every later one, so none are kept before the first left out
+    let mut rules = 0;
+    let kept_from = log
+        .iter()
+        .rposition(|change| match &change.diff {
+            Some(diff) => {
+                rules += diff.stored_rules();
+                rules > STORED_DIFF_RULES
+            }
+            None => true,
+        })
+        .map_or(0, |index| index + 1);
+
+    serializer.collect_seq(log.iter().enumerate().map(|(index, change)| StoredChange {
+        time: &change.time,
+        author: &change.author,
+        description: &change.description,
+        before: &change.before,
+        after: &change.after,
+        diff: change.diff.as_ref().filter(|_| index >= kept_from),
+    }))
+}
+
+/// Returns the name of the user the application is running as.
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| String::from("unknown"))
+}
+
+/// Returns a short description of a rule, e.g. example.com (Regex, Redirect).
+fn describe_rule(rule: &TrafficRule) -> String {
+    let mut details = Vec::new();
+    if rule.kind != TrafficRuleKind::Contains {
+        details.push(rule.kind.to_string());
+    }
+    if rule.action != TrafficRuleAction::Filter {
+        details.push(rule.action.to_string());
+    }
+    if rule.schedule.is_some() {
+        details.push(String::from("Scheduled"));
+    }
+    if rule.audit {
+        details.push(String::from("Audit"));
+    }
+
+    match details.is_empty() {
+        true => rule.pattern.clone(),
+        false => format!("{} ({})", rule.pattern, details.join(", ")),
+    }
+}
+
+/// Returns readable before and after values of what a change did.
+///
+/// # Arguments:
+/// * `diff` - The FilterDiff of the change.
+fn describe_change(diff: &FilterDiff) -> (String, String) {
+    let mut before_values = Vec::new();
+    let mut after_values = Vec::new();
+
+    if let Some((before, after)) = diff.enabled {
+        for (values, enabled) in [(&mut before_values, before), (&mut after_values, after)] {
+            values.push(String::from(match enabled {
+                true => "Filtering enabled",
+                false => "Filtering disabled",
+            }));
+        }
+    }
+
+    if let Some((before, after)) = diff.filter_type {
+        before_values.push(TrafficFilter::profile_name(before));
+        after_values.push(TrafficFilter::profile_name(after));
+    }
+
+    if let Some((_, before, after)) = &diff.schedule {
+        for (values, schedule) in [(&mut before_values, before), (&mut after_values, after)] {
+            values.push(match schedule {
+                Some(schedule) => format!("Scheduled: {}", schedule.describe()),
+                None => String::from("Always filtering"),
+            });
+        }
+    }
+
+    if let Some((_, before, after)) = diff.audit {
+        for (values, audit) in [(&mut before_values, before), (&mut after_values, after)] {
+            values.push(String::from(match audit {
+                true => "Audit mode",
+                false => "Enforcing",
+            }));
+        }
+    }
+
+    for rules in &diff.rules {
+        // Rules both removed and added were only moved, so they're grouped by pattern to find them quickly
+        let mut removed: HashMap<(&str, TrafficRuleKind), Vec<&TrafficRule>> = HashMap::new();
+        for (_, rule) in &rules.removed {
+            removed
+                .entry((rule.pattern.as_str(), rule.kind))
+                .or_default()
+                .push(rule);
+        }
+        let mut added: HashMap<(&str, TrafficRuleKind), Vec<&TrafficRule>> = HashMap::new();
+        for (_, rule) in &rules.added {
+            added
+                .entry((rule.pattern.as_str(), rule.kind))
+                .or_default()
+                .push(rule);
+        }
+
+        let changed =
+            |rule: &TrafficRule, other: &HashMap<(&str, TrafficRuleKind), Vec<&TrafficRule>>| {
+                other
+                    .get(&(rule.pattern.as_str(), rule.kind))
+                    .is_none_or(|others| !others.contains(&rule))
+            };
+
+        let count = before_values.len() + after_values.len();
+        before_values.extend(
+            rules
+                .removed
+                .iter()
+                .filter(|(_, rule)| changed(rule, &added))
+                .map(|(_, rule)| describe_rule(rule)),
+        );
+        after_values.extend(
+            rules
+                .added
+                .iter()
+                .filter(|(_, rule)| changed(rule, &removed))
+                .map(|(_, rule)| describe_rule(rule)),
+        );
+
+        // Sorting changes the list without changing which rules are in it
+        if !rules.is_empty() && before_values.len() + after_values.len() == count {
+            let moved = rules.order.as_ref().map_or(rules.added.len(), Vec::len);
+            before_values.push(format!("{} rules", moved));
+            after_values.push(format!("{} rules, reordered", moved));
+        }
+    }
+
+    (join_values(before_values), join_values(after_values))
+}
+
+/// Joins the values of a change, listing only the first few of a long list.
+fn join_values(values: Vec<String>) -> String {
+    match values.len() > CHANGE_VALUE_LIMIT {
+        true => format!(
+            "{}; and {} more",
+            values[..CHANGE_VALUE_LIMIT].join("; "),
+            values.len() - CHANGE_VALUE_LIMIT
+        ),
+        false => values.join("; "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::traffic_filter::TrafficRuleOrder;
+
+    /// Returns a traffic filter with rules in both exclusion lists, including a duplicate and some hits.
+    fn test_filter() -> TrafficFilter {
+        let mut filter = TrafficFilter::default();
+
+        let rules = [
+            ("b.com", 5),
+            ("a.com", 1),
+            ("c.com", 0),
+            ("a.com", 2),
+            ("d.com", 9),
+        ]
+        .into_iter()
+        .map(|(pattern, hits)| {
+            let mut rule = TrafficRule::new(pattern.to_string());
+            rule.hits.count = hits;
+            rule
+        })
+        .collect();
+        filter.set_filter_list(rules);
+
+        filter.set_filter_type(TrafficFilterType::Deny);
+        filter.set_filter_list(vec![TrafficRule::new(String::from("c.com"))]);
+        filter.set_filter_type(TrafficFilterType::Allow);
+
+        filter
+    }
+
+    /// Returns the traffic filter as it's stored, which unlike comparing it includes the hits of its rules.
+    fn stored(filter: &TrafficFilter) -> String {
+        serde_json::to_string(filter).unwrap()
+    }
+
+    /// Asserts that applying a change's diff, then its inverse, gets back the original traffic filter.
+    fn assert_round_trip(name: &str, change: impl FnOnce(&mut TrafficFilter) -> FilterDiff) {
+        let original = test_filter();
+        let mut changed = original.clone();
+        let diff = change(&mut changed);
+        assert!(!diff.is_empty(), "{} changed nothing", name);
+
+        let mut undone = changed.clone();
+        undone.apply_diff(&diff.inverse());
+        assert_eq!(stored(&undone), stored(&original), "undoing {}", name);
+
+        let mut redone = undone;
+        redone.apply_diff(&diff);
+        assert_eq!(stored(&redone), stored(&changed), "redoing {}", name);
+    }
+
+    #[test]
+    fn undoes_and_redoes_rule_changes() {
+        assert_round_trip("add", |filter| {
+            filter.add_filter_list_items(vec![
+                TrafficRule::new(String::from("e.com")),
+                TrafficRule::new(String::from("b.com")),
+            ])
+        });
+        assert_round_trip("insert", |filter| {
+            filter.insert_filter_list_item(1, TrafficRule::new(String::from("e.com")))
+        });
+        assert_round_trip("remove", |filter| {
+            filter.remove_filter_list_items(&[0, 2, 4])
+        });
+        assert_round_trip("move", |filter| filter.move_filter_list_items(&[1, 2]));
+        assert_round_trip("sort by pattern", |filter| {
+            filter.sort_filter_list(TrafficRuleOrder::Pattern)
+        });
+        assert_round_trip("sort by hits", |filter| {
+            filter.sort_filter_list(TrafficRuleOrder::Hits)
+        });
+        assert_round_trip("dedupe", TrafficFilter::remove_duplicates);
+        assert_round_trip("replace", |filter| {
+            filter.set_filter_list(vec![
+                TrafficRule::new(String::from("a.com")),
+                TrafficRule::new(String::from("e.com")),
+            ])
+        });
+        assert_round_trip("restore", |filter| {
+            let mut state = TrafficFilter::default();
+            state.set_enabled(true);
+            state.set_filter_type(TrafficFilterType::Deny);
+            filter.restore(state)
+        });
+    }
+
+    #[test]
+    fn undoes_and_redoes_recorded_changes() {
+        let original = test_filter();
+        let mut filter = original.clone();
+        let mut history = FilterHistory::default();
+
+        history.record("Sorted", filter.sort_filter_list(TrafficRuleOrder::Pattern));
+        let sorted = filter.clone();
+        history.record("Removed duplicates", filter.remove_duplicates());
+        history.record("Moved", filter.move_filter_list_items(&[0]));
+        let latest = filter.clone();
+
+        assert_eq!(
+            stored(&history.state_after(0, &filter).unwrap()),
+            stored(&sorted)
+        );
+
+        while let Some(diff) = history.undo() {
+            filter.apply_diff(&diff);
+        }
+        assert_eq!(stored(&filter), stored(&original));
+
+        while let Some(diff) = history.redo() {
+            filter.apply_diff(&diff);
+        }
+        assert_eq!(stored(&filter), stored(&latest));
+    }
+
+    #[test]
+    fn stores_only_the_latest_diffs() {
+        let mut filter = TrafficFilter::default();
+        let mut history = FilterHistory::default();
+
+        let rules = |prefix: &str, count: usize| {
+            (0..count)
+                .map(|index| TrafficRule::new(format!("{}{}.com", prefix, index)))
+                .collect::<Vec<_>>()
+        };
+
+        history.record("Enabled", filter.set_enabled(true));
+        history.record(
+            "Imported",
+            filter.add_filter_list_items(rules("a", STORED_DIFF_RULES)),
+        );
+        history.record("Added", filter.add_filter_list_items(rules("b", 10)));
+        history.record("Disabled", filter.set_enabled(false));
+
+        let stored: FilterHistory =
+            serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        let diffs = stored
+            .log()
+            .iter()
+            .map(|change| change.diff.is_some())
+            .collect::<Vec<_>>();
+
+        assert_eq!(diffs, [false, false, true, true]);
+        assert_eq!(stored.log()[2].diff, history.log()[2].diff);
+        assert!(stored.state_after(0, &filter).is_none());
+        assert!(stored.state_after(1, &filter).is_some());
+    }
+}
@@ -0,0 +1,295 @@
+use super::{
+    forward::{full, ProxyBody},
+    traffic_filter::TrafficRuleKind,
+};
+use hyper::{
+    header::{HeaderName, HeaderValue, CONTENT_TYPE},
+    Response, StatusCode, Uri,
+};
+use regex::Regex;
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The file served when a mapped directory is requested.
+const INDEX_FILE: &str = "index.html";
+
+/// A rule that answers matching requests from a local file, or a file in a local directory.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct MapLocalRule {
+    pub enabled: bool,
+    pub pattern: String,
+    pub kind: TrafficRuleKind,
+    /// The file to serve, or the directory to serve the rest of the URL's path from.
+    pub path: String,
+    pub status: u16,
+    /// Extra response headers, one "Name: Value" per line.
+    pub headers: String,
+    #[serde(skip)]
+    compiled: OnceLock<Option<Regex>>,
+}
+
+impl Default for MapLocalRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pattern: String::default(),
+            kind: TrafficRuleKind::default(),
+            path: String::default(),
+            status: 200,
+            headers: String::default(),
+            compiled: OnceLock::new(),
+        }
+    }
+}
+
+// Copies are edited in the settings window, so each one compiles its own pattern rather than keeping an outdated one
+impl Clone for MapLocalRule {
+    fn clone(&self) -> Self {
+        Self {
+            enabled: self.enabled,
+            pattern: self.pattern.clone(),
+            kind: self.kind,
+            path: self.path.clone(),
+            status: self.status,
+            headers: self.headers.clone(),
+            compiled: OnceLock::new(),
+        }
+    }
+}
+
+impl PartialEq for MapLocalRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.enabled == other.enabled
+            && self.pattern == other.pattern
+            && self.kind == other.kind
+            && self.path == other.path
+            && self.status == other.status
+            && self.headers == other.headers
+    }
+}
+
+impl MapLocalRule {
+    /// Returns the compiled pattern of a Regex rule, or None if it isn't a valid regular expression.
+    fn regex(&self) -> Option<&Regex> {
+        self.compiled
+            .get_or_init(|| Regex::new(&self.pattern).ok())
+            .as_ref()
+    }
+
+    /// Returns the parsed extra response headers, or an error message for the first malformed line.
+    pub fn parse_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+        self.headers
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (name, value) = line.split_once(':').ok_or(format!(
+                    "Headers must be written as 'Name: Value': {}",
+                    line
+                ))?;
+
+                Ok((
+                    HeaderName::from_bytes(name.trim().as_bytes())
+                        .map_err(|_| format!("Invalid header name: {}", name.trim()))?,
+                    HeaderValue::from_str(value.trim())
+                        .map_err(|_| format!("Invalid header value: {}", value.trim()))?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns an error message if the rule's pattern or headers are invalid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind == TrafficRuleKind::Regex {
+            Regex::new(&self.pattern).map_err(|error| error.to_string())?;
+        }
+
+        self.parse_headers().map(|_| ())
+    }
+
+    /// Returns what the rule maps the URI to, if the rule matches it.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI being requested.
+    fn mapping(&self, uri: &str) -> Option<LocalMapping> {
+        if !self.enabled || self.pattern.is_empty() {
+            return None;
+        }
+
+        // The part of the URI after the match is looked up in a mapped directory
+        let (path, rest) = match self.kind {
+            TrafficRuleKind::Contains => {
+                let start = uri.find(&self.pattern)?;
+                (self.path.clone(), &uri[start + self.pattern.len()..])
+            }
+            TrafficRuleKind::Regex => {
+                let captures = self.regex()?.captures(uri)?;
+                let mut path = String::new();
+                captures.expand(&self.path, &mut path);
+
+                // The captured parts of the URI may never reach outside the path the rule spells out
+                let literal = &self.path[..self.path.find('$').unwrap_or(self.path.len())];
+                if !is_contained(Path::new(path.strip_prefix(literal)?)) {
+                    return None;
+                }
+
+                (path, &uri[captures.get(0)?.end()..])
+            }
+        };
+
+        Some(LocalMapping {
+            rule: self.clone(),
+            path: PathBuf::from(path),
+            rest: rest
+                .split(['?', '#'])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+}
+
+/// A local file or directory a rule maps a request to, before it's been looked up on disk.
+#[derive(Debug, Clone)]
+pub struct LocalMapping {
+    pub rule: MapLocalRule,
+    /// The mapped file, or directory.
+    path: PathBuf,
+    /// The part of the URI's path after the match, looked up in the directory if the path is one.
+    rest: String,
+}
+
+impl LocalMapping {
+    /// Returns the local file to serve, or None if the rest of the URI's path leaves the mapped directory.
+    async fn local_path(&self) -> Option<PathBuf> {
+        if !is_dir(&self.path).await {
+            return Some(self.path.clone());
+        }
+
+        // Never serve files from outside the mapped directory
+        let rest = Path::new(self.rest.trim_start_matches('/'));
+        if !is_contained(rest) {
+            return None;
+        }
+
+        let mut file = self.path.join(rest);
+        if is_dir(&file).await {
+            file.push(INDEX_FILE);
+        }
+
+        Some(file)
+    }
+}
+
+/// Returns whether a relative path stays inside the directory it's joined to.
+fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Returns whether the path is a directory, without blocking the runtime.
+async fn is_dir(path: &Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Returns what every rule matching the URI maps it to, in order.
+///
+/// # Arguments:
+/// * `rules` - The map local rules to check.
+/// * `uri` - The URI being requested.
+pub fn match_local_rules(rules: &[MapLocalRule], uri: &Uri) -> Vec<LocalMapping> {
+    let uri = uri.to_string();
+    rules.iter().filter_map(|rule| rule.mapping(&uri)).collect()
+}
+
+/// Returns the first mapping of a request to a local file, along with the file to serve.
+///
+/// # Arguments:
+/// * `mappings` - What the matching rules map the request to, in order.
+pub async fn find_local_file(mappings: Vec<LocalMapping>) -> Option<(MapLocalRule, PathBuf)> {
+    for mapping in mappings {
+        if let Some(path) = mapping.local_path().await {
+            return Some((mapping.rule, path));
+        }
+    }
+
+    None
+}
+
+/// Creates a response from a local file, or a 404 if it can't be read.
+///
+/// # Arguments:
+/// * `rule` - The rule that mapped the request.
+/// * `path` - The local file to serve.
+pub async fn local_response(rule: &MapLocalRule, path: &Path) -> Response<ProxyBody> {
+    let contents = match tokio::fs::read(path).await {
+        Ok(contents) => contents,
+        Err(error) => {
+            let mut response = Response::new(full(format!(
+                "Mapped file could not be read: {} ({})",
+                path.display(),
+                error
+            )));
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        }
+    };
+
+    let mut response = Response::new(full(contents));
+    *response.status_mut() = StatusCode::from_u16(rule.status).unwrap_or(StatusCode::OK);
+
+    if let Ok(content_type) = guess_content_type(path).parse() {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+
+    // Configured headers may override the guessed content type
+    for (name, value) in rule.parse_headers().unwrap_or_default() {
+        response.headers_mut().insert(name, value);
+    }
+
+    response
+}
+
+/// Returns the content type of a file, guessed from its extension.
+///
+/// # Arguments:
+/// * `path` - The file to guess the content type of.
+pub fn guess_content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
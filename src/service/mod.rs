@@ -1,2 +1,22 @@
+pub(crate) mod block_page;
+pub(crate) mod cache;
+pub(crate) mod capture;
+pub(crate) mod fault;
+pub(crate) mod forward;
+pub(crate) mod header_rules;
+pub(crate) mod history;
+pub(crate) mod import;
+pub(crate) mod map_local;
+pub(crate) mod pool;
 pub(crate) mod proxy;
+pub(crate) mod rate_limit;
+pub(crate) mod replay;
+pub(crate) mod rule_action;
+pub(crate) mod schedule;
+pub(crate) mod settings;
+pub(crate) mod sni;
+pub(crate) mod snooze;
+pub(crate) mod stats;
+pub(crate) mod throttle;
+pub(crate) mod timeout;
 pub(crate) mod traffic_filter;
@@ -0,0 +1,379 @@
+use super::forward::ProxyBody;
+use hyper::client::conn::{http1, http2};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The upstream server a pooled connection is open to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub host: String,
+    pub port: u16,
+}
+
+impl std::fmt::Display for PoolKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+/// Contains the number of connections open to a single upstream server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProxyPoolHost {
+    pub host: String,
+    pub active: usize,
+    pub idle: usize,
+}
+
+/// Contains the statistics of the upstream connection pool.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProxyPoolStats {
+    pub active: usize,
+    pub idle: usize,
+    pub opened: usize,
+    pub reused: usize,
+    pub expired: usize,
+    pub unhealthy: usize,
+    pub hosts: Vec<ProxyPoolHost>,
+}
+
+/// An HTTP/1.1 connection waiting to be reused.
+struct IdleConnection {
+    sender: http1::SendRequest<ProxyBody>,
+    idle_since: Instant,
+}
+
+/// An HTTP/2 connection, shared by every request to the same server.
+struct SharedConnection {
+    sender: http2::SendRequest<ProxyBody>,
+    last_used: Instant,
+}
+
+/// The limit on the connections open to a single server.
+struct HostLimit {
+    max_connections: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: HashMap<PoolKey, Vec<IdleConnection>>,
+    shared: HashMap<PoolKey, SharedConnection>,
+    limits: HashMap<PoolKey, HostLimit>,
+    http1_only: HashSet<PoolKey>,
+    opened: usize,
+    reused: usize,
+    expired: usize,
+    unhealthy: usize,
+}
+
+/// An HTTP/1.1 connection checked out of the pool, which must be released once its request has been sent.
+pub struct PooledConnection {
+    pub key: PoolKey,
+    pub sender: http1::SendRequest<ProxyBody>,
+    pub reused: bool,
+    permit: OwnedSemaphorePermit,
+}
+
+impl PooledConnection {
+    /// Closes the connection, keeping the permit so another can be opened in its place.
+    pub fn into_permit(self) -> OwnedSemaphorePermit {
+        self.permit
+    }
+}
+
+/// Keeps upstream connections open so they can be reused by later requests to the same server.
+#[derive(Clone, Default)]
+pub struct ConnectionPool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl ConnectionPool {
+    /// Waits until another connection may be used for the given server, returning the permit to hold while using it.
+    ///
+    /// # Arguments:
+    /// * `key` - The server to connect to.
+    /// * `max_connections` - The most connections that may be in use for a single server.
+    pub async fn reserve(&self, key: &PoolKey, max_connections: usize) -> OwnedSemaphorePermit {
+        let semaphore = {
+            let mut state = self.state.lock().unwrap();
+            let max_connections = max_connections.max(1);
+
+            // A changed limit only applies to connections opened after the change
+            let limit = state
+                .limits
+                .entry(key.clone())
+                .or_insert_with(|| HostLimit {
+                    max_connections,
+                    semaphore: Arc::new(Semaphore::new(max_connections)),
+                });
+            if limit.max_connections != max_connections {
+                *limit = HostLimit {
+                    max_connections,
+                    semaphore: Arc::new(Semaphore::new(max_connections)),
+                };
+            }
+
+            Arc::clone(&limit.semaphore)
+        };
+
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("Pool semaphores are never closed")
+    }
+
+    /// Takes a healthy idle connection to the given server, closing any that have expired or failed.
+    ///
+    /// # Arguments:
+    /// * `key` - The server to connect to.
+    /// * `permit` - The permit returned by reserve.
+    /// * `idle_timeout` - How long a connection may be idle before it's closed.
+    pub fn checkout(
+        &self,
+        key: &PoolKey,
+        permit: OwnedSemaphorePermit,
+        idle_timeout: Duration,
+    ) -> Result<PooledConnection, OwnedSemaphorePermit> {
+        let mut state = self.state.lock().unwrap();
+
+        while let Some(connection) = state.idle.get_mut(key).and_then(|idle| idle.pop()) {
+            if connection.idle_since.elapsed() > idle_timeout {
+                state.expired += 1;
+                continue;
+            }
+
+            // Health check, the connection must still be open and waiting for a request
+            if !connection.sender.is_ready() {
+                state.unhealthy += 1;
+                continue;
+            }
+
+            state.reused += 1;
+            return Ok(PooledConnection {
+                key: key.clone(),
+                sender: connection.sender,
+                reused: true,
+                permit,
+            });
+        }
+
+        Err(permit)
+    }
+
+    /// Wraps a newly opened connection so it can be returned to the pool.
+    ///
+    /// # Arguments:
+    /// * `key` - The server the connection is open to.
+    /// * `sender` - The connection's request sender.
+    /// * `permit` - The permit returned by reserve.
+    pub fn opened(
+        &self,
+        key: &PoolKey,
+        sender: http1::SendRequest<ProxyBody>,
+        permit: OwnedSemaphorePermit,
+    ) -> PooledConnection {
+        self.state.lock().unwrap().opened += 1;
+
+        PooledConnection {
+            key: key.clone(),
+            sender,
+            reused: false,
+            permit,
+        }
+    }
+
+    /// Records that a reused connection was found closed when a request was sent on it.
+    pub fn record_unhealthy(&self) {
+        self.state.lock().unwrap().unhealthy += 1;
+    }
+
+    /// Returns a connection to the pool once its current response has been read.
+    ///
+    /// # Arguments:
+    /// * `connection` - The connection whose request has been sent.
+    pub fn release(&self, connection: PooledConnection) {
+        let pool = self.clone();
+
+        tokio::task::spawn(async move {
+            let PooledConnection {
+                key,
+                mut sender,
+                permit,
+                ..
+            } = connection;
+
+            // The connection becomes ready again once the response body has been read
+            if sender.ready().await.is_ok() {
+                pool.state
+                    .lock()
+                    .unwrap()
+                    .idle
+                    .entry(key)
+                    .or_default()
+                    .push(IdleConnection {
+                        sender,
+                        idle_since: Instant::now(),
+                    });
+            }
+
+            drop(permit);
+        });
+    }
+
+    /// Returns the shared HTTP/2 connection to the given server, if one is open.
+    ///
+    /// # Arguments:
+    /// * `key` - The server to connect to.
+    /// * `idle_timeout` - How long a connection may be idle before it's closed.
+    pub fn shared(
+        &self,
+        key: &PoolKey,
+        idle_timeout: Duration,
+    ) -> Option<http2::SendRequest<ProxyBody>> {
+        let mut state = self.state.lock().unwrap();
+        let connection = state.shared.remove(key)?;
+
+        if connection.last_used.elapsed() > idle_timeout {
+            state.expired += 1;
+            return None;
+        }
+
+        if connection.sender.is_closed() {
+            state.unhealthy += 1;
+            return None;
+        }
+
+        state.reused += 1;
+        let sender = connection.sender.clone();
+        state.shared.insert(
+            key.clone(),
+            SharedConnection {
+                sender: connection.sender,
+                last_used: Instant::now(),
+            },
+        );
+
+        Some(sender)
+    }
+
+    /// Stores a newly opened HTTP/2 connection so later requests can share it.
+    ///
+    /// # Arguments:
+    /// * `key` - The server the connection is open to.
+    /// * `sender` - The connection's request sender.
+    pub fn share(&self, key: &PoolKey, sender: http2::SendRequest<ProxyBody>) {
+        let mut state = self.state.lock().unwrap();
+        state.opened += 1;
+        state.shared.insert(
+            key.clone(),
+            SharedConnection {
+                sender,
+                last_used: Instant::now(),
+            },
+        );
+    }
+
+    /// Returns whether the given server is known not to support HTTP/2.
+    ///
+    /// # Arguments:
+    /// * `key` - The server to check.
+    pub fn is_http1_only(&self, key: &PoolKey) -> bool {
+        self.state.lock().unwrap().http1_only.contains(key)
+    }
+
+    /// Records that the given server doesn't support HTTP/2, so it isn't tried again.
+    ///
+    /// # Arguments:
+    /// * `key` - The server that failed to speak HTTP/2.
+    pub fn set_http1_only(&self, key: &PoolKey) {
+        self.state.lock().unwrap().http1_only.insert(key.clone());
+    }
+
+    /// Closes every idle connection that has expired or failed its health check.
+    ///
+    /// # Arguments:
+    /// * `idle_timeout` - How long a connection may be idle before it's closed.
+    pub fn prune(&self, idle_timeout: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let (mut expired, mut unhealthy) = (0, 0);
+
+        for connections in state.idle.values_mut() {
+            connections.retain(|connection| {
+                if connection.idle_since.elapsed() > idle_timeout {
+                    expired += 1;
+                    false
+                } else if !connection.sender.is_ready() {
+                    unhealthy += 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        state.shared.retain(|_, connection| {
+            if connection.last_used.elapsed() > idle_timeout {
+                expired += 1;
+                false
+            } else if connection.sender.is_closed() {
+                unhealthy += 1;
+                false
+            } else {
+                true
+            }
+        });
+
+        state.idle.retain(|_, connections| !connections.is_empty());
+        state.expired += expired;
+        state.unhealthy += unhealthy;
+    }
+
+    /// Closes every pooled connection and resets the statistics.
+    pub fn clear(&self) {
+        *self.state.lock().unwrap() = PoolState::default();
+    }
+
+    /// Returns the current statistics of the pool.
+    pub fn get_stats(&self) -> ProxyPoolStats {
+        let state = self.state.lock().unwrap();
+        let mut hosts: HashMap<&PoolKey, ProxyPoolHost> = HashMap::new();
+
+        for (key, limit) in &state.limits {
+            let active = limit.max_connections - limit.semaphore.available_permits();
+            if active > 0 {
+                hosts.entry(key).or_default().active += active;
+            }
+        }
+
+        for (key, connections) in &state.idle {
+            hosts.entry(key).or_default().idle += connections.len();
+        }
+
+        for key in state.shared.keys() {
+            hosts.entry(key).or_default().idle += 1;
+        }
+
+        let mut hosts: Vec<ProxyPoolHost> = hosts
+            .into_iter()
+            .map(|(key, host)| ProxyPoolHost {
+                host: key.to_string(),
+                ..host
+            })
+            .collect();
+        hosts.sort_by_key(|host| std::cmp::Reverse(host.active + host.idle));
+
+        ProxyPoolStats {
+            active: hosts.iter().map(|host| host.active).sum(),
+            idle: hosts.iter().map(|host| host.idle).sum(),
+            opened: state.opened,
+            reused: state.reused,
+            expired: state.expired,
+            unhealthy: state.unhealthy,
+            hosts,
+        }
+    }
+}
@@ -1,18 +1,63 @@
-use super::traffic_filter::TrafficFilter;
+use super::{
+    block_page::{
+        block_response, exception_response, is_exception_request, parse_exception_request,
+        BlockedRequest, ProxyExceptionRequest, ResetConnection,
+    },
+    cache::{CacheLookup, HttpCache, ProxyCacheEntry, ProxyCacheStats, ProxyCacheStatus},
+    capture::{CaptureBody, ProxyBodyLog},
+    fault::{fault_response, inject_fault, pick_fault, FaultRule},
+    forward::{empty, forward_request, full, ForwardError, ForwardSettings, ProxyBody},
+    header_rules::{apply_header_rules, HeaderDirection, HeaderRule, ProxyHeaderChange},
+    history::{FilterChange, FilterDiff, FilterHistory},
+    import::{merge_rules, ImportMode, ImportedRule},
+    map_local::{find_local_file, local_response, match_local_rules, MapLocalRule},
+    pool::{ConnectionPool, ProxyPoolStats},
+    rate_limit::{
+        check_rate_limit, rate_limit_response, record_bytes, QuotaUsage, RateLimitAction,
+        RateLimitRule,
+    },
+    replay::{replay_request, ProxyReplay, ProxyReplayRequest, ProxyReplayStatus},
+    rule_action::{apply_rule, rewrite_address, RuleOutcome},
+    schedule::TrafficSchedule,
+    settings::{ProxyBlockPage, ProxyCacheSettings, ProxyHttpVersion, ProxySettings},
+    sni::read_server_name,
+    snooze::{SnoozeDuration, TemporaryAllow, TemporaryAllows},
+    stats::{ProxyStats, ProxyStatsWindow},
+    throttle::{
+        simulate_connection, throttle_request, throttle_response, ProxyThrottle, ResetHandle,
+        ThrottledIo,
+    },
+    timeout::{
+        client_timeout, idle_timeout, lifetime_timeout, with_timeout, ActivityIo,
+        ConnectionActivity, ProxyTimeout, ProxyTimeoutCounts, ProxyTimeoutStats, TimeoutError,
+    },
+    traffic_filter::{
+        TrafficFilter, TrafficRule, TrafficRuleAction, TrafficRuleKind, TrafficRuleOrder,
+    },
+};
 use crate::utils::logger::{LogLevel, Logger};
-use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
+use http_body_util::BodyExt;
 use hyper::{
-    body::Bytes, http, server::conn::http1, service::service_fn, upgrade::Upgraded, Method,
-    Request, Response, Uri,
+    body::Bytes, http, service::service_fn, upgrade::Upgraded, HeaderMap, Method, Request,
+    Response, Uri,
+};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
 };
-use hyper_util::rt::TokioIo;
 use std::{
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
 };
-use tokio::net::{TcpListener, TcpStream};
 
 /// The enum that controls the current Proxy status, e.g. Running, Stopped.
 #[derive(Debug, PartialEq, Clone, Default)]
@@ -24,42 +69,33 @@ pub enum ProxyEvent {
     Error(String),
     Terminating,
     Terminated,
-    RequestEvent(ProxyRequestLog),
+    RequestEvent(Box<ProxyRequestLog>),
+    RequestUpdate(usize, ProxyRequestUpdate),
 }
 
-impl ToString for ProxyEvent {
+impl std::fmt::Display for ProxyEvent {
     /// Converts the ProxyEvent to a readable string.
-    fn to_string(&self) -> String {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let current_proxy_status = match self {
-            ProxyEvent::Starting => String::from("STARTING"),
-            ProxyEvent::Running => String::from("RUNNING"),
-            ProxyEvent::Stopped => String::from("STOPPED"),
-            ProxyEvent::Error(_) => String::from("ERROR"),
-            ProxyEvent::Terminating => String::from("TERMINATING"),
-            ProxyEvent::Terminated => String::from("TERMINATED"),
-            _ => String::from("UNKNOWN"),
+            ProxyEvent::Starting => "STARTING",
+            ProxyEvent::Running => "RUNNING",
+            ProxyEvent::Stopped => "STOPPED",
+            ProxyEvent::Error(_) => "ERROR",
+            ProxyEvent::Terminating => "TERMINATING",
+            ProxyEvent::Terminated => "TERMINATED",
+            _ => "UNKNOWN",
         };
 
-        current_proxy_status
+        write!(f, "{}", current_proxy_status)
     }
 }
 
 /// Contains the information regarding the selected exclusion list value.
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Default)]
 pub struct ProxyExclusionRow {
     pub updating: bool,
     pub index: usize,
-    pub value: String,
-}
-
-impl Default for ProxyExclusionRow {
-    fn default() -> Self {
-        Self {
-            updating: bool::default(),
-            index: usize::default(),
-            value: String::default(),
-        }
-    }
+    pub value: TrafficRule,
 }
 
 /// The enum that controls the action being taken when updating an exclusion list value.
@@ -75,14 +111,171 @@ pub struct ProxyRequestLog {
     pub method: String,
     pub request: String,
     pub blocked: bool,
+    /// Whether the request would have been blocked, if its profile or rule wasn't in audit mode.
+    #[serde(skip)]
+    pub would_block: bool,
+    #[serde(skip)]
+    pub id: usize,
+    #[serde(skip)]
+    pub version: String,
+    #[serde(skip)]
+    pub host: String,
+    #[serde(skip)]
+    pub timestamp: chrono::DateTime<chrono::Local>,
+    #[serde(skip)]
+    pub client: String,
+    #[serde(skip)]
+    pub rule: Option<String>,
+    #[serde(skip)]
+    pub action: Option<String>,
+    #[serde(skip)]
+    pub header_changes: Vec<ProxyHeaderChange>,
+    #[serde(skip)]
+    pub mapped_local: Option<String>,
+    #[serde(skip)]
+    pub throttle: Option<String>,
+    #[serde(skip)]
+    pub fault: Option<String>,
+    #[serde(skip)]
+    pub cache: Option<ProxyCacheStatus>,
+    #[serde(skip)]
+    pub rate_limit: Option<String>,
+    #[serde(skip)]
+    pub server_name: Option<String>,
+    #[serde(skip)]
+    pub request_headers: Vec<(String, String)>,
+    #[serde(skip)]
+    pub request_body: Option<ProxyBodyLog>,
+    #[serde(skip)]
+    pub response: Option<ProxyResponseLog>,
+    #[serde(skip)]
+    pub response_body: Option<ProxyBodyLog>,
+    #[serde(skip)]
+    pub duration: Option<Duration>,
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+/// Contains the information regarding the response to a request made through the Proxy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyResponseLog {
+    pub status: u16,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub time_to_headers: Duration,
+}
+
+/// The enum that carries the information gathered about a request after it has been recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProxyRequestUpdate {
+    Response(ProxyResponseLog),
+    RequestBody(ProxyBodyLog),
+    ResponseBody(ProxyBodyLog, Duration),
+    ServerName {
+        server_name: String,
+        blocked: bool,
+        would_block: bool,
+        rule: Option<String>,
+    },
+    HeaderChanges(Vec<ProxyHeaderChange>),
+    MappedLocal(String),
+    Throttled(String),
+    Fault(String),
+    Cache(ProxyCacheStatus),
+    RateLimited {
+        reason: String,
+        blocked: bool,
+    },
+    Error(String),
 }
 
 impl ProxyRequestLog {
+    /// Creates a new ProxyRequestLog from an incoming request.
+    ///
+    /// # Arguments:
+    /// * `id` - The unique identifier of the request.
+    /// * `client` - The address of the client making the request.
+    /// * `request` - The request being made.
+    fn new<B>(id: usize, client: SocketAddr, request: &Request<B>) -> Self {
+        Self {
+            method: request.method().to_string(),
+            request: request.uri().to_string(),
+            blocked: false,
+            would_block: false,
+            id,
+            version: format!("{:?}", request.version()),
+            host: request.uri().host().unwrap_or_default().to_string(),
+            timestamp: chrono::Local::now(),
+            client: client.to_string(),
+            rule: None,
+            action: None,
+            header_changes: Vec::new(),
+            mapped_local: None,
+            throttle: None,
+            fault: None,
+            cache: None,
+            rate_limit: None,
+            server_name: None,
+            request_headers: headers_to_vec(request.headers()),
+            request_body: None,
+            response: None,
+            response_body: None,
+            duration: None,
+            error: None,
+        }
+    }
+
+    /// Applies information gathered after the request was recorded.
+    ///
+    /// # Arguments:
+    /// * `update` - The ProxyRequestUpdate to apply.
+    fn update(&mut self, update: ProxyRequestUpdate) {
+        match update {
+            ProxyRequestUpdate::Response(response) => self.response = Some(response),
+            ProxyRequestUpdate::RequestBody(body) => self.request_body = Some(body),
+            ProxyRequestUpdate::ResponseBody(body, duration) => {
+                self.response_body = Some(body);
+                self.duration = Some(duration);
+            }
+            ProxyRequestUpdate::ServerName {
+                server_name,
+                blocked,
+                would_block,
+                rule,
+            } => {
+                self.server_name = Some(server_name);
+                if blocked || would_block {
+                    self.blocked |= blocked;
+                    self.would_block |= would_block;
+                    self.rule = rule;
+                }
+            }
+            ProxyRequestUpdate::HeaderChanges(changes) => self.header_changes.extend(changes),
+            ProxyRequestUpdate::MappedLocal(path) => self.mapped_local = Some(path),
+            ProxyRequestUpdate::Throttled(profile) => self.throttle = Some(profile),
+            ProxyRequestUpdate::Fault(fault) => self.fault = Some(fault),
+            ProxyRequestUpdate::Cache(status) => self.cache = Some(status),
+            ProxyRequestUpdate::RateLimited { reason, blocked } => {
+                self.rate_limit = Some(reason);
+                self.blocked |= blocked;
+            }
+            ProxyRequestUpdate::Error(message) => self.error = Some(message),
+        }
+    }
+
+    /// Returns whether the server name (SNI) sent through a tunnel differs from the host it was opened to.
+    pub fn server_name_mismatch(&self) -> bool {
+        self.server_name
+            .as_ref()
+            .is_some_and(|server_name| !server_name.eq_ignore_ascii_case(&self.host))
+    }
+
     /// Converts the exclusion status of a request to readable string.
     fn to_blocked_string(&self) -> String {
-        match self.blocked {
-            true => String::from("BLOCKED"),
-            false => String::from("ALLOWED"),
+        match (self.blocked, self.would_block) {
+            (true, _) => String::from("BLOCKED"),
+            (false, true) => String::from("WOULD BLOCK"),
+            (false, false) => String::from("ALLOWED"),
         }
     }
 }
@@ -94,16 +287,20 @@ pub enum ProxyView {
     Min,
     Logs,
     Filter,
+    Stats,
 }
 
-impl ToString for ProxyView {
+impl std::fmt::Display for ProxyView {
     /// Convert the ProxyView enum to a readable string.
-    fn to_string(&self) -> String {
-        match self {
-            ProxyView::Min => String::from("Default View"),
-            ProxyView::Logs => String::from("Log View"),
-            ProxyView::Filter => String::from("Filter View"),
-        }
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let view = match self {
+            ProxyView::Min => "Default View",
+            ProxyView::Logs => "Log View",
+            ProxyView::Filter => "Filter View",
+            ProxyView::Stats => "Stats View",
+        };
+
+        write!(f, "{}", view)
     }
 }
 
@@ -133,9 +330,50 @@ pub struct Proxy {
     #[serde(skip)]
     pub requests: Arc<Mutex<Vec<ProxyRequestLog>>>,
 
+    // The identifier given to the next request
+    #[serde(skip)]
+    pub request_id: Arc<AtomicUsize>,
+
+    // The number of times a recorded request was blocked, or found it would be, after it was recorded
+    #[serde(skip)]
+    pub outcome_changes: Arc<AtomicUsize>,
+
+    // The request currently open in the request inspector
+    #[serde(skip)]
+    pub selected_request: Option<usize>,
+
+    // The most recently resent request
+    #[serde(skip)]
+    pub replay: Arc<Mutex<Option<ProxyReplay>>>,
+
+    // Service settings
+    pub settings: Arc<Mutex<ProxySettings>>,
+
+    // Upstream connections kept open for reuse
+    #[serde(skip)]
+    pub pool: ConnectionPool,
+
+    // Responses stored on disk for reuse
+    #[serde(skip)]
+    pub cache: HttpCache,
+
+    // The number of timeouts that have fired, per host
+    #[serde(skip)]
+    pub timeouts: Arc<Mutex<ProxyTimeoutStats>>,
+
     // Traffic Filters
     pub traffic_filter: Arc<Mutex<TrafficFilter>>,
 
+    // Exceptions requested from the block page, waiting for review
+    pub exceptions: Arc<Mutex<Vec<ProxyExceptionRequest>>>,
+
+    // Undo/redo of the traffic filter edits, and their change log
+    pub filter_history: Arc<Mutex<FilterHistory>>,
+
+    // Hosts let through the traffic filter for a while, from the Filter view
+    #[serde(skip)]
+    pub temporary_allows: TemporaryAllows,
+
     // Different value selectors for exclusion management
     pub selected_value: String,
     pub selected_exclusion_row: ProxyExclusionRow,
@@ -152,6 +390,7 @@ impl Default for Proxy {
         let status = Arc::new(Mutex::new(ProxyEvent::default()));
         let requests = Arc::new(Mutex::new(Vec::<ProxyRequestLog>::new()));
         let traffic_filter = Arc::new(Mutex::new(TrafficFilter::default()));
+        let settings = Arc::new(Mutex::new(ProxySettings::default()));
         let run_time = Arc::new(Mutex::new(None));
 
         Self {
@@ -161,11 +400,22 @@ impl Default for Proxy {
             event: Arc::new(Mutex::new(None)),
             selected_value: String::default(),
             selected_exclusion_row: ProxyExclusionRow::default(),
+            selected_request: None,
+            replay: Arc::new(Mutex::new(None)),
             status,
             view: ProxyView::default(),
             logger,
             requests,
+            request_id: Arc::new(AtomicUsize::new(0)),
+            outcome_changes: Arc::new(AtomicUsize::new(0)),
             traffic_filter,
+            exceptions: Arc::new(Mutex::new(Vec::new())),
+            filter_history: Arc::new(Mutex::new(FilterHistory::default())),
+            temporary_allows: TemporaryAllows::default(),
+            settings,
+            pool: ConnectionPool::default(),
+            cache: HttpCache::default(),
+            timeouts: Arc::new(Mutex::new(ProxyTimeoutStats::default())),
             run_time,
         }
     }
@@ -178,11 +428,13 @@ impl Proxy {
     /// * `port` - A String that contains the port.
     /// * `view` - A ProxyView value indicating which view is active.
     /// * `traffic_filter` - A TrafficFilter containing the applied filters.
+    /// * `settings` - The ProxySettings to run the service with.
     /// * `log_level` - The logging level.
     pub fn new(
         port: String,
         view: ProxyView,
         traffic_filter: TrafficFilter,
+        settings: ProxySettings,
         log_level: LogLevel,
     ) -> Self {
         let mut logger = Logger::default();
@@ -191,6 +443,9 @@ impl Proxy {
         let status = Arc::new(Mutex::new(ProxyEvent::default()));
         let requests = Arc::new(Mutex::new(Vec::<ProxyRequestLog>::new()));
         let traffic_filter = Arc::new(Mutex::new(traffic_filter));
+        let cache = HttpCache::default();
+        cache.configure(&settings.cache);
+        let settings = Arc::new(Mutex::new(settings));
         let run_time = Arc::new(Mutex::new(None));
 
         Self {
@@ -200,11 +455,22 @@ impl Proxy {
             event: Arc::new(Mutex::new(None)),
             selected_value: String::default(),
             selected_exclusion_row: ProxyExclusionRow::default(),
+            selected_request: None,
+            replay: Arc::new(Mutex::new(None)),
             status,
             view,
             logger,
             requests,
+            request_id: Arc::new(AtomicUsize::new(0)),
+            outcome_changes: Arc::new(AtomicUsize::new(0)),
             traffic_filter,
+            exceptions: Arc::new(Mutex::new(Vec::new())),
+            filter_history: Arc::new(Mutex::new(FilterHistory::default())),
+            temporary_allows: TemporaryAllows::default(),
+            settings,
+            pool: ConnectionPool::default(),
+            cache,
+            timeouts: Arc::new(Mutex::new(ProxyTimeoutStats::default())),
             run_time,
         }
     }
@@ -237,15 +503,13 @@ impl Proxy {
         let run_time = self.run_time.clone();
         let status = self.status.clone();
         let requests = self.requests.clone();
+        let outcome_changes = self.outcome_changes.clone();
         let event_clone = self.event.clone();
         let logger = self.logger.clone();
 
         thread::spawn(move || {
             loop {
-                // Sleep loop to loosen CPU stress
-                thread::sleep(Duration::from_millis(100));
-
-                // Check incoming Proxy events
+                // Check incoming Proxy events, recv blocks so there's no need to sleep between them
                 match event_receiver.recv() {
                     Ok(event) => match event {
                         // Generic Events
@@ -287,7 +551,24 @@ impl Proxy {
                             //     }
                             // );
 
-                            requests.lock().unwrap().push(request_log.clone());
+                            requests.lock().unwrap().push(*request_log);
+                        }
+                        ProxyEvent::RequestUpdate(id, update) => {
+                            // Updates usually belong to recent requests, so search from the back
+                            if let Some(request_log) = requests
+                                .lock()
+                                .unwrap()
+                                .iter_mut()
+                                .rev()
+                                .find(|request_log| request_log.id == id)
+                            {
+                                let outcome = (request_log.blocked, request_log.would_block);
+                                request_log.update(update);
+
+                                if outcome != (request_log.blocked, request_log.would_block) {
+                                    outcome_changes.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
                         }
                         _ => {
                             *status.lock().unwrap() = event;
@@ -306,8 +587,23 @@ impl Proxy {
         let event = self.event.lock().unwrap().clone();
         let port = self.port.clone();
         let status = Arc::clone(&self.status);
-        let traffic_filter = Arc::clone(&self.traffic_filter);
         let logger = self.logger.clone();
+        let context = ProxyContext {
+            event: event.clone(),
+            traffic_filter: Arc::clone(&self.traffic_filter),
+            exceptions: Arc::clone(&self.exceptions),
+            temporary_allows: self.temporary_allows.clone(),
+            settings: Arc::clone(&self.settings),
+            request_id: Arc::clone(&self.request_id),
+            pool: self.pool.clone(),
+            cache: self.cache.clone(),
+            timeouts: Arc::clone(&self.timeouts),
+            exception_token: Arc::from(format!("{:032x}", rand::random::<u128>())),
+            logger: logger.clone(),
+        };
+
+        // Connections from a previous run were closed along with its runtime
+        self.pool.clear();
 
         thread::spawn(move || {
             tokio::runtime::Builder::new_multi_thread()
@@ -318,23 +614,34 @@ impl Proxy {
                     // Termination Signal
                     let mut signal = std::pin::pin!(handle_termination(event.clone(), status));
 
+                    // Close expired and unhealthy upstream connections in the background
+                    let pool = context.pool.clone();
+                    let settings = Arc::clone(&context.settings);
+                    tokio::task::spawn(async move {
+                        let mut interval = tokio::time::interval(Duration::from_secs(10));
+                        loop {
+                            interval.tick().await;
+                            let idle_timeout = settings.lock().unwrap().pool_idle_timeout();
+                            pool.prune(idle_timeout);
+                        }
+                    });
+
+                    // Remove temporary allows as they expire, so each is logged when it happens
+                    let temporary_allows = context.temporary_allows.clone();
+                    let allows_logger = logger.clone();
+                    tokio::task::spawn(async move {
+                        let mut interval = tokio::time::interval(Duration::from_secs(1));
+                        loop {
+                            interval.tick().await;
+                            temporary_allows.expire(&allows_logger);
+                        }
+                    });
+
                     // Bind to address with supplied port
                     let address =
                         SocketAddr::from(([127, 0, 0, 1], port.trim().parse::<u16>().unwrap()));
                     let listener = TcpListener::bind(address).await;
 
-                    // Create a request service
-                    let proxy_service_event = event.clone();
-                    let request_logger = logger.clone();
-                    let proxy_service = service_fn(move |request| {
-                        handle_request(
-                            request,
-                            proxy_service_event.clone(),
-                            traffic_filter.lock().unwrap().clone(),
-                            request_logger.clone(),
-                        )
-                    });
-
                     // Handle service listener events
                     match listener {
                         Ok(listener) => {
@@ -346,17 +653,8 @@ impl Proxy {
 
                             loop {
                                 tokio::select! {
-                                    Ok((stream, _addr)) = listener.accept() => {
-                                        let io = TokioIo::new(stream);
-                                        let connection = http1::Builder::new()
-                                            .preserve_header_case(true)
-                                            .title_case_headers(true)
-                                            .serve_connection(io, proxy_service.clone())
-                                            .with_upgrades();
-
-                                        tokio::task::spawn(async move {
-                                            let _ = connection.await;
-                                        });
+                                    Ok((stream, client)) = listener.accept() => {
+                                        tokio::task::spawn(serve_client(stream, client, context.clone()));
                                     },
 
                                     _ = &mut signal => break
@@ -393,6 +691,60 @@ impl Proxy {
         self.requests.lock().unwrap().to_vec()
     }
 
+    /// Returns the number of requests blocked by the Proxy.
+    pub fn get_blocked_request_count(&self) -> usize {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request_log| request_log.blocked)
+            .count()
+    }
+
+    /// Returns the traffic statistics for the requests made within the given window.
+    ///
+    /// # Arguments:
+    /// * `window` - The ProxyStatsWindow to calculate the statistics for.
+    pub fn get_stats(&self, window: ProxyStatsWindow) -> ProxyStats {
+        ProxyStats::new(&self.requests.lock().unwrap(), window)
+    }
+
+    /// Returns the request with the given identifier, if it is still in the request list.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the request.
+    pub fn get_request(&self, id: usize) -> Option<ProxyRequestLog> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|request_log| request_log.id == id)
+            .cloned()
+    }
+
+    /// Returns the number of requests made through the Proxy.
+    pub fn get_request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Returns the number of times a recorded request was blocked, or found it would be, after it was recorded.
+    pub fn get_outcome_changes(&self) -> usize {
+        self.outcome_changes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the Proxy's recent requests at the given positions.
+    ///
+    /// # Arguments:
+    /// * `indices` - The positions of the requests to return.
+    pub fn get_requests_by_index(&self, indices: &[usize]) -> Vec<ProxyRequestLog> {
+        let requests = self.requests.lock().unwrap();
+        indices
+            .iter()
+            .filter_map(|index| requests.get(*index).cloned())
+            .collect()
+    }
+
     /// Returns the Proxy's current running time.
     pub fn get_run_time(&mut self) -> String {
         let run_time = self.run_time.lock().unwrap();
@@ -414,233 +766,1633 @@ impl Proxy {
 
     /// Toggle the traffic filtering on/off.
     pub fn toggle_traffic_filtering(&self) {
-        let mut traffic_filter = self.traffic_filter.lock().unwrap();
-        let enabled = traffic_filter.get_enabled();
-        traffic_filter.set_enabled(!enabled);
+        self.change_filter("Toggled traffic filtering", |traffic_filter| {
+            let enabled = traffic_filter.get_enabled();
+            traffic_filter.set_enabled(!enabled)
+        });
         self.logger.debug("Traffic filtering has been toggled.");
     }
 
-    /// Toggle the traffic filter between: TrafficFilterType::Allow / TrafficFilterType::Deny.
-    pub fn switch_exclusion_list(&self) {
-        let mut traffic_filter = self.traffic_filter.lock().unwrap();
-        let switched_filter = traffic_filter.get_opposing_filter_type();
-        traffic_filter.set_filter_type(switched_filter);
-        self.logger.debug("Exclusion list has been switched.");
+    /// Returns the most recently resent request.
+    pub fn get_replay(&self) -> Option<ProxyReplay> {
+        self.replay.lock().unwrap().clone()
     }
 
-    /// Set the current exclusion list.
+    /// Resend a request through the Proxy's forwarding path, replacing the previous replay.
     ///
     /// # Arguments:
-    /// * `list` - A Vec<String> to set the exclusion list to.
-    pub fn set_exclusion_list(&mut self, list: Vec<String>) {
-        let mut traffic_filter = self.traffic_filter.lock().unwrap();
-        traffic_filter.set_filter_list(list);
-        self.logger.debug("Exclusion list has been set.");
-    }
+    /// * `request_id` - The identifier of the request being resent.
+    /// * `request` - The ProxyReplayRequest to send, either a copy or an edited version of the original.
+    pub fn replay_request(
+        &self,
+        request_id: usize,
+        request: ProxyReplayRequest,
+    ) -> Result<(), String> {
+        let built_request = request.build()?;
+
+        let message = format!("Resending request to: {}", request.uri);
+        self.logger.info(&message);
+
+        *self.replay.lock().unwrap() = Some(ProxyReplay {
+            request_id,
+            request: request.clone(),
+            status: ProxyReplayStatus::Sending,
+        });
 
-    /// Send an event to add a value to the current exclusion list
-    ///
-    /// # Arguments:
-    /// * `event_type` - A ProxyExclusionUpdateKind to Edit/Add/Remove an item in the current exclusion list.
-    pub fn update_exclusion_list(&mut self, event_type: ProxyExclusionUpdateKind) {
-        match event_type {
-            ProxyExclusionUpdateKind::Edit => {
-                let mut traffic_filter = self.traffic_filter.lock().unwrap();
-                traffic_filter.update_filter_list_item(
-                    self.selected_exclusion_row.index,
-                    self.selected_exclusion_row.value.clone(),
-                );
+        let replay = Arc::clone(&self.replay);
+        let settings = self.get_settings();
+        let logger = self.logger.clone();
 
-                self.selected_exclusion_row = ProxyExclusionRow::default();
-                self.logger.debug("Exclusion list value has been edited.");
-            }
-            ProxyExclusionUpdateKind::Add | ProxyExclusionUpdateKind::Remove => {
-                let mut traffic_filter = self.traffic_filter.lock().unwrap();
-                traffic_filter.update_filter_list(self.selected_value.clone());
-                self.logger.debug("Exclusion list has been updated.");
+        thread::spawn(move || {
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|error| error.to_string())
+                .and_then(|runtime| {
+                    runtime.block_on(replay_request(built_request, &settings, logger))
+                });
+
+            // Only update the replay if it hasn't been replaced by a newer one
+            if let Some(replay) = replay
+                .lock()
+                .unwrap()
+                .as_mut()
+                .filter(|replay| replay.request_id == request_id && replay.request == request)
+            {
+                replay.status = match result {
+                    Ok(response) => ProxyReplayStatus::Complete(response),
+                    Err(message) => ProxyReplayStatus::Failed(message),
+                };
             }
-        };
+        });
+
+        Ok(())
     }
-}
 
-/// Handles termination of the service.
-///
-/// # Arguments
-/// * `event` - The event sender to write current state.
-/// * `status` - The current ProxyEvent status.
-async fn handle_termination(
-    event: Option<std::sync::mpsc::Sender<ProxyEvent>>,
-    status: Arc<Mutex<ProxyEvent>>,
-) {
-    let (shutdown_sig, shutdown_rec) = tokio::sync::oneshot::channel::<()>();
+    /// Returns the Proxy's current ProxySettings.
+    pub fn get_settings(&self) -> ProxySettings {
+        self.settings.lock().unwrap().clone()
+    }
 
-    std::thread::spawn(move || loop {
-        thread::sleep(Duration::from_millis(1000));
+    /// Toggle the capturing of request and response bodies on/off.
+    pub fn toggle_body_capture(&self) {
+        let mut settings = self.settings.lock().unwrap();
+        settings.capture_bodies = !settings.capture_bodies;
+        self.logger.debug("Body capture has been toggled.");
+    }
 
-        let status = match status.lock() {
-            Ok(status) => status.clone(),
-            Err(_) => ProxyEvent::Terminating,
-        };
+    /// Set the HTTP version used to talk to upstream servers.
+    ///
+    /// # Arguments:
+    /// * `http_version` - The ProxyHttpVersion to use for new requests.
+    pub fn set_http_version(&self, http_version: ProxyHttpVersion) {
+        self.settings.lock().unwrap().http_version = http_version;
+        self.logger
+            .debug(format!("Upstream HTTP version has been set to: {}", http_version).as_str());
+    }
 
-        if status == ProxyEvent::Terminating {
-            shutdown_sig.send(()).unwrap();
-            break;
-        }
-    });
+    /// Set how connections to upstream servers are pooled.
+    ///
+    /// # Arguments:
+    /// * `idle_timeout` - How long, in seconds, a connection may be idle before it's closed.
+    /// * `max_connections` - The most connections that may be open to a single server.
+    pub fn set_pool_limits(&self, idle_timeout: u64, max_connections: usize) {
+        let mut settings = self.settings.lock().unwrap();
+        settings.pool_idle_timeout = idle_timeout;
+        settings.pool_max_connections = max_connections.max(1);
+        self.logger
+            .debug("Connection pool limits have been updated.");
+    }
 
-    if let Ok(_) = shutdown_rec.await {
-        if let Some(event) = event {
-            event.send(ProxyEvent::Terminated).unwrap();
-        }
+    /// Returns the number of timeouts that have fired for each host, most timeouts first.
+    pub fn get_timeout_stats(&self) -> Vec<(String, ProxyTimeoutCounts)> {
+        self.timeouts.lock().unwrap().get_hosts()
     }
-}
 
-/// Handle a server request.
-///
-/// # Arguments:
-/// * `request` - The request to proxy.
-/// * `event` - An internal event sender, to change the Proxy state.
-/// * `traffic_filter` - The current TrafficFilter configuration.
-/// * `logger` - The current logger to log events to.
-async fn handle_request(
-    request: Request<hyper::body::Incoming>,
-    event: Option<std::sync::mpsc::Sender<ProxyEvent>>,
-    traffic_filter: TrafficFilter,
-    logger: Logger,
-) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
-    let request_uri = request.uri().to_string();
+    /// Set how long, in seconds, to wait before one of the Proxy's timeouts fires.
+    ///
+    /// # Arguments:
+    /// * `timeout` - The ProxyTimeout to update.
+    /// * `seconds` - The number of seconds to wait, or 0 to disable the timeout.
+    pub fn set_timeout(&self, timeout: ProxyTimeout, seconds: u64) {
+        *self.settings.lock().unwrap().timeouts.get_mut(timeout) = seconds;
+        self.logger
+            .debug(format!("{} timeout has been set to: {}s", timeout, seconds).as_str());
+    }
 
-    let is_excluded_address = traffic_filter.in_filter_list(&request_uri);
-    let is_traffic_blocking = traffic_filter.is_blocking();
+    /// Set how blocked requests are answered.
+    ///
+    /// # Arguments:
+    /// * `block_page` - The ProxyBlockPage to answer new blocked requests with.
+    pub fn set_block_page(&self, block_page: ProxyBlockPage) {
+        self.settings.lock().unwrap().block_page = block_page;
+        self.logger.debug("Block page has been updated.");
+    }
 
-    if traffic_filter.get_enabled() {
-        let is_blocking_but_exluded = !is_excluded_address && is_traffic_blocking;
-        let is_allowing_but_excluded = is_excluded_address && !is_traffic_blocking;
-        let blocked = is_allowing_but_excluded || is_blocking_but_exluded;
+    /// Returns the exceptions requested from the block page, oldest first.
+    pub fn get_exception_requests(&self) -> Vec<ProxyExceptionRequest> {
+        self.exceptions.lock().unwrap().clone()
+    }
 
-        // Log the event
-        let request_log = ProxyRequestLog {
-            method: request.method().to_string(),
-            request: request_uri,
-            blocked: blocked,
+    /// Approve a requested exception, updating the current exclusion list so the URL is no longer blocked.
+    ///
+    /// An Allow List gains the URL's host, while a Deny List gains a rule that always allows the URL alone,
+    /// so the rule that blocked it keeps blocking everything else it matches.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the exception request to approve.
+    pub fn approve_exception(&self, index: usize) {
+        let Some(exception) = self.take_exception(index) else {
+            return;
         };
 
-        logger.debug(
-            format!(
-                "{} -> Request to: {} -> {}",
-                request_log.method,
-                request_log.request,
-                request_log.to_blocked_string()
-            )
-            .as_str(),
-        );
+        let description = format!("Approved exception for {}", exception.url);
+        self.change_filter(&description, |traffic_filter| {
+            if traffic_filter.is_blocking() {
+                let host = exception
+                    .url
+                    .parse::<Uri>()
+                    .ok()
+                    .and_then(|uri| uri.host().map(String::from))
+                    .unwrap_or_else(|| exception.url.clone());
+
+                match traffic_filter.in_filter_list(&host) {
+                    true => FilterDiff::default(),
+                    false => traffic_filter.add_filter_list_items(vec![TrafficRule::new(host)]),
+                }
+            } else {
+                let mut rule = TrafficRule::new(format!("^{}$", regex::escape(&exception.url)));
+                rule.kind = TrafficRuleKind::Regex;
+                rule.action = TrafficRuleAction::Allow;
 
-        // Record the request
-        if let Some(event) = event {
-            event
-                .send(ProxyEvent::RequestEvent(request_log.clone()))
-                .unwrap();
-        }
+                // Rules are matched in order, so the exception goes before the rule that blocked the URL
+                traffic_filter.insert_filter_list_item(0, rule)
+            }
+        });
 
-        // Return a generic response if the request was blocked
-        if blocked {
-            let mut resp = Response::new(full("Oopsie Whoopsie!"));
-            *resp.status_mut() = http::StatusCode::FORBIDDEN;
-            return Ok(resp);
-        }
+        let message = format!("Exception approved -> {}", exception.url);
+        self.logger.info(&message);
     }
 
-    // Proxy socket requests
-    if request.method() == Method::CONNECT {
-        if let Some(addr) = get_host_address(request.uri()) {
-            tokio::task::spawn(async move {
-                match hyper::upgrade::on(request).await {
-                    Ok(upgraded) => {
-                        if let Err(message) = tunnel(upgraded, addr).await {
-                            logger.warning(&message.to_string());
-                        };
-                    }
-                    Err(message) => {
-                        logger.warning(&message.to_string());
-                    }
-                }
-            });
-
-            return Ok(Response::new(empty()));
-        } else {
-            let mut resp = Response::new(full("CONNECT must be to a socket address"));
-            *resp.status_mut() = http::StatusCode::BAD_REQUEST;
-            return Ok(resp);
+    /// Dismiss a requested exception without changing the exclusion list.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the exception request to dismiss.
+    pub fn dismiss_exception(&self, index: usize) {
+        if let Some(exception) = self.take_exception(index) {
+            let message = format!("Exception dismissed -> {}", exception.url);
+            self.logger.debug(&message);
         }
     }
 
-    // Proxy web requests
-    if let Some(host) = request.uri().host() {
-        let port = request.uri().port_u16().unwrap_or(80);
+    /// Removes an exception request from the review queue, returning it.
+    fn take_exception(&self, index: usize) -> Option<ProxyExceptionRequest> {
+        let mut exceptions = self.exceptions.lock().unwrap();
+        (index < exceptions.len()).then(|| exceptions.remove(index))
+    }
 
-        let stream = TcpStream::connect((host, port)).await.unwrap();
-        let io = TokioIo::new(stream);
+    /// Returns the hosts temporarily allowed through the traffic filter, removing any that have expired.
+    pub fn get_temporary_allows(&self) -> Vec<TemporaryAllow> {
+        self.temporary_allows.expire(&self.logger);
+        self.temporary_allows.entries()
+    }
 
-        let (mut sender, conn) = hyper::client::conn::http1::Builder::new()
-            .preserve_header_case(true)
-            .title_case_headers(true)
-            .handshake(io)
-            .await?;
+    /// Let a blocked host through the traffic filter for a while, without changing the exclusion list.
+    ///
+    /// # Arguments:
+    /// * `host` - The host to allow.
+    /// * `duration` - How long to allow the host for.
+    pub fn allow_temporarily(&self, host: &str, duration: SnoozeDuration) {
+        self.temporary_allows.allow(host, duration);
 
-        tokio::task::spawn(async move {
-            if let Err(message) = conn.await {
-                logger.warning(&message.to_string());
-            };
-        });
+        let message = format!("Temporarily allowed ({}) -> {}", duration, host);
+        self.logger.info(&message);
+    }
 
-        let response = sender.send_request(request).await?;
-        return Ok(response.map(|b| b.boxed()));
-    } else {
-        logger.debug(
-            format!(
-                "Host address could not be found for: {}",
-                request.uri().to_string()
-            )
-            .as_str(),
-        );
-        let mut response = Response::new(full("Host address could not be processed."));
-        *response.status_mut() = http::StatusCode::BAD_REQUEST;
-        return Ok(response);
+    /// Stop letting a temporarily allowed host through the traffic filter.
+    ///
+    /// # Arguments:
+    /// * `host` - The host to stop allowing.
+    pub fn revoke_temporary_allow(&self, host: &str) {
+        if self.temporary_allows.revoke(host) {
+            let message = format!("Temporary allow revoked -> {}", host);
+            self.logger.info(&message);
+        }
     }
-}
 
-/// Tunnel a connection bidirectionally.
-///
-/// # Arguments:
-/// * `upgraded` - The upgraded connection to copy data to/from.
-/// * `address` - The target address to copy data to/from.
-async fn tunnel(upgraded: Upgraded, address: String) -> std::io::Result<()> {
-    let mut server = TcpStream::connect(address).await?;
-    let mut upgraded_connection = TokioIo::new(upgraded);
+    /// Set the rules that change the headers of requests and responses.
+    ///
+    /// # Arguments:
+    /// * `header_rules` - The HeaderRules to apply to new requests.
+    pub fn set_header_rules(&self, header_rules: Vec<HeaderRule>) {
+        self.settings.lock().unwrap().header_rules = header_rules;
+        self.logger.debug("Header rules have been updated.");
+    }
 
-    tokio::io::copy_bidirectional(&mut upgraded_connection, &mut server).await?;
+    /// Set the rules that answer requests from local files.
+    ///
+    /// # Arguments:
+    /// * `map_local` - The MapLocalRules to apply to new requests.
+    pub fn set_map_local_rules(&self, map_local: Vec<MapLocalRule>) {
+        self.settings.lock().unwrap().map_local = map_local;
+        self.logger.debug("Map local rules have been updated.");
+    }
 
-    Ok(())
-}
+    /// Set the network conditions simulated for new requests and tunnels.
+    ///
+    /// # Arguments:
+    /// * `throttle` - The ProxyThrottle to simulate.
+    pub fn set_throttle(&self, throttle: ProxyThrottle) {
+        self.settings.lock().unwrap().throttle = throttle;
+        self.logger.debug("Network conditions have been updated.");
+    }
 
-/// Get the current URI's host address.
+    /// Add a rule that injects faults into matching requests.
+    ///
+    /// # Arguments:
+    /// * `rule` - The FaultRule to add.
+    pub fn add_fault_rule(&self, rule: FaultRule) {
+        self.settings.lock().unwrap().faults.push(rule);
+        self.logger.debug("Fault rule has been added.");
+    }
+
+    /// Update the settings of a fault rule, the faults it has injected are kept.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the FaultRule to update.
+    /// * `rule` - The edited FaultRule.
+    pub fn update_fault_rule(&self, index: usize, rule: FaultRule) {
+        if let Some(current) = self.settings.lock().unwrap().faults.get_mut(index) {
+            current.update(rule);
+            self.logger.debug("Fault rule has been updated.");
+        }
+    }
+
+    /// Start counting the faults injected by a rule from zero again.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the FaultRule to reset.
+    pub fn reset_fault_count(&self, index: usize) {
+        if let Some(current) = self.settings.lock().unwrap().faults.get_mut(index) {
+            current.injected = 0;
+        }
+    }
+
+    /// Remove a rule that injects faults.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the FaultRule to remove.
+    pub fn remove_fault_rule(&self, index: usize) {
+        let mut settings = self.settings.lock().unwrap();
+        if index < settings.faults.len() {
+            settings.faults.remove(index);
+            self.logger.debug("Fault rule has been removed.");
+        }
+    }
+
+    /// Add a rule that limits the rate of requests, and traffic, to a host.
+    ///
+    /// # Arguments:
+    /// * `rule` - The RateLimitRule to add.
+    pub fn add_rate_limit(&self, rule: RateLimitRule) {
+        self.settings.lock().unwrap().rate_limits.push(rule);
+        self.logger.debug("Rate limit has been added.");
+    }
+
+    /// Update the settings of a rate limit rule, the usage it has counted is kept.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the RateLimitRule to update.
+    /// * `rule` - The edited RateLimitRule.
+    pub fn update_rate_limit(&self, index: usize, rule: RateLimitRule) {
+        if let Some(current) = self.settings.lock().unwrap().rate_limits.get_mut(index) {
+            current.update(rule);
+            self.logger.debug("Rate limit has been updated.");
+        }
+    }
+
+    /// Clear the usage a rate limit rule has counted in its current window.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the RateLimitRule to reset.
+    pub fn reset_rate_limit_usage(&self, index: usize) {
+        if let Some(current) = self.settings.lock().unwrap().rate_limits.get_mut(index) {
+            current.usage = QuotaUsage::default();
+        }
+    }
+
+    /// Remove a rule that limits the rate of requests.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the RateLimitRule to remove.
+    pub fn remove_rate_limit(&self, index: usize) {
+        let mut settings = self.settings.lock().unwrap();
+        if index < settings.rate_limits.len() {
+            settings.rate_limits.remove(index);
+            self.logger.debug("Rate limit has been removed.");
+        }
+    }
+
+    /// Set where and how much the Proxy caches responses on disk.
+    ///
+    /// # Arguments:
+    /// * `cache` - The ProxyCacheSettings to use.
+    pub fn set_cache_settings(&self, cache: ProxyCacheSettings) {
+        self.cache.configure(&cache);
+        self.settings.lock().unwrap().cache = cache;
+        self.logger.debug("Cache settings have been updated.");
+    }
+
+    /// Returns the responses stored in the cache, the most recently used first.
+    pub fn get_cache_entries(&self) -> Vec<ProxyCacheEntry> {
+        self.cache.entries()
+    }
+
+    /// Returns the current statistics of the cache.
+    pub fn get_cache_stats(&self) -> ProxyCacheStats {
+        self.cache.stats()
+    }
+
+    /// Remove a single response from the cache.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the ProxyCacheEntry to remove.
+    pub fn remove_cache_entry(&self, id: &str) {
+        self.cache.remove(id);
+        self.logger.debug("Cache entry has been removed.");
+    }
+
+    /// Remove every response from the cache.
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+        self.logger.info("Cache has been cleared.");
+    }
+
+    /// Returns the current statistics of the upstream connection pool.
+    pub fn get_pool_stats(&self) -> ProxyPoolStats {
+        self.pool.get_stats()
+    }
+
+    /// Toggle the traffic filter between: TrafficFilterType::Allow / TrafficFilterType::Deny.
+    pub fn switch_exclusion_list(&self) {
+        self.change_filter("Switched exclusion list", |traffic_filter| {
+            let switched_filter = traffic_filter.get_opposing_filter_type();
+            traffic_filter.set_filter_type(switched_filter)
+        });
+        self.logger.debug("Exclusion list has been switched.");
+    }
+
+    /// Set when the current exclusion list filters traffic.
+    ///
+    /// # Arguments:
+    /// * `schedule` - The TrafficSchedule to filter traffic during, or None to always filter it.
+    pub fn set_filter_schedule(&self, schedule: Option<TrafficSchedule>) {
+        self.change_filter("Changed the schedule", |traffic_filter| {
+            traffic_filter.set_schedule(schedule)
+        });
+        self.logger
+            .debug("Exclusion list schedule has been updated.");
+    }
+
+    /// Clear the hit counters of the current exclusion list.
+    pub fn reset_rule_hits(&self) {
+        self.traffic_filter.lock().unwrap().reset_hits();
+        self.logger
+            .debug("Exclusion list hit counters have been reset.");
+    }
+
+    /// Set whether the current exclusion list only records the requests it would block.
+    ///
+    /// # Arguments:
+    /// * `audit` - A bool value, whether blocked requests are forwarded anyway.
+    pub fn set_filter_audit(&self, audit: bool) {
+        self.change_filter("Changed audit mode", |traffic_filter| {
+            traffic_filter.set_audit(audit)
+        });
+        self.logger
+            .debug("Exclusion list audit mode has been updated.");
+    }
+
+    /// Set the current exclusion list.
+    ///
+    /// # Arguments:
+    /// * `list` - A Vec<TrafficRule> to set the exclusion list to.
+    pub fn set_exclusion_list(&mut self, list: Vec<TrafficRule>) {
+        self.change_filter("Replaced exclusion list", |traffic_filter| {
+            traffic_filter.set_filter_list(list)
+        });
+        self.logger.debug("Exclusion list has been set.");
+    }
+
+    /// Send an event to add a value to the current exclusion list
+    ///
+    /// # Arguments:
+    /// * `event_type` - A ProxyExclusionUpdateKind to Edit/Add/Remove an item in the current exclusion list.
+    pub fn update_exclusion_list(&mut self, event_type: ProxyExclusionUpdateKind) {
+        match event_type {
+            ProxyExclusionUpdateKind::Edit => {
+                let row = &self.selected_exclusion_row;
+                let description = format!("Edited rule {}", row.value.pattern);
+                self.change_filter(&description, |traffic_filter| {
+                    traffic_filter.update_filter_list_item(row.index, row.value.clone())
+                });
+
+                self.selected_exclusion_row = ProxyExclusionRow::default();
+                self.logger.debug("Exclusion list value has been edited.");
+            }
+            ProxyExclusionUpdateKind::Add | ProxyExclusionUpdateKind::Remove => {
+                let description = match event_type {
+                    ProxyExclusionUpdateKind::Add => format!("Added rule {}", self.selected_value),
+                    _ => format!("Removed rule {}", self.selected_value),
+                };
+                self.change_filter(&description, |traffic_filter| {
+                    traffic_filter.update_filter_list(self.selected_value.clone())
+                });
+                self.logger.debug("Exclusion list has been updated.");
+            }
+        };
+    }
+
+    /// Add several items to the current exclusion list, skipping those already in it.
+    ///
+    /// # Arguments:
+    /// * `rules` - The TrafficRules to add to the current exclusion list.
+    pub fn add_exclusion_rules(&mut self, rules: Vec<TrafficRule>) {
+        // Rules already in the list are skipped, so the description counts the ones that were added
+        let mut traffic_filter = self.traffic_filter.lock().unwrap();
+        let diff = traffic_filter.add_filter_list_items(rules);
+        let added = diff.added_rules();
+        self.record_filter_change(&format!("Added {} rules", added), diff);
+        drop(traffic_filter);
+
+        let message = format!("{} -> {}", "Added items to the exclusion list", added);
+        self.logger.debug(&message);
+    }
+
+    /// Import items into the current exclusion list, merging them into it or replacing it.
+    ///
+    /// # Arguments:
+    /// * `source` - Where the items were imported from, e.g. a file name.
+    /// * `rules` - The imported items, along with whether their files had their settings.
+    /// * `mode` - The ImportMode deciding what happens to the items already in the list.
+    pub fn import_exclusion_list(
+        &mut self,
+        source: &str,
+        rules: Vec<ImportedRule>,
+        mode: ImportMode,
+    ) {
+        let description = format!("Imported {} ({})", source, mode);
+        self.change_filter(&description, |traffic_filter| {
+            let merged = merge_rules(traffic_filter.get_filter_list(), rules, mode);
+            traffic_filter.set_filter_list(merged)
+        });
+
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        let message = format!("{} -> {}", "Imported exclusion list", source);
+        self.logger.info(&message);
+    }
+
+    /// Remove several items from the current exclusion list.
+    ///
+    /// # Arguments:
+    /// * `indices` - The positions of the items to remove from the current exclusion list.
+    pub fn remove_exclusion_rules(&mut self, indices: &[usize]) {
+        let description = format!("Removed {} rules", indices.len());
+        self.change_filter(&description, |traffic_filter| {
+            traffic_filter.remove_filter_list_items(indices)
+        });
+
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger
+            .debug("Exclusion list values have been removed.");
+    }
+
+    /// Move several items from the current exclusion list to the other one.
+    ///
+    /// # Arguments:
+    /// * `indices` - The positions of the items to move in the current exclusion list.
+    pub fn move_exclusion_rules(&mut self, indices: &[usize]) {
+        let traffic_filter = self.get_traffic_filter();
+        let description = format!(
+            "Moved {} rules to the {} List",
+            indices.len(),
+            traffic_filter.get_filter_type()
+        );
+        self.change_filter(&description, |traffic_filter| {
+            traffic_filter.move_filter_list_items(indices)
+        });
+
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger.debug("Exclusion list values have been moved.");
+    }
+
+    /// Sort the current exclusion list.
+    ///
+    /// # Arguments:
+    /// * `order` - The TrafficRuleOrder to sort the current exclusion list in.
+    pub fn sort_exclusion_list(&mut self, order: TrafficRuleOrder) {
+        let description = format!("Sorted by {}", order);
+        self.change_filter(&description, |traffic_filter| {
+            traffic_filter.sort_filter_list(order)
+        });
+
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger.debug("Exclusion list has been sorted.");
+    }
+
+    /// Remove the items in the current exclusion list that duplicate an earlier item.
+    pub fn remove_duplicate_rules(&mut self) {
+        self.change_filter("Removed duplicate rules", |traffic_filter| {
+            traffic_filter.remove_duplicates()
+        });
+
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger
+            .debug("Exclusion list duplicates have been removed.");
+    }
+
+    /// Returns what the next undo and redo of the traffic filter would change, if anything.
+    pub fn get_undo_redo(&self) -> (Option<String>, Option<String>) {
+        let history = self.filter_history.lock().unwrap();
+        (history.next_undo(), history.next_redo())
+    }
+
+    /// Returns the change log of the traffic filter, oldest first.
+    pub fn get_change_log(&self) -> Vec<FilterChange> {
+        self.filter_history.lock().unwrap().log().to_vec()
+    }
+
+    /// Undo the last change made to the traffic filter.
+    pub fn undo_filter_change(&mut self) {
+        let mut traffic_filter = self.traffic_filter.lock().unwrap();
+        let Some(diff) = self.filter_history.lock().unwrap().undo() else {
+            return;
+        };
+
+        traffic_filter.apply_diff(&diff);
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger.info("Traffic filter change has been undone.");
+    }
+
+    /// Redo the last change to the traffic filter that was undone.
+    pub fn redo_filter_change(&mut self) {
+        let mut traffic_filter = self.traffic_filter.lock().unwrap();
+        let Some(diff) = self.filter_history.lock().unwrap().redo() else {
+            return;
+        };
+
+        traffic_filter.apply_diff(&diff);
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger.info("Traffic filter change has been redone.");
+    }
+
+    /// Restore the traffic filter as it was right after a change in the change log, which can be undone.
+    ///
+    /// # Arguments:
+    /// * `index` - The position of the change in the change log.
+    pub fn restore_filter_change(&mut self, index: usize) {
+        let mut traffic_filter = self.traffic_filter.lock().unwrap();
+        let (time, state) = {
+            let history = self.filter_history.lock().unwrap();
+            let Some(change) = history.log().get(index) else {
+                return;
+            };
+            let Some(state) = history.state_after(index, &traffic_filter) else {
+                return;
+            };
+            (change.time, state)
+        };
+
+        let description = format!(
+            "Restored the filter as of {}",
+            time.format("%Y-%m-%d %H:%M:%S")
+        );
+        let diff = traffic_filter.restore(state);
+        self.record_filter_change(&description, diff);
+        drop(traffic_filter);
+        self.selected_exclusion_row = ProxyExclusionRow::default();
+        self.logger.info(&description);
+    }
+
+    /// Clear the change log of the traffic filter, keeping its undo and redo.
+    pub fn clear_change_log(&self) {
+        self.filter_history.lock().unwrap().clear_log();
+        self.logger.debug("Change log has been cleared.");
+    }
+
+    /// Apply a change to the traffic filter, recording it so it can be undone.
+    ///
+    /// # Arguments:
+    /// * `description` - What the change does, shown in the change log.
+    /// * `change` - The function that changes the traffic filter, returning what it changed.
+    fn change_filter(
+        &self,
+        description: &str,
+        change: impl FnOnce(&mut TrafficFilter) -> FilterDiff,
+    ) {
+        let mut traffic_filter = self.traffic_filter.lock().unwrap();
+        let diff = change(&mut traffic_filter);
+        self.record_filter_change(description, diff);
+    }
+
+    /// Record a change made to the traffic filter, if it changed anything, so it can be undone.
+    ///
+    /// The traffic filter should still be locked, so changes are recorded in the order they were made.
+    ///
+    /// # Arguments:
+    /// * `description` - What the change did, shown in the change log.
+    /// * `diff` - The FilterDiff of the change.
+    fn record_filter_change(&self, description: &str, diff: FilterDiff) {
+        if !diff.is_empty() {
+            self.filter_history
+                .lock()
+                .unwrap()
+                .record(description, diff);
+        }
+    }
+}
+
+/// Handles termination of the service.
 ///
 /// # Arguments
-/// * `uri` - The given URI.
-fn get_host_address(uri: &Uri) -> Option<String> {
-    uri.authority().and_then(|auth| Some(auth.to_string()))
+/// * `event` - The event sender to write current state.
+/// * `status` - The current ProxyEvent status.
+async fn handle_termination(
+    event: Option<std::sync::mpsc::Sender<ProxyEvent>>,
+    status: Arc<Mutex<ProxyEvent>>,
+) {
+    let (shutdown_sig, shutdown_rec) = tokio::sync::oneshot::channel::<()>();
+
+    std::thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(1000));
+
+        let status = match status.lock() {
+            Ok(status) => status.clone(),
+            Err(_) => ProxyEvent::Terminating,
+        };
+
+        if status == ProxyEvent::Terminating {
+            shutdown_sig.send(()).unwrap();
+            break;
+        }
+    });
+
+    if shutdown_rec.await.is_ok() {
+        if let Some(event) = event {
+            event.send(ProxyEvent::Terminated).unwrap();
+        }
+    }
+}
+
+/// The shared state handed to each request handled by the service.
+#[derive(Clone)]
+struct ProxyContext {
+    event: Option<std::sync::mpsc::Sender<ProxyEvent>>,
+    traffic_filter: Arc<Mutex<TrafficFilter>>,
+    exceptions: Arc<Mutex<Vec<ProxyExceptionRequest>>>,
+    temporary_allows: TemporaryAllows,
+    settings: Arc<Mutex<ProxySettings>>,
+    request_id: Arc<AtomicUsize>,
+    pool: ConnectionPool,
+    cache: HttpCache,
+    timeouts: Arc<Mutex<ProxyTimeoutStats>>,
+    // Exception links have to carry it, so other pages can't queue exceptions
+    exception_token: Arc<str>,
+    logger: Logger,
+}
+
+impl ProxyContext {
+    /// Send a ProxyEvent, ignoring it if the event handler has already stopped.
+    ///
+    /// # Arguments:
+    /// * `event` - A ProxyEvent to send to the event handler.
+    fn send(&self, event: ProxyEvent) {
+        if let Some(sender) = &self.event {
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Logs and counts a timeout against a host.
+    ///
+    /// # Arguments:
+    /// * `host` - The host, or client address when no host is known, the timeout fired for.
+    /// * `timeout` - The TimeoutError that was returned.
+    fn record_timeout(&self, host: &str, timeout: &TimeoutError) {
+        let message = format!("{} -> {}", host, timeout);
+        match timeout.timeout {
+            ProxyTimeout::Idle | ProxyTimeout::TunnelIdle => self.logger.debug(&message),
+            _ => self.logger.warning(&message),
+        }
+
+        self.timeouts.lock().unwrap().record(host, timeout.timeout);
+    }
+
+    /// Logs an error that ended a request, counting it if it was a timeout.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `host` - The host the request was made to.
+    /// * `error` - The error that ended the request.
+    fn record_error(&self, id: usize, host: &str, error: &ForwardError) {
+        match error.downcast_ref::<TimeoutError>() {
+            Some(timeout) => self.record_timeout(host, timeout),
+            None => self.logger.warning(&error.to_string()),
+        }
+
+        self.send(ProxyEvent::RequestUpdate(
+            id,
+            ProxyRequestUpdate::Error(error.to_string()),
+        ));
+    }
+
+    /// Queues an exception requested from the block page, returning the response to show the client.
+    ///
+    /// # Arguments:
+    /// * `request` - The request made to the exception link.
+    /// * `client` - The address of the client making the request.
+    fn request_exception<B>(
+        &self,
+        request: &Request<B>,
+        client: SocketAddr,
+    ) -> Response<ProxyBody> {
+        let exception =
+            parse_exception_request(request.uri(), client.to_string(), &self.exception_token);
+
+        if let Ok(exception) = &exception {
+            let mut exceptions = self.exceptions.lock().unwrap();
+
+            // Asking again for the same URL doesn't add another entry to review
+            if !exceptions.iter().any(|queued| queued.url == exception.url) {
+                exceptions.push(exception.clone());
+                self.logger
+                    .info(format!("Exception requested -> {}", exception.url).as_str());
+            }
+        }
+
+        exception_response(
+            exception.as_ref().map_err(|error| *error),
+            request.headers(),
+        )
+    }
+
+    /// Answers a blocked request with the block page, or resets the connection.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `started` - When the request was received.
+    /// * `blocked` - The details of the blocked request.
+    /// * `headers` - The headers of the blocked request.
+    fn block(
+        &self,
+        id: usize,
+        started: Instant,
+        blocked: &BlockedRequest,
+        headers: &HeaderMap,
+    ) -> Response<ProxyBody> {
+        let block_page = self.settings.lock().unwrap().block_page.clone();
+        let response = block_response(&block_page, blocked, headers, &self.exception_token);
+
+        if response.extensions().get::<ResetConnection>().is_some() {
+            self.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::Error(String::from("Connection reset by the Proxy.")),
+            ));
+            return response;
+        }
+
+        self.observe_response(id, started, response)
+    }
+
+    /// Counts a hit against the exclusion list item that matched a request.
+    ///
+    /// # Arguments:
+    /// * `rule` - The TrafficRule that matched the request.
+    /// * `uri` - The URI, or address, of the matched request.
+    fn record_rule_hit(&self, rule: &TrafficRule, uri: &str) {
+        self.traffic_filter
+            .lock()
+            .unwrap()
+            .record_hit(rule, uri, chrono::Local::now());
+    }
+
+    /// Counts bytes received from a host against the quota of the rate limit rule that allowed the request.
+    ///
+    /// # Arguments:
+    /// * `rate_limit` - The host pattern of the rule that allowed the request, if any.
+    /// * `bytes` - The number of bytes received.
+    fn record_quota_bytes(&self, rate_limit: Option<&str>, bytes: u64) {
+        if let Some(pattern) = rate_limit {
+            record_bytes(
+                &mut self.settings.lock().unwrap().rate_limits,
+                pattern,
+                bytes,
+            );
+        }
+    }
+
+    /// Applies the header rules to the headers of a request, or its response, and records the changes.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `direction` - Whether the headers belong to the request or its response.
+    /// * `host` - The host the request is being sent to.
+    /// * `headers` - The headers to change.
+    fn change_headers(
+        &self,
+        id: usize,
+        direction: HeaderDirection,
+        host: &str,
+        headers: &mut HeaderMap,
+    ) {
+        let changes = apply_header_rules(
+            &self.settings.lock().unwrap().header_rules,
+            direction,
+            host,
+            headers,
+        );
+        self.record_header_changes(id, changes);
+    }
+
+    /// Records the changes header rules made to a request, or its response.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `changes` - The changes that were made.
+    fn record_header_changes(&self, id: usize, changes: Vec<ProxyHeaderChange>) {
+        if !changes.is_empty() {
+            self.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::HeaderChanges(changes),
+            ));
+        }
+    }
+
+    /// Logs a rule whose action couldn't be applied, returning the response to show the client.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `started` - When the request was received.
+    /// * `message` - Why the rule's action couldn't be applied.
+    fn rule_error(&self, id: usize, started: Instant, message: &str) -> Response<ProxyBody> {
+        self.logger.warning(message);
+        self.send(ProxyEvent::RequestUpdate(
+            id,
+            ProxyRequestUpdate::Error(message.to_string()),
+        ));
+
+        let mut response = Response::new(full(message.to_string()));
+        *response.status_mut() = http::StatusCode::BAD_GATEWAY;
+        self.observe_response(id, started, response)
+    }
+
+    /// Returns the identifier for the next request.
+    fn next_request_id(&self) -> usize {
+        self.request_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Wraps the body of a request so its size, and optionally its content, are recorded.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `request` - The request to observe.
+    fn observe_request<B>(&self, id: usize, request: Request<B>) -> Request<ProxyBody>
+    where
+        B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + Sync + Unpin + 'static,
+    {
+        let event = self.event.clone();
+        let capture = self.settings.lock().unwrap().capture_bodies;
+
+        request.map(|body| {
+            CaptureBody::new(body, capture, move |body_log| {
+                if let Some(sender) = event {
+                    let _ = sender.send(ProxyEvent::RequestUpdate(
+                        id,
+                        ProxyRequestUpdate::RequestBody(body_log),
+                    ));
+                }
+            })
+            .boxed()
+        })
+    }
+
+    /// Records the response to a request, wrapping its body so its size, and optionally its content, are recorded.
+    ///
+    /// # Arguments:
+    /// * `id` - The identifier of the recorded request.
+    /// * `started` - When the request was received.
+    /// * `response` - The response to observe.
+    fn observe_response(
+        &self,
+        id: usize,
+        started: Instant,
+        response: Response<ProxyBody>,
+    ) -> Response<ProxyBody> {
+        self.send(ProxyEvent::RequestUpdate(
+            id,
+            ProxyRequestUpdate::Response(ProxyResponseLog {
+                status: response.status().as_u16(),
+                version: format!("{:?}", response.version()),
+                headers: headers_to_vec(response.headers()),
+                time_to_headers: started.elapsed(),
+            }),
+        ));
+
+        let event = self.event.clone();
+        let capture = self.settings.lock().unwrap().capture_bodies;
+
+        response.map(|body| {
+            CaptureBody::new(body, capture, move |body_log| {
+                if let Some(sender) = event {
+                    let _ = sender.send(ProxyEvent::RequestUpdate(
+                        id,
+                        ProxyRequestUpdate::ResponseBody(body_log, started.elapsed()),
+                    ));
+                }
+            })
+            .boxed()
+        })
+    }
 }
 
-/// Create an empty response body.
-fn empty() -> BoxBody<Bytes, hyper::Error> {
-    Empty::<Bytes>::new()
-        .map_err(|never| match never {})
-        .boxed()
+/// Serve a client connection, closing it if the client is too slow to send a request or stays idle.
+///
+/// # Arguments:
+/// * `stream` - The client connection.
+/// * `client` - The address of the client.
+/// * `context` - The shared state of the service.
+async fn serve_client(stream: TcpStream, client: SocketAddr, context: ProxyContext) {
+    // Keep a handle to the socket so a timeout response can be written once the connection is dropped
+    let (stream, writer) = match stream.into_std().and_then(|stream| {
+        let writer = stream.try_clone()?;
+        Ok((TcpStream::from_std(stream)?, writer))
+    }) {
+        Ok(streams) => streams,
+        Err(message) => {
+            context.logger.warning(&message.to_string());
+            return;
+        }
+    };
+
+    let activity = ConnectionActivity::new();
+    let io = TokioIo::new(ActivityIo::new(stream, Arc::clone(&activity)));
+
+    // Create a request service for the client
+    let service_context = context.clone();
+    let service_activity = Arc::clone(&activity);
+    let proxy_service = service_fn(move |request| {
+        let active_request = service_activity.start_request();
+        let activity = Arc::clone(&service_activity);
+        let context = service_context.clone();
+
+        async move {
+            let response = handle_request(request, client, context).await?;
+
+            // Nothing is sent for a reset, the connection is dropped while the request is still active
+            if response.extensions().get::<ResetConnection>().is_some() {
+                activity.request_reset();
+                return std::future::pending().await;
+            }
+
+            // Throttled responses may reset the connection part way through their body
+            if let Some(reset) = response.extensions().get::<ResetHandle>() {
+                reset.attach(Arc::clone(&activity));
+            }
+
+            // The request stays active until its response has been sent
+            Ok::<_, hyper::Error>(response.map(|body| {
+                body.map_frame(move |frame| {
+                    let _ = &active_request;
+                    frame
+                })
+                .boxed()
+            }))
+        }
+    });
+
+    // Serve HTTP/1.1, or HTTP/2 when the client sends its preface (h2c prior knowledge)
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .preserve_header_case(true)
+        .title_case_headers(true);
+
+    let connection = builder.serve_connection_with_upgrades(io, proxy_service);
+    let timeouts = || {
+        let timeouts = context.settings.lock().unwrap().timeouts.clone();
+        (
+            timeouts.get(ProxyTimeout::RequestHeaders),
+            timeouts.get(ProxyTimeout::Idle),
+        )
+    };
+
+    tokio::select! {
+        _ = connection => {}
+        timeout = client_timeout(&activity, timeouts) => {
+            if timeout.timeout == ProxyTimeout::RequestHeaders {
+                // The socket is non-blocking, so the response is written through the runtime, but a client
+                // that stopped reading isn't waited on for long
+                let response = async {
+                    TcpStream::from_std(writer)?
+                        .write_all(b"HTTP/1.1 408 Request Timeout\r\nConnection: close\r\nContent-Length: 0\r\n\r\n")
+                        .await
+                };
+                match tokio::time::timeout(Duration::from_secs(1), response).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(message)) => context.logger.debug(&message.to_string()),
+                    Err(_) => context.logger.debug("Timed out writing the request timeout response."),
+                }
+            }
+
+            context.record_timeout(&client.ip().to_string(), &timeout);
+        }
+        _ = activity.reset_requested() => {
+            // Closing a socket that lingers for no time sends a reset rather than a graceful close
+            if let Err(message) = socket2::SockRef::from(&writer).set_linger(Some(Duration::ZERO)) {
+                context.logger.warning(&message.to_string());
+            }
+        }
+    }
 }
 
-/// Create an body from the given bytes.
-fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
-    Full::new(chunk.into())
-        .map_err(|never| match never {})
-        .boxed()
+/// Handle a server request.
+///
+/// # Arguments:
+/// * `request` - The request to proxy.
+/// * `client` - The address of the client making the request.
+/// * `context` - The shared state of the service.
+async fn handle_request(
+    request: Request<hyper::body::Incoming>,
+    client: SocketAddr,
+    context: ProxyContext,
+) -> Result<Response<ProxyBody>, hyper::Error> {
+    let started = Instant::now();
+
+    // Requests for an exception are answered by the Proxy itself, so they can't be blocked
+    if is_exception_request(request.uri()) {
+        return Ok(context.request_exception(&request, client));
+    }
+
+    let logger = context.logger.clone();
+
+    let mut request_log = ProxyRequestLog::new(context.next_request_id(), client, &request);
+    let id = request_log.id;
+
+    // Only the matched rule is copied out of the traffic filter, which keeps the regexes it compiled
+    let (mut blocked, rule, audited, profile) = {
+        let traffic_filter = context.traffic_filter.lock().unwrap();
+        let (blocked, rule) =
+            traffic_filter.filter_request(&request_log.request, chrono::Local::now());
+        let audited = traffic_filter.is_audited(rule.as_ref());
+        (blocked, rule, audited, traffic_filter.get_profile_name())
+    };
+
+    // Hosts allowed for a while from the Filter view get through without changing the exclusion list
+    if blocked
+        && context
+            .temporary_allows
+            .is_allowed(&request_log.host, chrono::Local::now())
+    {
+        blocked = false;
+    }
+
+    // Profiles and rules in audit mode only record the requests they would block
+    request_log.would_block = blocked && audited;
+    request_log.blocked = blocked && !request_log.would_block;
+    request_log.rule = rule.as_ref().map(|rule| rule.pattern.clone());
+
+    if let Some(rule) = &rule {
+        context.record_rule_hit(rule, &request_log.request);
+    }
+
+    // Rules with an action take the request instead of allowing or blocking it, tunnels can only be rewritten
+    let action_rule = rule.filter(|rule| match rule.action {
+        TrafficRuleAction::Filter | TrafficRuleAction::Allow => false,
+        TrafficRuleAction::Rewrite => true,
+        TrafficRuleAction::Redirect | TrafficRuleAction::Respond => {
+            request.method() != Method::CONNECT
+        }
+    });
+    request_log.action = action_rule
+        .as_ref()
+        .and_then(|rule| rule.describe_action(&request_log.request));
+
+    // Log the event
+    logger.debug(
+        format!(
+            "{} -> Request to: {} -> {}",
+            request_log.method,
+            request_log.request,
+            request_log.to_blocked_string()
+        )
+        .as_str(),
+    );
+
+    // Record the request
+    let blocked = request_log.blocked.then(|| BlockedRequest {
+        url: request_log.request.clone(),
+        rule: request_log.rule.clone(),
+        profile: profile.clone(),
+        time: request_log.timestamp,
+    });
+    let host = request_log.host.clone();
+    context.send(ProxyEvent::RequestEvent(Box::new(request_log)));
+
+    // Answer with the block page if the request was blocked
+    if let Some(blocked) = blocked {
+        return Ok(context.block(id, started, &blocked, request.headers()));
+    }
+
+    // Refuse requests over the rate limit, or quota, of their host until it allows them again
+    let rate_limit = check_rate_limit(&mut context.settings.lock().unwrap().rate_limits, &host);
+    let rate_limit = match rate_limit {
+        Ok(rate_limit) => rate_limit,
+        Err(exceeded) => {
+            let message = format!("{} -> Rate limited: {}", host, exceeded.reason);
+            logger.debug(&message);
+
+            let blocked = exceeded.action == RateLimitAction::Block;
+            context.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::RateLimited {
+                    reason: exceeded.reason.clone(),
+                    blocked,
+                },
+            ));
+
+            if blocked {
+                let blocked = BlockedRequest {
+                    url: request.uri().to_string(),
+                    rule: Some(format!(
+                        "Rate limit for {}: {}",
+                        exceeded.host, exceeded.reason
+                    )),
+                    profile,
+                    time: chrono::Local::now(),
+                };
+                return Ok(context.block(id, started, &blocked, request.headers()));
+            }
+
+            return Ok(context.observe_response(id, started, rate_limit_response(&exceeded)));
+        }
+    };
+
+    // Proxy socket requests
+    if request.method() == Method::CONNECT {
+        let address = get_host_address(request.uri()).map(|address| match &action_rule {
+            Some(rule) => rewrite_address(rule, &address),
+            None => Ok(address),
+        });
+
+        if let Some(Err(message)) = &address {
+            return Ok(context.rule_error(id, started, message));
+        }
+
+        if let Some(Ok(addr)) = address {
+            context.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::Response(ProxyResponseLog {
+                    status: http::StatusCode::OK.as_u16(),
+                    version: format!("{:?}", request.version()),
+                    headers: Vec::new(),
+                    time_to_headers: started.elapsed(),
+                }),
+            ));
+
+            tokio::task::spawn(async move {
+                let result = match hyper::upgrade::on(request).await {
+                    Ok(upgraded) => {
+                        tunnel(upgraded, addr, id, started, rate_limit.as_deref(), &context).await
+                    }
+                    Err(message) => Err(message.into()),
+                };
+
+                if let Err(message) = result {
+                    context.record_error(id, &host, &message);
+                }
+            });
+
+            return Ok(Response::new(empty()));
+        } else {
+            let mut resp = Response::new(full("CONNECT must be to a socket address"));
+            *resp.status_mut() = http::StatusCode::BAD_REQUEST;
+            return Ok(context.observe_response(id, started, resp));
+        }
+    }
+
+    let forward_settings = ForwardSettings::from(&*context.settings.lock().unwrap());
+
+    // Apply the action of the matched rule, which may answer the request itself
+    let mut request = match &action_rule {
+        Some(rule) => match apply_rule(rule, request) {
+            Ok(RuleOutcome::Forward(request)) => request,
+            Ok(RuleOutcome::Respond(mut response)) => {
+                context.change_headers(
+                    id,
+                    HeaderDirection::Response,
+                    &host,
+                    response.headers_mut(),
+                );
+
+                return Ok(context.observe_response(id, started, response));
+            }
+            Err(message) => return Ok(context.rule_error(id, started, &message)),
+        },
+        None => request,
+    };
+
+    // Proxy web requests
+    if let Some(upstream_host) = request.uri().host().map(String::from) {
+        // Answer from disk, without calling upstream, when the URL is mapped to a local file
+        // Only the matching rules are copied out of the settings, so they keep the regexes they compiled
+        let mappings =
+            match_local_rules(&context.settings.lock().unwrap().map_local, request.uri());
+        if let Some((rule, path)) = find_local_file(mappings).await {
+            let message = format!("{} -> Mapped locally to: {}", request.uri(), path.display());
+            logger.debug(&message);
+
+            context.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::MappedLocal(path.display().to_string()),
+            ));
+
+            let mut response = local_response(&rule, &path).await;
+            context.change_headers(
+                id,
+                HeaderDirection::Response,
+                &upstream_host,
+                response.headers_mut(),
+            );
+
+            return Ok(context.observe_response(id, started, response));
+        }
+
+        // Inject a fault into a share of the matching requests, which may answer the request itself
+        let fault = pick_fault(&mut context.settings.lock().unwrap().faults, request.uri());
+        if let Some(fault) = &fault {
+            let message = format!("{} -> Injecting fault: {}", request.uri(), fault.describe());
+            logger.debug(&message);
+
+            context.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::Fault(fault.describe()),
+            ));
+
+            if let Some(mut response) = fault_response(fault) {
+                context.change_headers(
+                    id,
+                    HeaderDirection::Response,
+                    &upstream_host,
+                    response.headers_mut(),
+                );
+
+                return Ok(context.observe_response(id, started, response));
+            }
+        }
+
+        context.change_headers(
+            id,
+            HeaderDirection::Request,
+            &upstream_host,
+            request.headers_mut(),
+        );
+
+        // Answer with a stored response while it's fresh, a stale one is revalidated by the forwarded request
+        let mut lookup = context.cache.lookup(&mut request);
+        let mut cached = None;
+        if let CacheLookup::Hit(entry) = &lookup {
+            cached = context.cache.respond(entry).await;
+            if cached.is_none() {
+                lookup = CacheLookup::Bypass;
+            }
+        }
+
+        let request = context.observe_request(id, request);
+
+        // Simulate the network conditions for the host, which may fail the connection
+        let throttle = match cached {
+            Some(_) => None,
+            None => context
+                .settings
+                .lock()
+                .unwrap()
+                .throttle
+                .profile_for(&upstream_host)
+                .cloned(),
+        };
+        let result = match (cached, &throttle) {
+            (Some(response), _) => Ok(response),
+            (None, Some(profile)) => {
+                context.send(ProxyEvent::RequestUpdate(
+                    id,
+                    ProxyRequestUpdate::Throttled(profile.name.clone()),
+                ));
+
+                match simulate_connection(profile).await {
+                    Ok(()) => {
+                        let request = throttle_request(request, profile);
+                        forward_request(request, &forward_settings, &context.pool, logger.clone())
+                            .await
+                    }
+                    Err(message) => Err(message),
+                }
+            }
+            (None, None) => {
+                forward_request(request, &forward_settings, &context.pool, logger.clone()).await
+            }
+        };
+
+        // Completing a revalidation may send the request again, so it's done before the response is handled
+        let result = match (result, lookup) {
+            (Ok(response), CacheLookup::Hit(_)) => {
+                logger.debug(&format!("{} -> Answered from the cache", upstream_host));
+                Ok((response, Some(ProxyCacheStatus::Hit)))
+            }
+            (Ok(response), CacheLookup::Forward(cache_request)) => context
+                .cache
+                .complete(cache_request, response, |request| {
+                    forward_request(request, &forward_settings, &context.pool, logger.clone())
+                })
+                .await
+                .map(|(response, status)| (response, Some(status))),
+            (Ok(response), CacheLookup::Bypass) => Ok((response, None)),
+            (Err(error), _) => Err(error),
+        };
+
+        match result {
+            Ok((mut response, cache_status)) => {
+                if let Some(status) = cache_status {
+                    context.send(ProxyEvent::RequestUpdate(
+                        id,
+                        ProxyRequestUpdate::Cache(status),
+                    ));
+                }
+
+                context.change_headers(
+                    id,
+                    HeaderDirection::Response,
+                    &upstream_host,
+                    response.headers_mut(),
+                );
+
+                let response = match &fault {
+                    Some(fault) => {
+                        let context = context.clone();
+                        inject_fault(fault, response, move |message| {
+                            context.send(ProxyEvent::RequestUpdate(
+                                id,
+                                ProxyRequestUpdate::Error(message),
+                            ));
+                        })
+                        .await
+                    }
+                    None => response,
+                };
+
+                let response = match &throttle {
+                    Some(profile) => {
+                        let context = context.clone();
+                        throttle_response(response, profile, move |message| {
+                            context.logger.warning(&message);
+                            context.send(ProxyEvent::RequestUpdate(
+                                id,
+                                ProxyRequestUpdate::Error(message),
+                            ));
+                        })
+                    }
+                    None => response,
+                };
+
+                let response = match rate_limit {
+                    Some(pattern) => {
+                        let context = context.clone();
+                        response.map(|body| {
+                            CaptureBody::new(body, false, move |body_log| {
+                                context.record_quota_bytes(Some(&pattern), body_log.size);
+                            })
+                            .boxed()
+                        })
+                    }
+                    None => response,
+                };
+
+                Ok(context.observe_response(id, started, response))
+            }
+            Err(message) => {
+                context.record_error(id, &host, &message);
+
+                let (status, body) = match message.is::<TimeoutError>() {
+                    true => (
+                        http::StatusCode::GATEWAY_TIMEOUT,
+                        "Upstream server took too long to respond.",
+                    ),
+                    false => (
+                        http::StatusCode::BAD_GATEWAY,
+                        "Upstream server could not be reached.",
+                    ),
+                };
+
+                let mut response = Response::new(full(body));
+                *response.status_mut() = status;
+                Ok(context.observe_response(id, started, response))
+            }
+        }
+    } else {
+        logger.debug(format!("Host address could not be found for: {}", request.uri()).as_str());
+        let mut response = Response::new(full("Host address could not be processed."));
+        *response.status_mut() = http::StatusCode::BAD_REQUEST;
+        Ok(context.observe_response(id, started, response))
+    }
+}
+
+/// Tunnel a connection bidirectionally, recording the number of bytes sent and received.
+///
+/// # Arguments:
+/// * `upgraded` - The upgraded connection to copy data to/from.
+/// * `address` - The target address to copy data to/from.
+/// * `id` - The identifier of the recorded request.
+/// * `started` - When the CONNECT request was received.
+/// * `rate_limit` - The host pattern of the rate limit rule whose quota the tunnel counts against, if any.
+/// * `context` - The shared state of the service.
+async fn tunnel(
+    upgraded: Upgraded,
+    address: String,
+    id: usize,
+    started: Instant,
+    rate_limit: Option<&str>,
+    context: &ProxyContext,
+) -> Result<(), ForwardError> {
+    let activity = ConnectionActivity::new();
+    let mut upgraded_connection = ActivityIo::new(TokioIo::new(upgraded), Arc::clone(&activity));
+
+    let result = copy_tunnel(&mut upgraded_connection, &activity, address, id, context).await;
+
+    let (sent, received) = activity.bytes();
+    context.record_quota_bytes(rate_limit, received);
+    context.send(ProxyEvent::RequestUpdate(
+        id,
+        ProxyRequestUpdate::RequestBody(ProxyBodyLog {
+            size: sent,
+            ..Default::default()
+        }),
+    ));
+    context.send(ProxyEvent::RequestUpdate(
+        id,
+        ProxyRequestUpdate::ResponseBody(
+            ProxyBodyLog {
+                size: received,
+                ..Default::default()
+            },
+            started.elapsed(),
+        ),
+    ));
+
+    result
+}
+
+/// Copy data between a client and the tunnel's target until either side closes, or a timeout fires.
+///
+/// The TLS ClientHello is read before any data is sent upstream so the server name (SNI) can be filtered,
+/// as clients connecting by IP address would otherwise get around host rules. The upstream connection is
+/// opened meanwhile, so protocols the server speaks first aren't held up.
+///
+/// # Arguments:
+/// * `upgraded_connection` - The client side of the tunnel.
+/// * `activity` - The ConnectionActivity recording the tunnel's traffic.
+/// * `address` - The target address to copy data to/from.
+/// * `id` - The identifier of the recorded request.
+/// * `context` - The shared state of the service.
+async fn copy_tunnel(
+    upgraded_connection: &mut ActivityIo<TokioIo<Upgraded>>,
+    activity: &ConnectionActivity,
+    address: String,
+    id: usize,
+    context: &ProxyContext,
+) -> Result<(), ForwardError> {
+    let (timeouts, throttle) = {
+        let settings = context.settings.lock().unwrap();
+        let host = address
+            .rsplit_once(':')
+            .map_or(address.as_str(), |(host, _)| host);
+        (
+            settings.timeouts.clone(),
+            settings.throttle.profile_for(host).cloned(),
+        )
+    };
+
+    let connect = async {
+        // Simulate the network conditions for the host, which may fail the connection
+        if let Some(profile) = &throttle {
+            context.send(ProxyEvent::RequestUpdate(
+                id,
+                ProxyRequestUpdate::Throttled(profile.name.clone()),
+            ));
+            simulate_connection(profile).await?;
+        }
+
+        let server = with_timeout(
+            ProxyTimeout::Connect,
+            timeouts.get(ProxyTimeout::Connect),
+            TcpStream::connect(&address),
+        )
+        .await??;
+
+        Ok::<_, ForwardError>(server)
+    };
+    let (client_hello, server) = tokio::join!(read_server_name(upgraded_connection), connect);
+    let (client_hello, server_name) = client_hello?;
+
+    if let Some(server_name) = server_name {
+        let port = address.rsplit_once(':').map_or("443", |(_, port)| port);
+        let server_address = format!("{}:{}", server_name, port);
+        let (blocked, rule, audited) = {
+            let traffic_filter = context.traffic_filter.lock().unwrap();
+            let (blocked, rule) =
+                traffic_filter.filter_request(&server_address, chrono::Local::now());
+            let audited = traffic_filter.is_audited(rule.as_ref());
+            (blocked, rule, audited)
+        };
+
+        // The CONNECT request was already counted, unless the server name differs from its host
+        if let Some(rule) = rule
+            .as_ref()
+            .filter(|_| !server_address.eq_ignore_ascii_case(&address))
+        {
+            context.record_rule_hit(rule, &server_address);
+        }
+
+        let blocked = blocked
+            && !context
+                .temporary_allows
+                .is_allowed(&server_name, chrono::Local::now());
+        let would_block = blocked && audited;
+        let blocked = blocked && !would_block;
+        let rule = rule.map(|rule| rule.pattern);
+
+        context.logger.debug(
+            format!(
+                "CONNECT -> {} sent server name: {} -> {}",
+                address,
+                server_name,
+                match (blocked, would_block) {
+                    (true, _) => "BLOCKED",
+                    (false, true) => "WOULD BLOCK",
+                    (false, false) => "ALLOWED",
+                }
+            )
+            .as_str(),
+        );
+
+        context.send(ProxyEvent::RequestUpdate(
+            id,
+            ProxyRequestUpdate::ServerName {
+                server_name,
+                blocked,
+                would_block,
+                rule,
+            },
+        ));
+
+        // The upstream connection is dropped before anything is sent through it
+        if blocked {
+            return Ok(());
+        }
+    }
+
+    let mut server = server?;
+    server.write_all(&client_hello).await?;
+
+    let mut client = ThrottledIo::new(upgraded_connection, throttle.as_ref());
+
+    tokio::select! {
+        result = tokio::io::copy_bidirectional(&mut client, &mut server) => {
+            result?;
+            Ok(())
+        }
+        timeout = idle_timeout(activity, timeouts.get(ProxyTimeout::TunnelIdle)) => Err(timeout.into()),
+        timeout = lifetime_timeout(timeouts.get(ProxyTimeout::TunnelLifetime)) => Err(timeout.into()),
+    }
+}
+
+/// Converts a HeaderMap to a list of readable name/value pairs.
+///
+/// # Arguments
+/// * `headers` - The headers to convert.
+pub fn headers_to_vec(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Get the current URI's host address.
+///
+/// # Arguments
+/// * `uri` - The given URI.
+fn get_host_address(uri: &Uri) -> Option<String> {
+    uri.authority().map(|auth| auth.to_string())
 }
@@ -0,0 +1,336 @@
+use super::{
+    forward::{full, ProxyBody},
+    header_rules::host_matches,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDateTime, Timelike};
+use hyper::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    Response, StatusCode,
+};
+use std::time::Instant;
+
+/// The enum that controls what happens to requests over a rate limit or quota.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum RateLimitAction {
+    /// Answer with 429 Too Many Requests and a Retry-After header.
+    #[default]
+    TooManyRequests,
+    /// Answer as if the request was blocked by the exclusion list.
+    Block,
+}
+
+impl std::fmt::Display for RateLimitAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitAction::TooManyRequests => write!(f, "429 Too Many Requests"),
+            RateLimitAction::Block => write!(f, "Block"),
+        }
+    }
+}
+
+/// The enum that controls what a quota counts.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum QuotaUnit {
+    #[default]
+    Requests,
+    /// Megabytes received from the host.
+    Megabytes,
+}
+
+impl std::fmt::Display for QuotaUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaUnit::Requests => write!(f, "Requests"),
+            QuotaUnit::Megabytes => write!(f, "MB"),
+        }
+    }
+}
+
+/// The enum that controls how often a quota resets.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum QuotaWindow {
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl std::fmt::Display for QuotaWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaWindow::Hourly => write!(f, "per Hour"),
+            QuotaWindow::Daily => write!(f, "per Day"),
+        }
+    }
+}
+
+impl QuotaWindow {
+    /// Returns when the window containing the given time started, in local time.
+    ///
+    /// # Arguments:
+    /// * `now` - The time to return the window of.
+    fn start(&self, now: DateTime<Local>) -> NaiveDateTime {
+        let now = now.naive_local();
+
+        match self {
+            QuotaWindow::Hourly => now.date().and_hms_opt(now.hour(), 0, 0),
+            QuotaWindow::Daily => now.date().and_hms_opt(0, 0, 0),
+        }
+        .unwrap_or(now)
+    }
+
+    /// Returns how long a window lasts.
+    fn length(&self) -> ChronoDuration {
+        match self {
+            QuotaWindow::Hourly => ChronoDuration::hours(1),
+            QuotaWindow::Daily => ChronoDuration::days(1),
+        }
+    }
+}
+
+/// Contains the traffic counted against a quota in its current window.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct QuotaUsage {
+    /// When the window the usage was counted in started, in local time.
+    pub window: Option<NaiveDateTime>,
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+/// The token bucket of a rule's requests per second limit.
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A rule that limits the rate of requests to matching hosts, and how much traffic they may use.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct RateLimitRule {
+    pub enabled: bool,
+    /// The host to apply the rule to, e.g. example.com, *.example.com, or * for every host.
+    pub host: String,
+    /// The number of requests allowed per second, or 0 for no limit.
+    pub rate: u32,
+    /// The number of requests allowed at once before the rate applies.
+    pub burst: u32,
+    /// The amount of traffic allowed per window, or 0 for no quota.
+    pub quota: u64,
+    pub unit: QuotaUnit,
+    pub window: QuotaWindow,
+    pub action: RateLimitAction,
+    pub usage: QuotaUsage,
+    #[serde(skip)]
+    bucket: Option<TokenBucket>,
+}
+
+impl Default for RateLimitRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: String::from("*"),
+            rate: 10,
+            burst: 20,
+            quota: 0,
+            unit: QuotaUnit::default(),
+            window: QuotaWindow::default(),
+            action: RateLimitAction::default(),
+            usage: QuotaUsage::default(),
+            bucket: None,
+        }
+    }
+}
+
+impl PartialEq for RateLimitRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.enabled == other.enabled
+            && self.host == other.host
+            && self.rate == other.rate
+            && self.burst == other.burst
+            && self.quota == other.quota
+            && self.unit == other.unit
+            && self.window == other.window
+            && self.action == other.action
+            && self.usage == other.usage
+    }
+}
+
+impl RateLimitRule {
+    /// Replaces the rule's settings with those of an edited copy, keeping the usage counted since it was copied.
+    ///
+    /// # Arguments:
+    /// * `rule` - The edited RateLimitRule.
+    pub fn update(&mut self, rule: RateLimitRule) {
+        let usage = std::mem::take(&mut self.usage);
+        let bucket = self.bucket.take();
+        *self = rule;
+        self.usage = usage;
+        self.bucket = bucket;
+    }
+
+    /// Returns the usage counted in the current window, which is empty once the window has reset.
+    pub fn current_usage(&self) -> QuotaUsage {
+        let window = self.window.start(Local::now());
+
+        match self.usage.window == Some(window) {
+            true => self.usage.clone(),
+            false => QuotaUsage {
+                window: Some(window),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns how much of the quota has been used in the current window, in its unit.
+    pub fn quota_used(&self) -> u64 {
+        let usage = self.current_usage();
+
+        match self.unit {
+            QuotaUnit::Requests => usage.requests,
+            QuotaUnit::Megabytes => usage.bytes / (1024 * 1024),
+        }
+    }
+
+    /// Returns whether the quota has been used up in the current window.
+    pub fn quota_exceeded(&self) -> bool {
+        let usage = self.current_usage();
+
+        match self.unit {
+            _ if self.quota == 0 => false,
+            QuotaUnit::Requests => usage.requests >= self.quota,
+            QuotaUnit::Megabytes => usage.bytes >= self.quota.saturating_mul(1024 * 1024),
+        }
+    }
+
+    /// Returns the number of seconds until the current window resets.
+    pub fn seconds_until_reset(&self) -> u64 {
+        let now = Local::now().naive_local();
+        let reset = self.window.start(Local::now()) + self.window.length();
+
+        (reset - now).num_seconds().max(1) as u64
+    }
+
+    /// Takes a token from the rule's bucket, returning false if none are left.
+    fn take_token(&mut self) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+
+        let capacity = self.burst.max(1) as f64;
+        let now = Instant::now();
+        let bucket = self.bucket.get_or_insert(TokenBucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate as f64).min(capacity);
+        bucket.updated = now;
+
+        match bucket.tokens >= 1. {
+            true => {
+                bucket.tokens -= 1.;
+                true
+            }
+            false => false,
+        }
+    }
+}
+
+/// Contains why a request was refused by a rate limit rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitExceeded {
+    /// The host pattern of the rule that refused the request.
+    pub host: String,
+    pub action: RateLimitAction,
+    pub reason: String,
+    /// The number of seconds until the request would be allowed.
+    pub retry_after: u64,
+}
+
+/// Checks a request against the first rule matching its host, counting it against the rule's quota if it's allowed.
+///
+/// Returns the host pattern of the matched rule, or why the request was refused.
+///
+/// # Arguments:
+/// * `rules` - The rate limit rules to check, which are updated with the request.
+/// * `host` - The host the request is being sent to.
+pub fn check_rate_limit(
+    rules: &mut [RateLimitRule],
+    host: &str,
+) -> Result<Option<String>, RateLimitExceeded> {
+    let Some(rule) = rules
+        .iter_mut()
+        .find(|rule| rule.enabled && host_matches(&rule.host, host))
+    else {
+        return Ok(None);
+    };
+
+    if rule.quota_exceeded() {
+        return Err(RateLimitExceeded {
+            host: rule.host.clone(),
+            action: rule.action,
+            reason: format!(
+                "Quota of {} {} {} used up",
+                rule.quota,
+                rule.unit.to_string().to_lowercase(),
+                rule.window.to_string().to_lowercase()
+            ),
+            retry_after: rule.seconds_until_reset(),
+        });
+    }
+
+    if !rule.take_token() {
+        return Err(RateLimitExceeded {
+            host: rule.host.clone(),
+            action: rule.action,
+            reason: format!(
+                "Over {} requests per second (burst {})",
+                rule.rate, rule.burst
+            ),
+            retry_after: 1,
+        });
+    }
+
+    rule.usage = rule.current_usage();
+    rule.usage.requests += 1;
+
+    Ok(Some(rule.host.clone()))
+}
+
+/// Counts bytes received from a host against the quota of the rule that allowed the request.
+///
+/// # Arguments:
+/// * `rules` - The rate limit rules to update.
+/// * `pattern` - The host pattern of the rule that allowed the request.
+/// * `bytes` - The number of bytes received.
+pub fn record_bytes(rules: &mut [RateLimitRule], pattern: &str, bytes: u64) {
+    if let Some(rule) = rules
+        .iter_mut()
+        .find(|rule| rule.enabled && rule.host == pattern)
+    {
+        rule.usage = rule.current_usage();
+        rule.usage.bytes += bytes;
+    }
+}
+
+/// Creates the 429 Too Many Requests response to a refused request.
+///
+/// # Arguments:
+/// * `exceeded` - Why the request was refused.
+pub fn rate_limit_response(exceeded: &RateLimitExceeded) -> Response<ProxyBody> {
+    let mut response = Response::new(full(format!(
+        "Rate limited by the Proxy: {}",
+        exceeded.reason
+    )));
+    *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "text/plain; charset=utf-8".parse().unwrap());
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, exceeded.retry_after.into());
+
+    response
+}
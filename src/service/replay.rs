@@ -0,0 +1,159 @@
+use super::{
+    capture::{CaptureBody, ProxyBodyLog},
+    forward::{forward_request, full, ForwardSettings, ProxyBody},
+    pool::ConnectionPool,
+    proxy::{headers_to_vec, ProxyRequestLog, ProxyResponseLog},
+    settings::ProxySettings,
+};
+use crate::utils::logger::Logger;
+use http_body_util::BodyExt;
+use hyper::{body::Bytes, header, http::HeaderName, http::HeaderValue, Method, Request, Uri};
+use std::time::{Duration, Instant};
+
+/// Headers that describe the original connection or body, so are recalculated rather than resent.
+const REPLAY_SKIPPED_HEADERS: [&str; 8] = [
+    "host",
+    "connection",
+    "proxy-connection",
+    "keep-alive",
+    "transfer-encoding",
+    "te",
+    "upgrade",
+    "content-length",
+];
+
+/// Contains a request to resend through the Proxy.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ProxyReplayRequest {
+    pub method: String,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+}
+
+impl From<&ProxyRequestLog> for ProxyReplayRequest {
+    fn from(request_log: &ProxyRequestLog) -> Self {
+        Self {
+            method: request_log.method.clone(),
+            uri: request_log.request.clone(),
+            headers: request_log.request_headers.clone(),
+            body: request_log
+                .request_body
+                .as_ref()
+                .and_then(|body| body.preview.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl ProxyReplayRequest {
+    /// Builds the request to forward, validating the method, URL and headers.
+    pub fn build(&self) -> Result<Request<ProxyBody>, String> {
+        let method = Method::from_bytes(self.method.trim().as_bytes())
+            .map_err(|_| format!("Invalid method: {}", self.method))?;
+        let uri = self
+            .uri
+            .trim()
+            .parse::<Uri>()
+            .map_err(|error| format!("Invalid URL: {}", error))?;
+
+        if uri.scheme_str() != Some("http") {
+            return Err(String::from(
+                "Only plain HTTP URLs can be resent, e.g. http://example.com/",
+            ));
+        }
+
+        let authority = uri
+            .authority()
+            .ok_or("The URL must contain a host, e.g. http://example.com/")?
+            .to_string();
+
+        let mut builder = Request::builder().method(method).uri(uri);
+
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| format!("Invalid header name: {}", name))?;
+
+            if REPLAY_SKIPPED_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+
+            let value = HeaderValue::from_str(value.trim())
+                .map_err(|_| format!("Invalid value for header: {}", name))?;
+
+            builder = builder.header(name, value);
+        }
+
+        builder
+            .header(header::HOST, authority)
+            .body(full(self.body.clone()))
+            .map_err(|error| error.to_string())
+    }
+}
+
+/// Contains the response received when resending a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyReplayResponse {
+    pub response: ProxyResponseLog,
+    pub body: ProxyBodyLog,
+    pub duration: Duration,
+}
+
+/// The enum that carries the current state of a resent request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProxyReplayStatus {
+    Sending,
+    Complete(ProxyReplayResponse),
+    Failed(String),
+}
+
+/// Contains a resent request, the request it was copied from and its current state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyReplay {
+    pub request_id: usize,
+    pub request: ProxyReplayRequest,
+    pub status: ProxyReplayStatus,
+}
+
+/// Resend a request through the Proxy's forwarding path, returning once the whole response has been read.
+///
+/// # Arguments:
+/// * `request` - The request to resend.
+/// * `settings` - The current ProxySettings.
+/// * `logger` - The current logger to log connection events to.
+pub async fn replay_request(
+    request: Request<ProxyBody>,
+    settings: &ProxySettings,
+    logger: Logger,
+) -> Result<ProxyReplayResponse, String> {
+    let started = Instant::now();
+
+    // Replays run on their own runtime, so can't share the service's pooled connections
+    let settings = ForwardSettings::from(settings);
+    let response = forward_request(request, &settings, &ConnectionPool::default(), logger)
+        .await
+        .map_err(|error| error.to_string())?;
+
+    let response_log = ProxyResponseLog {
+        status: response.status().as_u16(),
+        version: format!("{:?}", response.version()),
+        headers: headers_to_vec(response.headers()),
+        time_to_headers: started.elapsed(),
+    };
+
+    let (body_sender, body_receiver) = tokio::sync::oneshot::channel();
+    let mut body = CaptureBody::new(response.into_body(), true, move |body_log| {
+        let _ = body_sender.send(body_log);
+    });
+
+    while let Some(frame) = body.frame().await {
+        frame.map_err(|error| error.to_string())?;
+    }
+    drop(body);
+
+    Ok(ProxyReplayResponse {
+        response: response_log,
+        body: body_receiver.await.map_err(|error| error.to_string())?,
+        duration: started.elapsed(),
+    })
+}
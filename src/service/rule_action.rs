@@ -0,0 +1,129 @@
+use super::{
+    forward::{empty, full, ProxyBody},
+    traffic_filter::{TrafficRule, TrafficRuleAction},
+};
+use hyper::{
+    header::{CONTENT_TYPE, HOST, LOCATION},
+    http::uri::{Authority, PathAndQuery},
+    Request, Response, StatusCode, Uri,
+};
+
+/// The enum that carries what should happen to a request after a rule's action has been applied.
+pub enum RuleOutcome<B> {
+    /// Answer the client with the response, without forwarding the request.
+    Respond(Response<ProxyBody>),
+    /// Forward the request, which may have been rewritten.
+    Forward(Request<B>),
+}
+
+/// Applies the action of the rule that matched a request.
+///
+/// # Arguments:
+/// * `rule` - The TrafficRule that matched the request.
+/// * `request` - The matched request.
+pub fn apply_rule<B>(
+    rule: &TrafficRule,
+    mut request: Request<B>,
+) -> Result<RuleOutcome<B>, String> {
+    let uri = request.uri().to_string();
+    let target = rule.expand_target(&uri);
+
+    match rule.action {
+        TrafficRuleAction::Filter | TrafficRuleAction::Allow => Ok(RuleOutcome::Forward(request)),
+        TrafficRuleAction::Redirect => {
+            let status = StatusCode::from_u16(rule.status)
+                .ok()
+                .filter(StatusCode::is_redirection)
+                .unwrap_or(StatusCode::FOUND);
+
+            let mut response = Response::new(empty());
+            *response.status_mut() = status;
+            response.headers_mut().insert(
+                LOCATION,
+                target
+                    .parse()
+                    .map_err(|_| format!("Redirect target is not a valid header: {}", target))?,
+            );
+
+            Ok(RuleOutcome::Respond(response))
+        }
+        TrafficRuleAction::Rewrite => {
+            let rewritten = rewrite_uri(request.uri(), &target)?;
+
+            // The Host header must follow the rewritten host, or the upstream server sees the original one
+            if let Some(authority) = rewritten.authority() {
+                if request.headers().contains_key(HOST) {
+                    if let Ok(host) = authority.as_str().parse() {
+                        request.headers_mut().insert(HOST, host);
+                    }
+                }
+            }
+
+            *request.uri_mut() = rewritten;
+            Ok(RuleOutcome::Forward(request))
+        }
+        TrafficRuleAction::Respond => {
+            let mut response = Response::new(full(target));
+            *response.status_mut() = StatusCode::from_u16(rule.status).unwrap_or(StatusCode::OK);
+            if let Ok(content_type) = rule.content_type.parse() {
+                response.headers_mut().insert(CONTENT_TYPE, content_type);
+            }
+
+            Ok(RuleOutcome::Respond(response))
+        }
+    }
+}
+
+/// Returns the address a CONNECT tunnel should be opened to, after applying a rewrite rule's target.
+///
+/// # Arguments:
+/// * `rule` - The TrafficRule that matched the tunnel.
+/// * `address` - The address the client asked to connect to.
+pub fn rewrite_address(rule: &TrafficRule, address: &str) -> Result<String, String> {
+    let target = rule.expand_target(address);
+
+    // Tunnels have no path, so only the host and port can be rewritten
+    let authority = match target.parse::<Uri>() {
+        Ok(uri) if uri.scheme().is_some() => uri.authority().cloned(),
+        _ => target.parse::<Authority>().ok(),
+    }
+    .ok_or(format!("Rewrite target is not a valid address: {}", target))?;
+
+    Ok(match authority.port() {
+        Some(_) => authority.to_string(),
+        None => {
+            let port = address.rsplit_once(':').map_or("443", |(_, port)| port);
+            format!("{}:{}", authority, port)
+        }
+    })
+}
+
+/// Rewrites a URI to the target of a rewrite rule.
+///
+/// A target containing a scheme replaces the whole URI, one starting with / replaces the path and query,
+/// otherwise it replaces the host and port.
+///
+/// # Arguments:
+/// * `uri` - The URI being requested.
+/// * `target` - The rule's target, with its capture groups expanded.
+fn rewrite_uri(uri: &Uri, target: &str) -> Result<Uri, String> {
+    let invalid = || {
+        format!(
+            "Rewrite target is not a valid URL, host or path: {}",
+            target
+        )
+    };
+
+    if target.contains("://") {
+        return target.parse().map_err(|_| invalid());
+    }
+
+    let mut parts = uri.clone().into_parts();
+    if target.starts_with('/') {
+        parts.path_and_query = Some(target.parse::<PathAndQuery>().map_err(|_| invalid())?);
+    } else {
+        parts.authority = Some(target.parse::<Authority>().map_err(|_| invalid())?);
+    }
+
+    Uri::from_parts(parts).map_err(|_| invalid())
+}
@@ -0,0 +1,144 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime};
+
+/// The short names of the days of the week, from Monday to Sunday.
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Contains when a rule, or profile, is active, judged in local time.
+///
+/// Every part that has been set must match for the schedule to be active.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TrafficSchedule {
+    /// The days of the week the schedule is active on, from Monday to Sunday.
+    pub days: [bool; 7],
+    /// The time of day the schedule starts and ends, an end before the start runs past midnight.
+    pub hours: Option<(NaiveTime, NaiveTime)>,
+    /// The first and last day the schedule is active on.
+    pub dates: Option<(NaiveDate, NaiveDate)>,
+    /// When the schedule stops being active for good.
+    pub expires: Option<DateTime<Local>>,
+}
+
+impl Default for TrafficSchedule {
+    fn default() -> Self {
+        Self {
+            days: [true; 7],
+            hours: None,
+            dates: None,
+            expires: None,
+        }
+    }
+}
+
+impl TrafficSchedule {
+    /// Creates a schedule that's active on weekdays, during working hours.
+    pub fn working_hours() -> Self {
+        Self {
+            days: [true, true, true, true, true, false, false],
+            hours: NaiveTime::from_hms_opt(9, 0, 0).zip(NaiveTime::from_hms_opt(17, 0, 0)),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a schedule that's active from now, for a number of minutes.
+    ///
+    /// # Arguments:
+    /// * `minutes` - How long the schedule is active for.
+    pub fn for_minutes(minutes: i64) -> Self {
+        Self {
+            expires: Some(Local::now() + Duration::minutes(minutes)),
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the schedule is active at the given time.
+    ///
+    /// # Arguments:
+    /// * `now` - The local time to check.
+    pub fn is_active(&self, now: DateTime<Local>) -> bool {
+        let today = now.date_naive();
+        let time = now.time();
+
+        if self.expires.is_some_and(|expires| now >= expires) {
+            return false;
+        }
+
+        // The hours after midnight of an overnight schedule belong to the day it started on
+        let day = match self.hours {
+            Some((start, end)) if start > end && time < end => today.pred_opt().unwrap_or(today),
+            _ => today,
+        };
+
+        if self
+            .dates
+            .is_some_and(|(from, until)| day < from || day > until)
+        {
+            return false;
+        }
+
+        if !self.days[day.weekday().num_days_from_monday() as usize] {
+            return false;
+        }
+
+        match self.hours {
+            Some((start, end)) if start <= end => time >= start && time < end,
+            Some((start, end)) => time >= start || time < end,
+            None => true,
+        }
+    }
+
+    /// Returns whether the schedule will never be active again.
+    ///
+    /// # Arguments:
+    /// * `now` - The local time to check.
+    pub fn is_expired(&self, now: DateTime<Local>) -> bool {
+        self.expires.is_some_and(|expires| now >= expires)
+            || self
+                .dates
+                .is_some_and(|(_, until)| now.date_naive() > until)
+            || !self.days.contains(&true)
+    }
+
+    /// Returns a readable description of the schedule, e.g. Weekdays 09:00-17:00.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+
+        match self.days {
+            [true, true, true, true, true, true, true] => {}
+            [true, true, true, true, true, false, false] => parts.push(String::from("Weekdays")),
+            [false, false, false, false, false, true, true] => parts.push(String::from("Weekends")),
+            [false, false, false, false, false, false, false] => {
+                parts.push(String::from("No days"))
+            }
+            days => parts.push(
+                WEEKDAYS
+                    .iter()
+                    .zip(days)
+                    .filter(|(_, active)| *active)
+                    .map(|(day, _)| *day)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        }
+
+        if let Some((start, end)) = self.hours {
+            parts.push(format!("{}-{}", start.format("%H:%M"), end.format("%H:%M")));
+        }
+
+        if let Some((from, until)) = self.dates {
+            parts.push(format!("{} to {}", from, until));
+        }
+
+        if let Some(expires) = self.expires {
+            match expires.date_naive() == Local::now().date_naive() {
+                true => parts.push(format!("until {}", expires.format("%H:%M"))),
+                false => parts.push(format!("until {}", expires.format("%Y-%m-%d %H:%M"))),
+            }
+        }
+
+        match parts.is_empty() {
+            true => String::from("Always"),
+            false => parts.join(", "),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use super::{
+    fault::FaultRule, header_rules::HeaderRule, map_local::MapLocalRule, rate_limit::RateLimitRule,
+    throttle::ProxyThrottle, timeout::ProxyTimeout,
+};
+use hyper::StatusCode;
+use std::{path::PathBuf, time::Duration};
+
+/// The page shown for blocked requests unless it has been customised.
+pub const DEFAULT_BLOCK_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>Request Blocked</title>
+    <style>
+      body { font-family: sans-serif; margin: 4em auto; max-width: 40em; color: #333; }
+      dt { font-weight: bold; margin-top: 1em; }
+      dd { margin: 0; word-break: break-all; }
+    </style>
+  </head>
+  <body>
+    <h1>Request Blocked</h1>
+    <p>This request was blocked by the proxy.</p>
+    <dl>
+      <dt>URL</dt><dd>{url}</dd>
+      <dt>Rule</dt><dd>{rule}</dd>
+      <dt>Profile</dt><dd>{profile}</dd>
+      <dt>Time</dt><dd>{time}</dd>
+    </dl>
+    <p>{exception_link}</p>
+  </body>
+</html>
+"#;
+
+/// The enum that controls which HTTP version is used to talk to upstream servers.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum ProxyHttpVersion {
+    /// Use HTTP/2 when the client did, falling back to HTTP/1.1 where possible.
+    #[default]
+    Auto,
+    Http1,
+    Http2,
+}
+
+impl std::fmt::Display for ProxyHttpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyHttpVersion::Auto => write!(f, "Auto"),
+            ProxyHttpVersion::Http1 => write!(f, "HTTP/1.1"),
+            ProxyHttpVersion::Http2 => write!(f, "HTTP/2"),
+        }
+    }
+}
+
+/// The enum that controls how the Proxy answers a blocked request.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum ProxyBlockStatus {
+    #[default]
+    Forbidden,
+    UnavailableForLegalReasons,
+    NoContent,
+    /// Close the connection with a TCP reset, without sending a response.
+    Reset,
+}
+
+impl std::fmt::Display for ProxyBlockStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyBlockStatus::Forbidden => write!(f, "403 Forbidden"),
+            ProxyBlockStatus::UnavailableForLegalReasons => {
+                write!(f, "451 Unavailable For Legal Reasons")
+            }
+            ProxyBlockStatus::NoContent => write!(f, "204 No Content"),
+            ProxyBlockStatus::Reset => write!(f, "TCP Reset"),
+        }
+    }
+}
+
+impl ProxyBlockStatus {
+    /// Returns the status code to respond with, or None if the connection is reset instead.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            ProxyBlockStatus::Forbidden => Some(StatusCode::FORBIDDEN),
+            ProxyBlockStatus::UnavailableForLegalReasons => {
+                Some(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS)
+            }
+            ProxyBlockStatus::NoContent => Some(StatusCode::NO_CONTENT),
+            ProxyBlockStatus::Reset => None,
+        }
+    }
+}
+
+/// Contains how blocked requests are answered.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProxyBlockPage {
+    pub status: ProxyBlockStatus,
+    /// The HTML page shown to browsers, see block_page::block_response for the placeholders it may contain.
+    pub template: String,
+    /// Whether the page offers a link to request an exception for the blocked URL.
+    pub exception_link: bool,
+}
+
+impl Default for ProxyBlockPage {
+    fn default() -> Self {
+        Self {
+            status: ProxyBlockStatus::default(),
+            template: String::from(DEFAULT_BLOCK_TEMPLATE),
+            exception_link: true,
+        }
+    }
+}
+
+/// Contains the Proxy's timeouts in seconds, where 0 disables the timeout.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProxyTimeouts {
+    pub connect: u64,
+    pub request_headers: u64,
+    pub response: u64,
+    /// How long a client connection may wait between requests, tunnels aren't affected.
+    pub idle: u64,
+    /// How long a tunnel may go without traffic either way, which websockets and streams keep up with pings.
+    pub tunnel_idle: u64,
+    /// How long a tunnel may stay open, disabled by default as websockets and streams stay open for hours.
+    pub tunnel_lifetime: u64,
+}
+
+impl Default for ProxyTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: 10,
+            request_headers: 30,
+            response: 60,
+            idle: 120,
+            tunnel_idle: 3600,
+            tunnel_lifetime: 0,
+        }
+    }
+}
+
+impl ProxyTimeouts {
+    /// Returns how long to wait before the given timeout fires, or None if it's disabled.
+    ///
+    /// # Arguments:
+    /// * `timeout` - The ProxyTimeout to return the duration of.
+    pub fn get(&self, timeout: ProxyTimeout) -> Option<Duration> {
+        let seconds = match timeout {
+            ProxyTimeout::Connect => self.connect,
+            ProxyTimeout::RequestHeaders => self.request_headers,
+            ProxyTimeout::Response => self.response,
+            ProxyTimeout::Idle => self.idle,
+            ProxyTimeout::TunnelIdle => self.tunnel_idle,
+            ProxyTimeout::TunnelLifetime => self.tunnel_lifetime,
+        };
+
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+
+    /// Returns the number of seconds to wait before the given timeout fires, as a mutable reference.
+    ///
+    /// # Arguments:
+    /// * `timeout` - The ProxyTimeout to return the value of.
+    pub fn get_mut(&mut self, timeout: ProxyTimeout) -> &mut u64 {
+        match timeout {
+            ProxyTimeout::Connect => &mut self.connect,
+            ProxyTimeout::RequestHeaders => &mut self.request_headers,
+            ProxyTimeout::Response => &mut self.response,
+            ProxyTimeout::Idle => &mut self.idle,
+            ProxyTimeout::TunnelIdle => &mut self.tunnel_idle,
+            ProxyTimeout::TunnelLifetime => &mut self.tunnel_lifetime,
+        }
+    }
+}
+
+/// Contains where and how much the Proxy caches responses on disk.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ProxyCacheSettings {
+    pub enabled: bool,
+    /// The directory the responses are stored in, or empty for one in the system's temporary directory.
+    pub directory: String,
+    /// The most space the cache may take up, in megabytes.
+    pub max_size: u64,
+}
+
+impl Default for ProxyCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: String::default(),
+            max_size: 256,
+        }
+    }
+}
+
+impl ProxyCacheSettings {
+    /// Returns the directory the responses are stored in.
+    pub fn directory(&self) -> PathBuf {
+        match self.directory.trim() {
+            "" => std::env::temp_dir().join("a-bean-sieve-cache"),
+            directory => PathBuf::from(directory),
+        }
+    }
+
+    /// Returns the most space the cache may take up, in bytes.
+    pub fn max_size_bytes(&self) -> u64 {
+        self.max_size.saturating_mul(1024 * 1024)
+    }
+}
+
+/// Contains the configurable behaviour of the Proxy service.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub capture_bodies: bool,
+    pub http_version: ProxyHttpVersion,
+    pub pool_idle_timeout: u64,
+    pub pool_max_connections: usize,
+    pub timeouts: ProxyTimeouts,
+    pub block_page: ProxyBlockPage,
+    pub header_rules: Vec<HeaderRule>,
+    pub map_local: Vec<MapLocalRule>,
+    pub throttle: ProxyThrottle,
+    pub faults: Vec<FaultRule>,
+    pub cache: ProxyCacheSettings,
+    pub rate_limits: Vec<RateLimitRule>,
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            capture_bodies: false,
+            http_version: ProxyHttpVersion::default(),
+            pool_idle_timeout: 90,
+            pool_max_connections: 6,
+            timeouts: ProxyTimeouts::default(),
+            block_page: ProxyBlockPage::default(),
+            header_rules: Vec::new(),
+            map_local: Vec::new(),
+            throttle: ProxyThrottle::default(),
+            faults: Vec::new(),
+            cache: ProxyCacheSettings::default(),
+            rate_limits: Vec::new(),
+        }
+    }
+}
+
+impl ProxySettings {
+    /// Returns how long an upstream connection may be idle before it's closed.
+    pub fn pool_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.pool_idle_timeout)
+    }
+}
@@ -0,0 +1,376 @@
+use std::time::Duration;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    time::Instant,
+};
+
+/// The most data read from a tunnel while waiting for a complete TLS ClientHello.
+const CLIENT_HELLO_LIMIT: usize = 16 * 1024;
+
+/// How long to wait for the client to send anything, before treating the tunnel as a protocol the server speaks first.
+const CLIENT_HELLO_PEEK_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// How long to wait for the rest of a TLS ClientHello the client has started sending, before tunnelling without one.
+const CLIENT_HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The TLS record type used for handshake messages.
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// The TLS handshake message type of a ClientHello.
+const TLS_CLIENT_HELLO: u8 = 0x01;
+
+/// The TLS extension type that carries the server name.
+const TLS_SERVER_NAME_EXTENSION: u16 = 0x0000;
+
+/// The server name type used for DNS host names.
+const TLS_HOST_NAME: u8 = 0x00;
+
+/// The enum that carries the result of parsing the start of a tunnelled connection.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientHello {
+    /// More data is needed before the ClientHello can be parsed.
+    Incomplete,
+    /// The connection doesn't start with a TLS handshake.
+    NotTls,
+    /// The ClientHello was parsed, along with the server name it contained, if any.
+    Parsed(Option<String>),
+}
+
+/// Reads from the start of a buffer, returning None once it runs out of data.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        if self.data.len() < length {
+            return None;
+        }
+
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Takes a value prefixed by a single byte length.
+    fn vector8(&mut self) -> Option<&'a [u8]> {
+        let length = self.u8()? as usize;
+        self.take(length)
+    }
+
+    /// Takes a value prefixed by a two byte length.
+    fn vector16(&mut self) -> Option<&'a [u8]> {
+        let length = self.u16()? as usize;
+        self.take(length)
+    }
+}
+
+/// Parses the TLS ClientHello at the start of a connection, returning the server name (SNI) it was sent with.
+///
+/// # Arguments:
+/// * `buffer` - The data received from the client so far.
+pub fn parse_client_hello(buffer: &[u8]) -> ClientHello {
+    let mut handshake = Vec::new();
+    let mut offset = 0;
+
+    // A ClientHello may be split across several handshake records
+    loop {
+        let Some(header) = buffer.get(offset..offset + 5) else {
+            return ClientHello::Incomplete;
+        };
+
+        if header[0] != TLS_HANDSHAKE_RECORD {
+            return match offset {
+                0 => ClientHello::NotTls,
+                _ => ClientHello::Parsed(None),
+            };
+        }
+
+        let length = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let Some(fragment) = buffer.get(offset + 5..offset + 5 + length) else {
+            return ClientHello::Incomplete;
+        };
+
+        handshake.extend_from_slice(fragment);
+        offset += 5 + length;
+
+        if handshake.len() < 4 {
+            continue;
+        }
+
+        if handshake[0] != TLS_CLIENT_HELLO {
+            return ClientHello::Parsed(None);
+        }
+
+        let message_length =
+            u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+        if let Some(message) = handshake.get(4..4 + message_length) {
+            return ClientHello::Parsed(server_name(message));
+        }
+    }
+}
+
+/// Returns the host name from the server name extension of a ClientHello message.
+///
+/// # Arguments:
+/// * `message` - The body of the ClientHello message.
+fn server_name(message: &[u8]) -> Option<String> {
+    let mut reader = Reader::new(message);
+
+    // Skip the client version and random
+    reader.take(2 + 32)?;
+    reader.vector8()?; // Session ID
+    reader.vector16()?; // Cipher suites
+    reader.vector8()?; // Compression methods
+
+    let mut extensions = Reader::new(reader.vector16()?);
+    while !extensions.is_empty() {
+        let extension_type = extensions.u16()?;
+        let extension = extensions.vector16()?;
+
+        if extension_type != TLS_SERVER_NAME_EXTENSION {
+            continue;
+        }
+
+        let mut names = Reader::new(Reader::new(extension).vector16()?);
+        while !names.is_empty() {
+            let name_type = names.u8()?;
+            let name = names.vector16()?;
+
+            if name_type == TLS_HOST_NAME {
+                return String::from_utf8(name.to_vec())
+                    .ok()
+                    .map(|name| name.to_lowercase());
+            }
+        }
+    }
+
+    None
+}
+
+/// Reads the start of a tunnelled connection until its TLS ClientHello can be parsed.
+///
+/// Returns the data read, which must be passed on to the upstream server, and the server name if one was found.
+/// Connections that aren't TLS, or don't send anything in time, are returned without a server name.
+///
+/// # Arguments:
+/// * `stream` - The client side of the tunnel.
+pub async fn read_server_name<S>(stream: &mut S) -> std::io::Result<(Vec<u8>, Option<String>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let started = Instant::now();
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+
+    loop {
+        match parse_client_hello(&buffer) {
+            ClientHello::Parsed(server_name) => return Ok((buffer, server_name)),
+            ClientHello::NotTls => return Ok((buffer, None)),
+            ClientHello::Incomplete if buffer.len() >= CLIENT_HELLO_LIMIT => {
+                return Ok((buffer, None))
+            }
+            ClientHello::Incomplete => {}
+        }
+
+        // TLS clients send their ClientHello straight away, while protocols like SMTP or SSH wait for the server
+        let deadline = match buffer.is_empty() {
+            true => started + CLIENT_HELLO_PEEK_TIMEOUT,
+            false => started + CLIENT_HELLO_TIMEOUT,
+        };
+
+        let read = match tokio::time::timeout_at(deadline, stream.read(&mut chunk)).await {
+            Ok(read) => read?,
+            Err(_) => return Ok((buffer, None)),
+        };
+
+        if read == 0 {
+            return Ok((buffer, None));
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    /// Returns a ClientHello handshake message, with a server name extension when a name is given.
+    fn client_hello_message(server_name: Option<&str>) -> Vec<u8> {
+        let mut extensions = Vec::new();
+
+        // An extension other than the server name, which is skipped
+        extensions.extend_from_slice(&[0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]);
+
+        if let Some(name) = server_name {
+            let mut names = vec![TLS_HOST_NAME];
+            names.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            names.extend_from_slice(name.as_bytes());
+
+            extensions.extend_from_slice(&TLS_SERVER_NAME_EXTENSION.to_be_bytes());
+            extensions.extend_from_slice(&(names.len() as u16 + 2).to_be_bytes());
+            extensions.extend_from_slice(&(names.len() as u16).to_be_bytes());
+            extensions.extend_from_slice(&names);
+        }
+
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0; 32]); // Random
+        body.push(0); // Session ID
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // Cipher suites
+        body.extend_from_slice(&[0x01, 0x00]); // Compression methods
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut message = vec![TLS_CLIENT_HELLO];
+        message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        message.extend_from_slice(&body);
+        message
+    }
+
+    /// Wraps handshake data in TLS records, splitting it into fragments of at most the given size.
+    fn records(message: &[u8], fragment_size: usize) -> Vec<u8> {
+        let mut records = Vec::new();
+
+        for fragment in message.chunks(fragment_size) {
+            records.extend_from_slice(&[TLS_HANDSHAKE_RECORD, 0x03, 0x01]);
+            records.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            records.extend_from_slice(fragment);
+        }
+
+        records
+    }
+
+    #[test]
+    fn parses_the_server_name() {
+        let hello = records(&client_hello_message(Some("Example.COM")), usize::MAX);
+
+        assert_eq!(
+            parse_client_hello(&hello),
+            ClientHello::Parsed(Some(String::from("example.com")))
+        );
+    }
+
+    #[test]
+    fn parses_a_client_hello_without_a_server_name() {
+        let hello = records(&client_hello_message(None), usize::MAX);
+
+        assert_eq!(parse_client_hello(&hello), ClientHello::Parsed(None));
+    }
+
+    #[test]
+    fn waits_for_the_rest_of_a_truncated_record() {
+        let hello = records(&client_hello_message(Some("example.com")), usize::MAX);
+
+        for length in 0..hello.len() {
+            assert_eq!(
+                parse_client_hello(&hello[..length]),
+                ClientHello::Incomplete,
+                "truncated to {} bytes",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn parses_a_client_hello_fragmented_across_records() {
+        let hello = records(&client_hello_message(Some("example.com")), 7);
+
+        assert_eq!(
+            parse_client_hello(&hello),
+            ClientHello::Parsed(Some(String::from("example.com")))
+        );
+
+        let header_only = records(&client_hello_message(Some("example.com")), 2);
+        assert_eq!(
+            parse_client_hello(&header_only),
+            ClientHello::Parsed(Some(String::from("example.com")))
+        );
+    }
+
+    #[test]
+    fn rejects_connections_that_are_not_tls() {
+        assert_eq!(
+            parse_client_hello(b"SSH-2.0-OpenSSH_9.6\r\n"),
+            ClientHello::NotTls
+        );
+        assert_eq!(
+            parse_client_hello(b"EHLO example.com\r\n"),
+            ClientHello::NotTls
+        );
+    }
+
+    #[test]
+    fn ignores_a_handshake_that_is_not_a_client_hello() {
+        let mut message = client_hello_message(Some("example.com"));
+        message[0] = 0x02;
+
+        assert_eq!(
+            parse_client_hello(&records(&message, usize::MAX)),
+            ClientHello::Parsed(None)
+        );
+    }
+
+    #[test]
+    fn ignores_a_malformed_server_name_extension() {
+        let mut message = client_hello_message(Some("example.com"));
+        // Claim the host name is longer than the extension holding it
+        let length_offset = message.len() - "example.com".len() - 2;
+        message[length_offset + 1] = 0xff;
+
+        assert_eq!(
+            parse_client_hello(&records(&message, usize::MAX)),
+            ClientHello::Parsed(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_waiting_when_the_server_speaks_first() {
+        let (mut client, _server) = tokio::io::duplex(64);
+
+        let started = std::time::Instant::now();
+        let (read, server_name) = read_server_name(&mut client).await.unwrap();
+
+        assert!(read.is_empty());
+        assert_eq!(server_name, None);
+        assert!(started.elapsed() < CLIENT_HELLO_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn reads_a_client_hello_sent_in_pieces() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let hello = records(&client_hello_message(Some("example.com")), usize::MAX);
+
+        let sent = hello.clone();
+        tokio::spawn(async move {
+            for chunk in sent.chunks(10) {
+                server.write_all(chunk).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            server
+        });
+
+        let (read, server_name) = read_server_name(&mut client).await.unwrap();
+
+        assert_eq!(read, hello);
+        assert_eq!(server_name, Some(String::from("example.com")));
+    }
+}
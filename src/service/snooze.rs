@@ -0,0 +1,136 @@
+use crate::utils::logger::Logger;
+use chrono::{DateTime, Duration, Local};
+use std::sync::{Arc, Mutex};
+
+/// The enum that controls how long a blocked host is temporarily allowed for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnoozeDuration {
+    FiveMinutes,
+    OneHour,
+    /// Until the application is restarted.
+    UntilRestart,
+}
+
+impl std::fmt::Display for SnoozeDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnoozeDuration::FiveMinutes => write!(f, "5 Minutes"),
+            SnoozeDuration::OneHour => write!(f, "1 Hour"),
+            SnoozeDuration::UntilRestart => write!(f, "Until Restart"),
+        }
+    }
+}
+
+impl SnoozeDuration {
+    /// Returns when a temporary allow made at the given time expires, None when it lasts until restart.
+    ///
+    /// # Arguments:
+    /// * `now` - When the host was allowed.
+    fn expires(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            SnoozeDuration::FiveMinutes => Some(now + Duration::minutes(5)),
+            SnoozeDuration::OneHour => Some(now + Duration::hours(1)),
+            SnoozeDuration::UntilRestart => None,
+        }
+    }
+}
+
+/// A host let through the traffic filter for a while, without changing the exclusion list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemporaryAllow {
+    pub host: String,
+    pub duration: SnoozeDuration,
+    /// When the host stops being allowed, None when it's allowed until restart.
+    pub expires: Option<DateTime<Local>>,
+}
+
+impl TemporaryAllow {
+    /// Returns how long the host is still allowed for, None when it's allowed until restart.
+    ///
+    /// # Arguments:
+    /// * `now` - The local time to count from.
+    pub fn remaining(&self, now: DateTime<Local>) -> Option<Duration> {
+        self.expires
+            .map(|expires| (expires - now).max(Duration::zero()))
+    }
+
+    /// Returns whether the host is no longer allowed.
+    ///
+    /// # Arguments:
+    /// * `now` - The local time to check.
+    pub fn is_expired(&self, now: DateTime<Local>) -> bool {
+        self.expires.is_some_and(|expires| now >= expires)
+    }
+}
+
+/// The hosts temporarily allowed through the traffic filter, shared by every request.
+///
+/// They're only kept in memory, so none of them outlive the application.
+#[derive(Clone, Default)]
+pub struct TemporaryAllows {
+    allows: Arc<Mutex<Vec<TemporaryAllow>>>,
+}
+
+impl TemporaryAllows {
+    /// Allows a host through the traffic filter, replacing an earlier allow of the same host.
+    ///
+    /// # Arguments:
+    /// * `host` - The host to allow.
+    /// * `duration` - How long to allow the host for.
+    pub fn allow(&self, host: &str, duration: SnoozeDuration) {
+        let mut allows = self.allows.lock().unwrap();
+        allows.retain(|allow| !allow.host.eq_ignore_ascii_case(host));
+        allows.push(TemporaryAllow {
+            host: host.to_string(),
+            duration,
+            expires: duration.expires(Local::now()),
+        });
+    }
+
+    /// Stops allowing a host through the traffic filter, returning whether it was allowed.
+    ///
+    /// # Arguments:
+    /// * `host` - The host to stop allowing.
+    pub fn revoke(&self, host: &str) -> bool {
+        let mut allows = self.allows.lock().unwrap();
+        let count = allows.len();
+        allows.retain(|allow| !allow.host.eq_ignore_ascii_case(host));
+
+        allows.len() != count
+    }
+
+    /// Returns the hosts currently allowed, oldest first.
+    pub fn entries(&self) -> Vec<TemporaryAllow> {
+        self.allows.lock().unwrap().clone()
+    }
+
+    /// Returns whether a host is currently allowed through the traffic filter.
+    ///
+    /// # Arguments:
+    /// * `host` - The host being requested.
+    /// * `now` - The local time to check.
+    pub fn is_allowed(&self, host: &str, now: DateTime<Local>) -> bool {
+        self.allows
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|allow| allow.host.eq_ignore_ascii_case(host) && !allow.is_expired(now))
+    }
+
+    /// Removes the allows that have expired, logging each of them.
+    ///
+    /// # Arguments:
+    /// * `logger` - The Logger to record the expired allows in.
+    pub fn expire(&self, logger: &Logger) {
+        let now = Local::now();
+        let mut allows = self.allows.lock().unwrap();
+
+        allows.retain(|allow| {
+            if allow.is_expired(now) {
+                logger.info(&format!("Temporary allow expired -> {}", allow.host));
+                return false;
+            }
+            true
+        });
+    }
+}
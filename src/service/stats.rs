@@ -0,0 +1,167 @@
+use super::proxy::ProxyRequestLog;
+use std::collections::HashMap;
+
+/// The number of buckets the requests-over-time chart is split into.
+const STATS_BUCKETS: usize = 60;
+
+/// The number of hosts shown in each of the top host lists.
+const STATS_TOP_HOSTS: usize = 10;
+
+/// The enum that controls the time window covered by the traffic statistics.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum ProxyStatsWindow {
+    #[default]
+    FiveMinutes,
+    Hour,
+    Session,
+}
+
+impl std::fmt::Display for ProxyStatsWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyStatsWindow::FiveMinutes => write!(f, "Last 5 Minutes"),
+            ProxyStatsWindow::Hour => write!(f, "Last Hour"),
+            ProxyStatsWindow::Session => write!(f, "Session"),
+        }
+    }
+}
+
+impl ProxyStatsWindow {
+    /// Returns how far back the window reaches, or None if it covers the whole session.
+    fn duration(&self) -> Option<chrono::Duration> {
+        match self {
+            ProxyStatsWindow::FiveMinutes => Some(chrono::Duration::minutes(5)),
+            ProxyStatsWindow::Hour => Some(chrono::Duration::hours(1)),
+            ProxyStatsWindow::Session => None,
+        }
+    }
+}
+
+/// Contains the number of requests made within a slice of the statistics window.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProxyStatsBucket {
+    pub allowed: usize,
+    pub blocked: usize,
+}
+
+/// Contains the traffic statistics for the requests made within a window.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProxyStats {
+    pub total: usize,
+    pub allowed: usize,
+    pub blocked: usize,
+    pub client_errors: usize,
+    pub server_errors: usize,
+    pub failed: usize,
+    pub bucket_seconds: f64,
+    pub buckets: Vec<ProxyStatsBucket>,
+    pub top_hosts: Vec<(String, usize)>,
+    pub top_hosts_by_bytes: Vec<(String, u64)>,
+    pub top_blocked_hosts: Vec<(String, usize)>,
+    pub methods: Vec<(String, usize)>,
+}
+
+impl ProxyStats {
+    /// Creates the statistics for the requests made within the given window.
+    ///
+    /// # Arguments:
+    /// * `requests` - The requests made through the Proxy, oldest first.
+    /// * `window` - The ProxyStatsWindow to cover.
+    pub fn new(requests: &[ProxyRequestLog], window: ProxyStatsWindow) -> Self {
+        let now = chrono::Local::now();
+        let start = match window.duration() {
+            Some(duration) => now - duration,
+            None => requests
+                .first()
+                .map(|request| request.timestamp)
+                .unwrap_or(now),
+        };
+
+        let span = (now - start).num_milliseconds().max(1000) as f64 / 1000.;
+        let bucket_seconds = span / STATS_BUCKETS as f64;
+
+        let mut stats = Self {
+            bucket_seconds,
+            buckets: vec![ProxyStatsBucket::default(); STATS_BUCKETS],
+            ..Default::default()
+        };
+
+        let mut host_counts: HashMap<&str, usize> = HashMap::new();
+        let mut host_bytes: HashMap<&str, u64> = HashMap::new();
+        let mut blocked_hosts: HashMap<&str, usize> = HashMap::new();
+        let mut methods: HashMap<&str, usize> = HashMap::new();
+
+        for request in requests.iter().filter(|request| request.timestamp >= start) {
+            let offset = (request.timestamp - start).num_milliseconds() as f64 / 1000.;
+            let bucket = ((offset / bucket_seconds) as usize).min(STATS_BUCKETS - 1);
+
+            stats.total += 1;
+            *host_counts.entry(&request.host).or_default() += 1;
+            *methods.entry(&request.method).or_default() += 1;
+
+            *host_bytes.entry(&request.host).or_default() += request
+                .request_body
+                .iter()
+                .chain(request.response_body.iter())
+                .map(|body| body.size)
+                .sum::<u64>();
+
+            if request.blocked {
+                stats.blocked += 1;
+                stats.buckets[bucket].blocked += 1;
+                *blocked_hosts.entry(&request.host).or_default() += 1;
+                continue;
+            }
+
+            stats.allowed += 1;
+            stats.buckets[bucket].allowed += 1;
+
+            if request.error.is_some() {
+                stats.failed += 1;
+            } else if let Some(response) = &request.response {
+                match response.status {
+                    400..=499 => stats.client_errors += 1,
+                    500..=599 => stats.server_errors += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        stats.top_hosts = top_entries(host_counts);
+        stats.top_hosts_by_bytes = top_entries(host_bytes);
+        stats.top_blocked_hosts = top_entries(blocked_hosts);
+        stats.methods = top_entries(methods);
+
+        stats
+    }
+
+    /// Returns the highest number of requests per second in any bucket.
+    pub fn peak_rate(&self) -> f64 {
+        self.buckets
+            .iter()
+            .map(|bucket| (bucket.allowed + bucket.blocked) as f64 / self.bucket_seconds)
+            .fold(0., f64::max)
+    }
+
+    /// Returns the share of allowed requests that resulted in an error, as a percentage.
+    pub fn error_rate(&self) -> f64 {
+        if self.allowed == 0 {
+            return 0.;
+        }
+
+        (self.client_errors + self.server_errors + self.failed) as f64 / self.allowed as f64 * 100.
+    }
+}
+
+/// Sorts the given counts from highest to lowest, keeping the top entries.
+fn top_entries<T: Ord + Copy>(counts: HashMap<&str, T>) -> Vec<(String, T)> {
+    let mut entries: Vec<(String, T)> = counts
+        .into_iter()
+        .map(|(key, count)| (key.to_string(), count))
+        .collect();
+
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    entries.truncate(STATS_TOP_HOSTS);
+
+    entries
+}
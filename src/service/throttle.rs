@@ -0,0 +1,585 @@
+use super::{
+    forward::{ForwardError, ProxyBody},
+    header_rules::host_matches,
+    timeout::ConnectionActivity,
+};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body, Bytes, Frame, SizeHint},
+    Request, Response,
+};
+use rand::Rng;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep},
+};
+
+/// The most bytes a response, or tunnel, may carry before a simulated reset, which picks a point at random below it.
+const RESET_LIMIT: u64 = 64 * 1024;
+
+/// The network conditions simulated for the requests a profile applies to.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ThrottleProfile {
+    pub name: String,
+    /// Milliseconds added before each request is forwarded, or tunnel is opened.
+    pub latency: u64,
+    /// The most milliseconds added at random on top of the latency.
+    pub jitter: u64,
+    /// The download bandwidth in kbit/s, or 0 for no limit.
+    pub download: u64,
+    /// The upload bandwidth in kbit/s, or 0 for no limit.
+    pub upload: u64,
+    /// The percentage of connections that fail before reaching the upstream server.
+    pub failure_rate: u8,
+    /// The percentage of responses, and tunnels, that are reset part way through.
+    pub reset_rate: u8,
+}
+
+impl Default for ThrottleProfile {
+    fn default() -> Self {
+        Self {
+            name: String::from("Custom"),
+            latency: 0,
+            jitter: 0,
+            download: 0,
+            upload: 0,
+            failure_rate: 0,
+            reset_rate: 0,
+        }
+    }
+}
+
+impl ThrottleProfile {
+    /// Returns the built in network condition presets.
+    pub fn presets() -> Vec<ThrottleProfile> {
+        vec![
+            ThrottleProfile {
+                name: String::from("3G"),
+                latency: 300,
+                jitter: 50,
+                download: 1600,
+                upload: 750,
+                ..Default::default()
+            },
+            ThrottleProfile {
+                name: String::from("Slow 3G"),
+                latency: 2000,
+                jitter: 200,
+                download: 400,
+                upload: 400,
+                ..Default::default()
+            },
+            ThrottleProfile {
+                name: String::from("Flaky Wi-Fi"),
+                latency: 80,
+                jitter: 400,
+                download: 5000,
+                upload: 2000,
+                failure_rate: 5,
+                reset_rate: 5,
+            },
+            ThrottleProfile {
+                name: String::from("Offline"),
+                failure_rate: 100,
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// Returns the delay to add before a connection, including a random amount of jitter.
+    fn delay(&self) -> Duration {
+        let jitter = match self.jitter {
+            0 => 0,
+            jitter => rand::thread_rng().gen_range(0..=jitter),
+        };
+
+        Duration::from_millis(self.latency + jitter)
+    }
+
+    /// Returns whether an event with the given percentage chance should happen.
+    fn chance(percentage: u8) -> bool {
+        percentage > 0 && rand::thread_rng().gen_range(0..100) < percentage
+    }
+
+    /// Returns the number of bytes to transfer before a simulated reset, if this connection should be reset.
+    ///
+    /// # Arguments:
+    /// * `length` - The number of bytes the connection will carry, if known.
+    fn reset_after(&self, length: Option<u64>) -> Option<u64> {
+        let limit = length.unwrap_or(RESET_LIMIT).clamp(1, RESET_LIMIT);
+        Self::chance(self.reset_rate).then(|| rand::thread_rng().gen_range(0..limit))
+    }
+
+    /// Returns the message recorded when a connection is reset by this profile.
+    fn reset_message(&self) -> String {
+        format!(
+            "Connection reset, simulated by the {} network profile",
+            self.name
+        )
+    }
+}
+
+/// A rule that applies a throttle profile to the requests for matching hosts.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ThrottleRule {
+    pub enabled: bool,
+    /// The host to apply the rule to, e.g. example.com, *.example.com, or * for every host.
+    pub host: String,
+    pub profile: ThrottleProfile,
+}
+
+impl Default for ThrottleRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: String::from("*"),
+            profile: ThrottleProfile::default(),
+        }
+    }
+}
+
+/// Contains the network conditions simulated by the Proxy.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ProxyThrottle {
+    /// The profile applied to every host without a matching rule, or None to leave them unthrottled.
+    pub profile: Option<ThrottleProfile>,
+    pub rules: Vec<ThrottleRule>,
+}
+
+impl ProxyThrottle {
+    /// Returns the profile to apply to requests for a host, if any.
+    ///
+    /// # Arguments:
+    /// * `host` - The host a request is being sent to.
+    pub fn profile_for(&self, host: &str) -> Option<&ThrottleProfile> {
+        self.rules
+            .iter()
+            .find(|rule| rule.enabled && host_matches(&rule.host, host))
+            .map(|rule| &rule.profile)
+            .or(self.profile.as_ref())
+    }
+}
+
+/// Waits for the profile's latency, then returns an error if the connection should fail.
+///
+/// # Arguments:
+/// * `profile` - The ThrottleProfile to simulate.
+pub async fn simulate_connection(profile: &ThrottleProfile) -> Result<(), ForwardError> {
+    tokio::time::sleep(profile.delay()).await;
+
+    if ThrottleProfile::chance(profile.failure_rate) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionRefused,
+            format!(
+                "Connection failed, simulated by the {} network profile",
+                profile.name
+            ),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Limits the upload bandwidth of a request's body.
+///
+/// # Arguments:
+/// * `request` - The request to throttle.
+/// * `profile` - The ThrottleProfile to simulate.
+pub fn throttle_request(
+    request: Request<ProxyBody>,
+    profile: &ThrottleProfile,
+) -> Request<ProxyBody> {
+    let limiter = RateLimiter::new(profile.upload);
+    request.map(|body| ThrottledBody::new(body, limiter, None).boxed())
+}
+
+/// Limits the download bandwidth of a response's body, and may reset the connection part way through it.
+///
+/// # Arguments:
+/// * `response` - The response to throttle.
+/// * `profile` - The ThrottleProfile to simulate.
+/// * `on_reset` - The callback to run with a message if the connection is reset.
+pub fn throttle_response(
+    response: Response<ProxyBody>,
+    profile: &ThrottleProfile,
+    on_reset: impl FnOnce(String) + Send + Sync + 'static,
+) -> Response<ProxyBody> {
+    // Bodies of a known length aren't polled past their end, so the reset must come before it
+    let length = response.body().size_hint().exact();
+    let reset = profile
+        .reset_after(length)
+        .map(|after| (after, profile.reset_message(), on_reset));
+
+    wrap_response(response, RateLimiter::new(profile.download), reset)
+}
+
+/// Resets the connection once a response has sent a number of bytes of its body.
+///
+/// # Arguments:
+/// * `response` - The response to reset part way through.
+/// * `after` - The number of bytes of the body to send before resetting.
+/// * `message` - The message the callback is run with.
+/// * `on_reset` - The callback to run once the connection is reset.
+pub fn reset_response(
+    response: Response<ProxyBody>,
+    after: u64,
+    message: String,
+    on_reset: impl FnOnce(String) + Send + Sync + 'static,
+) -> Response<ProxyBody> {
+    let after = match response.body().size_hint().exact() {
+        Some(length) => after.min(length.saturating_sub(1)),
+        None => after,
+    };
+
+    wrap_response(
+        response,
+        RateLimiter::new(0),
+        Some((after, message, on_reset)),
+    )
+}
+
+/// Wraps a response's body in a ThrottledBody, adding the handle to reset its connection if it may be reset.
+///
+/// # Arguments:
+/// * `response` - The response to wrap.
+/// * `limiter` - The RateLimiter for the body's data.
+/// * `reset` - The bytes to send before a reset, along with the message and callback to run once it has.
+fn wrap_response(
+    response: Response<ProxyBody>,
+    limiter: RateLimiter,
+    reset: Option<(u64, String, impl FnOnce(String) + Send + Sync + 'static)>,
+) -> Response<ProxyBody> {
+    let reset = reset.map(|(after, message, on_reset)| PendingReset {
+        after,
+        handle: ResetHandle::default(),
+        on_reset: Box::new(move || on_reset(message)),
+    });
+    let handle = reset.as_ref().map(|reset| reset.handle.clone());
+
+    let mut response = response.map(|body| ThrottledBody::new(body, limiter, reset).boxed());
+    if let Some(handle) = handle {
+        response.extensions_mut().insert(handle);
+    }
+
+    response
+}
+
+/// Added to a response's extensions, so its body can reset the client connection it's sent on.
+#[derive(Clone, Debug, Default)]
+pub struct ResetHandle(Arc<Mutex<Option<Arc<ConnectionActivity>>>>);
+
+impl ResetHandle {
+    /// Sets the client connection to reset.
+    ///
+    /// # Arguments:
+    /// * `activity` - The ConnectionActivity of the client connection.
+    pub fn attach(&self, activity: Arc<ConnectionActivity>) {
+        *self.0.lock().unwrap() = Some(activity);
+    }
+
+    /// Resets the client connection, if one has been attached.
+    fn reset(&self) {
+        if let Some(activity) = self.0.lock().unwrap().take() {
+            activity.request_reset();
+        }
+    }
+}
+
+/// Limits the rate data may be sent at, allowing short bursts.
+struct RateLimiter {
+    /// The bytes allowed per second, or None for no limit.
+    rate: Option<f64>,
+    available: f64,
+    refilled: Instant,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl RateLimiter {
+    /// Creates a new RateLimiter.
+    ///
+    /// # Arguments:
+    /// * `kbps` - The bandwidth in kbit/s, or 0 for no limit.
+    fn new(kbps: u64) -> Self {
+        let rate = (kbps > 0).then(|| kbps as f64 * 1000. / 8.);
+
+        Self {
+            rate,
+            available: rate.map(Self::burst).unwrap_or_default(),
+            refilled: Instant::now(),
+            sleep: None,
+        }
+    }
+
+    /// Returns the most bytes that may be sent at once, a tenth of a second's worth.
+    fn burst(rate: f64) -> f64 {
+        (rate / 10.).max(512.)
+    }
+
+    /// Returns how many of the wanted bytes may be sent now, or Pending until some may be.
+    ///
+    /// # Arguments:
+    /// * `cx` - The context of the task waiting to send.
+    /// * `wanted` - The number of bytes waiting to be sent.
+    fn poll_allowance(&mut self, cx: &mut Context<'_>, wanted: usize) -> Poll<usize> {
+        let Some(rate) = self.rate else {
+            return Poll::Ready(wanted);
+        };
+
+        if wanted == 0 {
+            return Poll::Ready(0);
+        }
+
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+
+            let now = Instant::now();
+            let burst = Self::burst(rate);
+            self.available = (self.available
+                + now.duration_since(self.refilled).as_secs_f64() * rate)
+                .min(burst);
+            self.refilled = now;
+
+            // Wait for a reasonably sized chunk, rather than waking up for every byte
+            let target = (wanted as f64).min(burst);
+            if self.available >= target {
+                return Poll::Ready((self.available as usize).min(wanted));
+            }
+
+            let wait = Duration::from_secs_f64((target - self.available) / rate);
+            self.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+
+    /// Records bytes that have been sent.
+    fn consume(&mut self, sent: usize) {
+        if self.rate.is_some() {
+            self.available -= sent as f64;
+        }
+    }
+}
+
+/// Contains when, and how, a throttled body should reset its connection.
+struct PendingReset {
+    /// The number of bytes to send before resetting the connection.
+    after: u64,
+    handle: ResetHandle,
+    on_reset: Box<dyn FnOnce() + Send + Sync>,
+}
+
+/// A body that streams its data no faster than a RateLimiter allows, optionally resetting part way through.
+struct ThrottledBody<B> {
+    inner: B,
+    limiter: RateLimiter,
+    /// Data that has been read from the inner body, but not sent yet.
+    pending: Option<Bytes>,
+    reset: Option<PendingReset>,
+    sent: u64,
+    stalled: bool,
+}
+
+impl<B> ThrottledBody<B> {
+    fn new(inner: B, limiter: RateLimiter, reset: Option<PendingReset>) -> Self {
+        Self {
+            inner,
+            limiter,
+            pending: None,
+            reset,
+            sent: 0,
+            stalled: false,
+        }
+    }
+
+    /// Resets the connection if enough data has been sent, or the body has ended, returning whether it was.
+    ///
+    /// # Arguments:
+    /// * `ended` - Whether the inner body has no more data.
+    fn check_reset(&mut self, ended: bool) -> bool {
+        if let Some(reset) = &self.reset {
+            if ended || self.sent >= reset.after {
+                if let Some(reset) = self.reset.take() {
+                    reset.handle.reset();
+                    (reset.on_reset)();
+                }
+                self.stalled = true;
+            }
+        }
+
+        self.stalled
+    }
+}
+
+impl<B> Body for ThrottledBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+
+        // A reset body never finishes, the connection is dropped underneath it
+        if this.stalled || this.check_reset(false) {
+            return Poll::Pending;
+        }
+
+        if this.pending.is_none() {
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.pending = Some(data),
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                None if this.check_reset(true) => return Poll::Pending,
+                other => return Poll::Ready(other),
+            }
+        }
+
+        let Some(data) = this.pending.as_mut() else {
+            return Poll::Pending;
+        };
+
+        // Data past the reset point is never sent
+        let wanted = match &this.reset {
+            Some(reset) => data.len().min((reset.after - this.sent) as usize),
+            None => data.len(),
+        };
+        let allowed = ready!(this.limiter.poll_allowance(cx, wanted));
+        let chunk = data.split_to(allowed);
+        if data.is_empty() {
+            this.pending = None;
+        }
+
+        this.limiter.consume(chunk.len());
+        this.sent += chunk.len() as u64;
+
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_none() && self.reset.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Wraps the client side of a tunnel, limiting its bandwidth and optionally resetting it part way through.
+pub struct ThrottledIo<T> {
+    inner: T,
+    upload: RateLimiter,
+    download: RateLimiter,
+    /// The number of bytes to carry before resetting the tunnel, and the message to reset it with.
+    reset: Option<(u64, String)>,
+    transferred: u64,
+    scratch: Vec<u8>,
+}
+
+impl<T> ThrottledIo<T> {
+    /// Creates a new ThrottledIo.
+    ///
+    /// # Arguments:
+    /// * `inner` - The client side of the tunnel.
+    /// * `profile` - The ThrottleProfile to simulate, or None to pass the traffic through unchanged.
+    pub fn new(inner: T, profile: Option<&ThrottleProfile>) -> Self {
+        Self {
+            inner,
+            upload: RateLimiter::new(profile.map_or(0, |profile| profile.upload)),
+            download: RateLimiter::new(profile.map_or(0, |profile| profile.download)),
+            reset: profile.and_then(|profile| {
+                profile
+                    .reset_after(None)
+                    .map(|after| (after, profile.reset_message()))
+            }),
+            transferred: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Returns an error once the tunnel has carried enough data to be reset.
+    fn check_reset(&self) -> std::io::Result<()> {
+        match &self.reset {
+            Some((after, message)) if self.transferred >= *after => Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                message.clone(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ThrottledIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        this.check_reset()?;
+
+        let allowed = ready!(this.upload.poll_allowance(cx, buf.remaining()));
+
+        // Reads are made into a smaller buffer when the whole one can't be filled yet
+        let read = if allowed >= buf.remaining() {
+            let filled = buf.filled().len();
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            buf.filled().len() - filled
+        } else {
+            this.scratch.resize(allowed, 0);
+            let mut limited = ReadBuf::new(&mut this.scratch);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut limited))?;
+            let read = limited.filled().len();
+            buf.put_slice(&this.scratch[..read]);
+            read
+        };
+
+        this.upload.consume(read);
+        this.transferred += read as u64;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ThrottledIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        this.check_reset()?;
+
+        let allowed = ready!(this.download.poll_allowance(cx, buf.len()));
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..allowed]))?;
+
+        this.download.consume(written);
+        this.transferred += written as u64;
+
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
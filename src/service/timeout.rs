@@ -0,0 +1,438 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::Notify,
+};
+
+/// The enum that carries which of the Proxy's timeouts fired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProxyTimeout {
+    Connect,
+    RequestHeaders,
+    Response,
+    Idle,
+    TunnelIdle,
+    TunnelLifetime,
+}
+
+impl std::fmt::Display for ProxyTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyTimeout::Connect => write!(f, "Connect"),
+            ProxyTimeout::RequestHeaders => write!(f, "Request Headers"),
+            ProxyTimeout::Response => write!(f, "Response"),
+            ProxyTimeout::Idle => write!(f, "Idle"),
+            ProxyTimeout::TunnelIdle => write!(f, "Tunnel Idle"),
+            ProxyTimeout::TunnelLifetime => write!(f, "Tunnel Lifetime"),
+        }
+    }
+}
+
+/// The error returned when one of the Proxy's timeouts fires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeoutError {
+    pub timeout: ProxyTimeout,
+    pub duration: Duration,
+}
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let waiting_for = match self.timeout {
+            ProxyTimeout::Connect => "the upstream server to accept a connection",
+            ProxyTimeout::RequestHeaders => "the client to send its request headers",
+            ProxyTimeout::Response => "the upstream server to respond",
+            ProxyTimeout::Idle => "any traffic on an idle connection",
+            ProxyTimeout::TunnelIdle => "any traffic through an idle tunnel",
+            ProxyTimeout::TunnelLifetime => "the tunnel to close",
+        };
+
+        write!(
+            f,
+            "Timed out after {}s waiting for {}",
+            self.duration.as_secs(),
+            waiting_for
+        )
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// Runs a future, returning a TimeoutError if it doesn't complete within the duration.
+///
+/// # Arguments:
+/// * `timeout` - The ProxyTimeout being applied.
+/// * `duration` - How long to wait, or None to wait forever.
+/// * `future` - The future to run.
+pub async fn with_timeout<F: std::future::Future>(
+    timeout: ProxyTimeout,
+    duration: Option<Duration>,
+    future: F,
+) -> Result<F::Output, TimeoutError> {
+    match duration {
+        Some(duration) => tokio::time::timeout(duration, future)
+            .await
+            .map_err(|_| TimeoutError { timeout, duration }),
+        None => Ok(future.await),
+    }
+}
+
+/// Contains the number of times each timeout has fired for a single host.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProxyTimeoutCounts {
+    pub connect: usize,
+    pub request_headers: usize,
+    pub response: usize,
+    pub idle: usize,
+    pub tunnel_idle: usize,
+    pub tunnel_lifetime: usize,
+}
+
+impl ProxyTimeoutCounts {
+    /// Returns the number of timeouts of any kind.
+    pub fn total(&self) -> usize {
+        self.connect
+            + self.request_headers
+            + self.response
+            + self.idle
+            + self.tunnel_idle
+            + self.tunnel_lifetime
+    }
+}
+
+/// Counts the timeouts that have fired, per host.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyTimeoutStats {
+    hosts: HashMap<String, ProxyTimeoutCounts>,
+}
+
+impl ProxyTimeoutStats {
+    /// Counts a timeout against a host.
+    ///
+    /// # Arguments:
+    /// * `host` - The host, or client address when no host is known, the timeout fired for.
+    /// * `timeout` - The ProxyTimeout that fired.
+    pub fn record(&mut self, host: &str, timeout: ProxyTimeout) {
+        let counts = self.hosts.entry(host.to_string()).or_default();
+
+        match timeout {
+            ProxyTimeout::Connect => counts.connect += 1,
+            ProxyTimeout::RequestHeaders => counts.request_headers += 1,
+            ProxyTimeout::Response => counts.response += 1,
+            ProxyTimeout::Idle => counts.idle += 1,
+            ProxyTimeout::TunnelIdle => counts.tunnel_idle += 1,
+            ProxyTimeout::TunnelLifetime => counts.tunnel_lifetime += 1,
+        }
+    }
+
+    /// Returns the timeout counts for every host, most timeouts first.
+    pub fn get_hosts(&self) -> Vec<(String, ProxyTimeoutCounts)> {
+        let mut hosts: Vec<(String, ProxyTimeoutCounts)> = self
+            .hosts
+            .iter()
+            .map(|(host, counts)| (host.clone(), counts.clone()))
+            .collect();
+
+        hosts.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then(a.0.cmp(&b.0)));
+        hosts
+    }
+}
+
+/// Tracks the traffic on a connection so timeouts can tell when it's idle or stalled.
+#[derive(Debug)]
+pub struct ConnectionActivity {
+    started: Instant,
+    last_activity: AtomicU64,
+    headers_started: AtomicU64,
+    active_requests: AtomicUsize,
+    http2: AtomicBool,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    reset: Notify,
+}
+
+/// Used in place of a time when nothing is being waited on.
+const NOT_WAITING: u64 = u64::MAX;
+
+impl ConnectionActivity {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            last_activity: AtomicU64::new(0),
+            headers_started: AtomicU64::new(NOT_WAITING),
+            active_requests: AtomicUsize::new(0),
+            http2: AtomicBool::new(false),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            reset: Notify::new(),
+        })
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn elapsed_since(&self, millis: u64) -> Duration {
+        Duration::from_millis(self.now().saturating_sub(millis))
+    }
+
+    fn on_read(&self, data: &[u8]) {
+        if self
+            .bytes_read
+            .fetch_add(data.len() as u64, Ordering::Relaxed)
+            == 0
+            && data.starts_with(b"PRI * HTTP/2.0")
+        {
+            self.http2.store(true, Ordering::Relaxed);
+        }
+
+        let now = self.now();
+        self.last_activity.store(now, Ordering::Relaxed);
+
+        // Data arriving while no request is being handled is the start of the next request's headers
+        if self.active_requests.load(Ordering::Relaxed) == 0 {
+            let _ = self.headers_started.compare_exchange(
+                NOT_WAITING,
+                now,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    fn on_write(&self, written: usize) {
+        self.bytes_written
+            .fetch_add(written as u64, Ordering::Relaxed);
+        self.last_activity.store(self.now(), Ordering::Relaxed);
+    }
+
+    /// Records that a request's headers have been read, returning a guard to hold until its response is sent.
+    pub fn start_request(self: &Arc<Self>) -> ActiveRequest {
+        self.active_requests.fetch_add(1, Ordering::Relaxed);
+        self.headers_started.store(NOT_WAITING, Ordering::Relaxed);
+
+        ActiveRequest {
+            activity: Arc::clone(self),
+        }
+    }
+
+    /// Returns the number of bytes read from, and written to, the connection.
+    pub fn bytes(&self) -> (u64, u64) {
+        (
+            self.bytes_read.load(Ordering::Relaxed),
+            self.bytes_written.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Returns how long the connection has been without traffic.
+    pub fn idle_for(&self) -> Duration {
+        self.elapsed_since(self.last_activity.load(Ordering::Relaxed))
+    }
+
+    /// Asks for the connection to be reset rather than closed gracefully.
+    pub fn request_reset(&self) {
+        self.reset.notify_one();
+    }
+
+    /// Waits until the connection has been asked to reset.
+    pub async fn reset_requested(&self) {
+        self.reset.notified().await
+    }
+
+    /// Returns the timeout that has fired for a client connection, if any.
+    ///
+    /// # Arguments:
+    /// * `header_timeout` - How long a client may take to send its request headers.
+    /// * `idle_timeout` - How long a connection may be idle between requests.
+    pub fn expired(
+        &self,
+        header_timeout: Option<Duration>,
+        idle_timeout: Option<Duration>,
+    ) -> Option<ProxyTimeout> {
+        if self.active_requests.load(Ordering::Relaxed) > 0 {
+            return None;
+        }
+
+        // HTTP/2 clients send frames between requests, so only the idle timeout applies
+        let headers_started = self.headers_started.load(Ordering::Relaxed);
+        if headers_started != NOT_WAITING && !self.http2.load(Ordering::Relaxed) {
+            return header_timeout
+                .filter(|timeout| self.elapsed_since(headers_started) > *timeout)
+                .map(|_| ProxyTimeout::RequestHeaders);
+        }
+
+        idle_timeout
+            .filter(|timeout| self.idle_for() > *timeout)
+            .map(|_| ProxyTimeout::Idle)
+    }
+}
+
+/// Marks a request as being handled until it's dropped, along with its response.
+pub struct ActiveRequest {
+    activity: Arc<ConnectionActivity>,
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.activity
+            .active_requests
+            .fetch_sub(1, Ordering::Relaxed);
+        self.activity
+            .last_activity
+            .store(self.activity.now(), Ordering::Relaxed);
+    }
+}
+
+/// Wraps a connection, recording its traffic in a ConnectionActivity.
+pub struct ActivityIo<T> {
+    inner: T,
+    activity: Arc<ConnectionActivity>,
+}
+
+impl<T> ActivityIo<T> {
+    pub fn new(inner: T, activity: Arc<ConnectionActivity>) -> Self {
+        Self { inner, activity }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ActivityIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = result {
+            if buf.filled().len() > filled {
+                self.activity.on_read(&buf.filled()[filled..]);
+            }
+        }
+
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ActivityIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(written)) = result {
+            self.activity.on_write(written);
+        }
+
+        result
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+
+        if let Poll::Ready(Ok(written)) = result {
+            self.activity.on_write(written);
+        }
+
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Waits until a client connection's header or idle timeout fires.
+///
+/// # Arguments:
+/// * `activity` - The ConnectionActivity of the client connection.
+/// * `timeouts` - Returns the current header and idle timeouts, so changes apply to open connections.
+pub async fn client_timeout(
+    activity: &ConnectionActivity,
+    timeouts: impl Fn() -> (Option<Duration>, Option<Duration>),
+) -> TimeoutError {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+
+        let (header_timeout, idle_timeout) = timeouts();
+        if let Some(timeout) = activity.expired(header_timeout, idle_timeout) {
+            let duration = match timeout {
+                ProxyTimeout::RequestHeaders => header_timeout,
+                _ => idle_timeout,
+            };
+
+            return TimeoutError {
+                timeout,
+                duration: duration.unwrap_or_default(),
+            };
+        }
+    }
+}
+
+/// Waits until a tunnel has been idle for too long.
+///
+/// # Arguments:
+/// * `activity` - The ConnectionActivity of the tunnel.
+/// * `idle_timeout` - How long the tunnel may be idle, or None to wait forever.
+pub async fn idle_timeout(
+    activity: &ConnectionActivity,
+    idle_timeout: Option<Duration>,
+) -> TimeoutError {
+    let Some(duration) = idle_timeout else {
+        return std::future::pending().await;
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+
+        if activity.idle_for() > duration {
+            return TimeoutError {
+                timeout: ProxyTimeout::TunnelIdle,
+                duration,
+            };
+        }
+    }
+}
+
+/// Waits until a tunnel has been open for too long.
+///
+/// # Arguments:
+/// * `lifetime` - How long the tunnel may be open, or None to wait forever.
+pub async fn lifetime_timeout(lifetime: Option<Duration>) -> TimeoutError {
+    let Some(duration) = lifetime else {
+        return std::future::pending().await;
+    };
+
+    tokio::time::sleep(duration).await;
+
+    TimeoutError {
+        timeout: ProxyTimeout::TunnelLifetime,
+        duration,
+    }
+}
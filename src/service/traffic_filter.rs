@@ -1,26 +1,594 @@
-#[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone, Copy)]
+use super::{
+    history::{FilterDiff, RuleListDiff},
+    schedule::TrafficSchedule,
+};
+use chrono::{DateTime, Duration, Local};
+use regex::Regex;
+use std::{borrow::Cow, collections::HashSet, sync::OnceLock};
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq)]
 pub enum TrafficFilterType {
     #[default]
     Allow,
     Deny,
 }
 
-impl ToString for TrafficFilterType {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for TrafficFilterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficFilterType::Allow => write!(f, "Allow"),
+            TrafficFilterType::Deny => write!(f, "Deny"),
+        }
+    }
+}
+
+/// The enum that controls how a rule's pattern is matched against a URI.
+#[derive(
+    Debug, Default, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Hash,
+)]
+pub enum TrafficRuleKind {
+    /// Matches when either the URI or the pattern contains the other.
+    #[default]
+    Contains,
+    /// Matches when the pattern, a regular expression, matches the URI.
+    Regex,
+}
+
+impl std::fmt::Display for TrafficRuleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficRuleKind::Contains => write!(f, "Contains"),
+            TrafficRuleKind::Regex => write!(f, "Regex"),
+        }
+    }
+}
+
+/// The enum that controls what happens to a request matched by a rule.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq)]
+pub enum TrafficRuleAction {
+    /// Allow or block the request, depending on the exclusion list the rule is in.
+    #[default]
+    Filter,
+    /// Redirect the client to the target URL.
+    Redirect,
+    /// Forward the request to the target URL, host or path instead.
+    Rewrite,
+    /// Answer the request with the target as the body, without forwarding it.
+    Respond,
+    /// Always allow the request, even in a list that blocks the requests it matches.
+    Allow,
+}
+
+impl std::fmt::Display for TrafficRuleAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TrafficFilterType::Allow => String::from("Allow"),
-            TrafficFilterType::Deny => String::from("Deny"),
+            TrafficRuleAction::Filter => write!(f, "Filter"),
+            TrafficRuleAction::Redirect => write!(f, "Redirect"),
+            TrafficRuleAction::Rewrite => write!(f, "Rewrite"),
+            TrafficRuleAction::Respond => write!(f, "Static Response"),
+            TrafficRuleAction::Allow => write!(f, "Always Allow"),
+        }
+    }
+}
+
+/// The prefix of a Regex rule in the text form of an exclusion list, e.g. regex:^https://.
+const REGEX_LINE_PREFIX: &str = "regex:";
+
+/// The enum that controls the order an exclusion list is sorted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrafficRuleOrder {
+    /// Alphabetically by pattern.
+    Pattern,
+    /// The rules that matched the most requests first.
+    Hits,
+    /// The rules that matched a request most recently first.
+    LastHit,
+}
+
+impl std::fmt::Display for TrafficRuleOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrafficRuleOrder::Pattern => write!(f, "Pattern (A-Z)"),
+            TrafficRuleOrder::Hits => write!(f, "Most Hits"),
+            TrafficRuleOrder::LastHit => write!(f, "Last Hit"),
+        }
+    }
+}
+
+/// A problem with a line in the text form of an exclusion list, or a row of an imported file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficRuleLineIssue {
+    /// The line number, starting at 1, or the row number of an imported file.
+    pub line: usize,
+    pub message: String,
+    /// Whether the line can't be used, rather than only being worth a warning.
+    pub error: bool,
+}
+
+/// Contains how often a rule has matched a request, and when it last did.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TrafficRuleHits {
+    pub count: u64,
+    pub last_hit: Option<DateTime<Local>>,
+    /// The URI, or address, of the last request the rule matched.
+    pub last_uri: String,
+    /// When the rule was added, or its hits were reset, rules stored without it count from when they're loaded.
+    pub added: DateTime<Local>,
+}
+
+impl Default for TrafficRuleHits {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            last_hit: None,
+            last_uri: String::default(),
+            added: Local::now(),
+        }
+    }
+}
+
+impl TrafficRuleHits {
+    /// Counts a request matched by the rule.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI, or address, of the matched request.
+    /// * `now` - When the request was matched.
+    pub fn record(&mut self, uri: &str, now: DateTime<Local>) {
+        self.count += 1;
+        self.last_hit = Some(now);
+        self.last_uri = uri.to_string();
+    }
+}
+
+/// The fields of a TrafficRule, as they're stored.
+#[derive(serde::Deserialize)]
+#[serde(default)]
+struct TrafficRuleFields {
+    pattern: String,
+    kind: TrafficRuleKind,
+    action: TrafficRuleAction,
+    status: u16,
+    target: String,
+    content_type: String,
+    schedule: Option<TrafficSchedule>,
+    audit: bool,
+    hits: TrafficRuleHits,
+}
+
+impl Default for TrafficRuleFields {
+    fn default() -> Self {
+        let rule = TrafficRule::default();
+
+        Self {
+            pattern: rule.pattern,
+            kind: rule.kind,
+            action: rule.action,
+            status: rule.status,
+            target: rule.target,
+            content_type: rule.content_type,
+            schedule: rule.schedule,
+            audit: rule.audit,
+            hits: rule.hits,
+        }
+    }
+}
+
+/// A stored rule, exclusion lists saved before rules had actions contain only the pattern.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TrafficRuleEntry {
+    Pattern(String),
+    Rule(TrafficRuleFields),
+}
+
+impl From<TrafficRuleEntry> for TrafficRule {
+    fn from(entry: TrafficRuleEntry) -> Self {
+        match entry {
+            TrafficRuleEntry::Pattern(pattern) => TrafficRule::new(pattern),
+            TrafficRuleEntry::Rule(fields) => TrafficRule {
+                pattern: fields.pattern,
+                kind: fields.kind,
+                action: fields.action,
+                status: fields.status,
+                target: fields.target,
+                content_type: fields.content_type,
+                schedule: fields.schedule,
+                audit: fields.audit,
+                hits: fields.hits,
+                compiled: OnceLock::new(),
+            },
         }
     }
 }
 
+/// An item in an exclusion list, matching URIs and deciding what happens to them.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+#[serde(from = "TrafficRuleEntry")]
+pub struct TrafficRule {
+    pub pattern: String,
+    pub kind: TrafficRuleKind,
+    pub action: TrafficRuleAction,
+    /// The status code of a redirect or static response.
+    pub status: u16,
+    /// The URL to redirect to, the URL, host or path to rewrite to, or the body of a static response.
+    /// Regex rules may refer to their capture groups, e.g. $1 or ${name}.
+    pub target: String,
+    /// The content type of a static response.
+    pub content_type: String,
+    /// When the rule is active, or None if it always is.
+    pub schedule: Option<TrafficSchedule>,
+    /// Whether requests the rule would block are only recorded, and forwarded anyway.
+    pub audit: bool,
+    /// How often the rule has matched a request, which isn't part of its configuration.
+    pub hits: TrafficRuleHits,
+    /// The pattern a Regex rule was compiled from, along with the compiled regex, which clones share.
+    #[serde(skip)]
+    compiled: OnceLock<(String, Option<Regex>)>,
+}
+
+impl Default for TrafficRule {
+    fn default() -> Self {
+        Self {
+            pattern: String::default(),
+            kind: TrafficRuleKind::default(),
+            action: TrafficRuleAction::default(),
+            status: 302,
+            target: String::default(),
+            content_type: String::from("text/plain"),
+            schedule: None,
+            audit: false,
+            hits: TrafficRuleHits::default(),
+            compiled: OnceLock::new(),
+        }
+    }
+}
+
+impl PartialEq for TrafficRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+            && self.kind == other.kind
+            && self.action == other.action
+            && self.status == other.status
+            && self.target == other.target
+            && self.content_type == other.content_type
+            && self.schedule == other.schedule
+            && self.audit == other.audit
+    }
+}
+
+impl TrafficRule {
+    /// Creates a rule that filters URIs containing the given pattern.
+    ///
+    /// # Arguments:
+    /// * `pattern` - The value to match URIs against.
+    pub fn new(pattern: String) -> Self {
+        Self {
+            pattern,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the rule as a line in the text form of an exclusion list, e.g. example.com or regex:^https://.
+    pub fn to_line(&self) -> String {
+        match self.kind {
+            TrafficRuleKind::Contains => self.pattern.clone(),
+            TrafficRuleKind::Regex => format!("{}{}", REGEX_LINE_PREFIX, self.pattern),
+        }
+    }
+
+    /// Returns whether the rule matches exactly the same URIs as another, so only the first of them is ever used.
+    ///
+    /// # Arguments:
+    /// * `other` - The TrafficRule to compare with.
+    pub fn is_duplicate_of(&self, other: &TrafficRule) -> bool {
+        self.pattern == other.pattern && self.kind == other.kind
+    }
+
+    /// Returns the compiled pattern of a Regex rule, or None if it isn't a valid regular expression.
+    ///
+    /// A rule whose pattern was edited after it was compiled, e.g. a copy being edited, compiles it again.
+    fn regex(&self) -> Option<Cow<'_, Regex>> {
+        let (pattern, regex) = self
+            .compiled
+            .get_or_init(|| (self.pattern.clone(), Regex::new(&self.pattern).ok()));
+
+        match *pattern == self.pattern {
+            true => regex.as_ref().map(Cow::Borrowed),
+            false => Regex::new(&self.pattern).ok().map(Cow::Owned),
+        }
+    }
+
+    /// Drops the compiled pattern if the pattern was edited since, so it's compiled once again rather than every time.
+    fn refresh_compiled(&mut self) {
+        if self
+            .compiled
+            .get()
+            .is_some_and(|(pattern, _)| *pattern != self.pattern)
+        {
+            self.compiled = OnceLock::new();
+        }
+    }
+
+    /// Returns an error message if the rule's pattern isn't a valid regular expression.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.kind {
+            TrafficRuleKind::Contains => Ok(()),
+            TrafficRuleKind::Regex => Regex::new(&self.pattern)
+                .map(|_| ())
+                .map_err(|error| error.to_string()),
+        }
+    }
+
+    /// Returns whether the rule matches the provided URI.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI, or address, to match.
+    pub fn matches(&self, uri: &str) -> bool {
+        match self.kind {
+            TrafficRuleKind::Contains => uri.contains(&self.pattern) || self.pattern.contains(uri),
+            TrafficRuleKind::Regex => self.regex().is_some_and(|regex| regex.is_match(uri)),
+        }
+    }
+
+    /// Returns whether the rule's schedule, if it has one, is active at the given time.
+    ///
+    /// # Arguments:
+    /// * `now` - The local time to check.
+    pub fn is_active(&self, now: DateTime<Local>) -> bool {
+        self.schedule
+            .as_ref()
+            .is_none_or(|schedule| schedule.is_active(now))
+    }
+
+    /// Returns whether the rule hasn't matched a request in the given number of days.
+    ///
+    /// A rule that never matched a request is only unused once it has been in the list that long.
+    ///
+    /// # Arguments:
+    /// * `days` - The number of days to look back.
+    /// * `now` - The local time to count back from.
+    pub fn is_unused(&self, days: i64, now: DateTime<Local>) -> bool {
+        self.hits.last_hit.unwrap_or(self.hits.added) < now - Duration::days(days)
+    }
+
+    /// Returns the rule's target, with any capture groups replaced by the values matched in the URI.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI the rule matched.
+    pub fn expand_target(&self, uri: &str) -> String {
+        let captures = match self.kind {
+            TrafficRuleKind::Contains => None,
+            TrafficRuleKind::Regex => self.regex().and_then(|regex| regex.captures(uri)),
+        };
+
+        match captures {
+            Some(captures) => {
+                let mut target = String::new();
+                captures.expand(&self.target, &mut target);
+                target
+            }
+            None => self.target.clone(),
+        }
+    }
+
+    /// Returns a short description of what the rule does to a matched request, e.g. Redirect (302) -> URL.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI the rule matched.
+    pub fn describe_action(&self, uri: &str) -> Option<String> {
+        match self.action {
+            TrafficRuleAction::Filter | TrafficRuleAction::Allow => None,
+            TrafficRuleAction::Redirect => Some(format!(
+                "{} ({}) -> {}",
+                self.action,
+                self.status,
+                self.expand_target(uri)
+            )),
+            TrafficRuleAction::Rewrite => {
+                Some(format!("{} -> {}", self.action, self.expand_target(uri)))
+            }
+            TrafficRuleAction::Respond => Some(format!("{} ({})", self.action, self.status)),
+        }
+    }
+}
+
+/// Returns the rules in the text form of an exclusion list, one per line, along with any problems found in it.
+///
+/// Blank lines and lines starting with # are skipped. Lines that match an existing rule keep its action,
+/// schedule and hits, as the text form only contains the pattern and how it matches.
+///
+/// # Arguments:
+/// * `text` - The text form of the exclusion list.
+/// * `existing` - The rules the text was made from, if any.
+pub fn parse_rule_lines(
+    text: &str,
+    existing: &[TrafficRule],
+) -> (Vec<TrafficRule>, Vec<TrafficRuleLineIssue>) {
+    let mut rules: Vec<TrafficRule> = Vec::new();
+    let mut issues = Vec::new();
+    let mut unused = existing.iter().collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut issue = |message: String, error: bool| {
+            issues.push(TrafficRuleLineIssue {
+                line: index + 1,
+                message,
+                error,
+            })
+        };
+
+        let rule = match unused.iter().position(|rule| rule.to_line() == line) {
+            Some(position) => unused.remove(position).clone(),
+            None => match line.strip_prefix(REGEX_LINE_PREFIX) {
+                Some(pattern) => TrafficRule {
+                    kind: TrafficRuleKind::Regex,
+                    ..TrafficRule::new(pattern.trim().to_string())
+                },
+                None => TrafficRule::new(line.to_string()),
+            },
+        };
+
+        if rule.pattern.is_empty() {
+            issue(String::from("The pattern is empty."), true);
+            continue;
+        }
+
+        if let Err(error) = rule.validate() {
+            issue(error, true);
+            continue;
+        }
+
+        if !seen.insert((rule.pattern.clone(), rule.kind)) {
+            issue(format!("{} is already in the list.", rule.pattern), false);
+        }
+
+        rules.push(rule);
+    }
+
+    (rules, issues)
+}
+
+/// A TrafficRule as a row of an exported exclusion list.
 #[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct TrafficRuleRecord {
+    #[serde(rename = "REQUEST")]
+    pub pattern: String,
+    #[serde(rename = "TYPE")]
+    pub kind: TrafficRuleKind,
+    #[serde(rename = "ACTION")]
+    pub action: TrafficRuleAction,
+    #[serde(rename = "STATUS")]
+    pub status: Option<u16>,
+    #[serde(rename = "TARGET")]
+    pub target: String,
+    #[serde(rename = "CONTENT_TYPE")]
+    pub content_type: String,
+    /// The rule's schedule as JSON, or empty if it's always active.
+    #[serde(rename = "SCHEDULE")]
+    pub schedule: String,
+    #[serde(rename = "AUDIT")]
+    pub audit: bool,
+}
+
+impl TrafficRuleRecord {
+    /// The column headers of an exported exclusion list.
+    pub const HEADERS: [&'static str; 8] = [
+        "REQUEST",
+        "TYPE",
+        "ACTION",
+        "STATUS",
+        "TARGET",
+        "CONTENT_TYPE",
+        "SCHEDULE",
+        "AUDIT",
+    ];
+}
+
+impl From<TrafficRule> for TrafficRuleRecord {
+    fn from(rule: TrafficRule) -> Self {
+        Self {
+            pattern: rule.pattern,
+            kind: rule.kind,
+            action: rule.action,
+            status: Some(rule.status),
+            target: rule.target,
+            content_type: rule.content_type,
+            schedule: rule
+                .schedule
+                .and_then(|schedule| serde_json::to_string(&schedule).ok())
+                .unwrap_or_default(),
+            audit: rule.audit,
+        }
+    }
+}
+
+impl From<TrafficRuleRecord> for TrafficRule {
+    fn from(record: TrafficRuleRecord) -> Self {
+        let rule = TrafficRule::default();
+
+        Self {
+            pattern: record.pattern,
+            kind: record.kind,
+            action: record.action,
+            status: record.status.unwrap_or(rule.status),
+            target: record.target,
+            content_type: match record.content_type.is_empty() {
+                true => rule.content_type,
+                false => record.content_type,
+            },
+            schedule: serde_json::from_str(&record.schedule).ok(),
+            audit: record.audit,
+            ..rule
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 pub struct TrafficFilterList {
-    pub allow_exclusions: Vec<String>,
-    pub deny_exclusions: Vec<String>,
+    pub allow_exclusions: Vec<TrafficRule>,
+    pub deny_exclusions: Vec<TrafficRule>,
+    /// When the Deny List filters traffic, or None if it always does.
+    #[serde(default)]
+    pub allow_schedule: Option<TrafficSchedule>,
+    /// When the Allow List filters traffic, or None if it always does.
+    #[serde(default)]
+    pub deny_schedule: Option<TrafficSchedule>,
+    /// Whether requests the Deny List would block are only recorded, and forwarded anyway.
+    #[serde(default)]
+    pub allow_audit: bool,
+    /// Whether requests the Allow List would block are only recorded, and forwarded anyway.
+    #[serde(default)]
+    pub deny_audit: bool,
 }
 
-#[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone)]
+impl TrafficFilterList {
+    /// Returns the rules of the exclusion list used by a filter type as a mutable reference.
+    fn rules_mut(&mut self, filter_type: TrafficFilterType) -> &mut Vec<TrafficRule> {
+        match filter_type {
+            TrafficFilterType::Allow => &mut self.allow_exclusions,
+            TrafficFilterType::Deny => &mut self.deny_exclusions,
+        }
+    }
+
+    /// Returns the schedule of the exclusion list used by a filter type as a mutable reference.
+    fn schedule_mut(&mut self, filter_type: TrafficFilterType) -> &mut Option<TrafficSchedule> {
+        match filter_type {
+            TrafficFilterType::Allow => &mut self.allow_schedule,
+            TrafficFilterType::Deny => &mut self.deny_schedule,
+        }
+    }
+
+    /// Returns the audit mode of the exclusion list used by a filter type as a mutable reference.
+    fn audit_mut(&mut self, filter_type: TrafficFilterType) -> &mut bool {
+        match filter_type {
+            TrafficFilterType::Allow => &mut self.allow_audit,
+            TrafficFilterType::Deny => &mut self.deny_audit,
+        }
+    }
+
+    /// Replaces the rules of the exclusion list used by a filter type, and returns what changed.
+    fn set_rules(
+        &mut self,
+        filter_type: TrafficFilterType,
+        mut rules: Vec<TrafficRule>,
+    ) -> RuleListDiff {
+        rules.iter_mut().for_each(TrafficRule::refresh_compiled);
+        let before = std::mem::take(self.rules_mut(filter_type));
+        let diff = RuleListDiff::between(filter_type, before, &rules);
+        *self.rules_mut(filter_type) = rules;
+        diff
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 pub struct TrafficFilter {
     filter_enabled: bool,
     filter_type: TrafficFilterType,
@@ -45,8 +613,13 @@ impl TrafficFilter {
     ///
     /// # Arguments:
     /// * `active` - A bool value, whether the exclusion list is active or not.
-    pub fn set_enabled(&mut self, active: bool) {
-        self.filter_enabled = active
+    pub fn set_enabled(&mut self, active: bool) -> FilterDiff {
+        let before = std::mem::replace(&mut self.filter_enabled, active);
+
+        FilterDiff {
+            enabled: Some((before, active)),
+            ..Default::default()
+        }
     }
 
     /// Returns the current exclusion type, e.g. Allow/Deny.
@@ -58,8 +631,13 @@ impl TrafficFilter {
     ///
     /// # Arguments:
     /// * `filter_type` - A TrafficFilterType to set the filter type to.
-    pub fn set_filter_type(&mut self, filter_type: TrafficFilterType) {
-        self.filter_type = filter_type;
+    pub fn set_filter_type(&mut self, filter_type: TrafficFilterType) -> FilterDiff {
+        let before = std::mem::replace(&mut self.filter_type, filter_type);
+
+        FilterDiff {
+            filter_type: Some((before, filter_type)),
+            ..Default::default()
+        }
     }
 
     /// Returns the opposing filter type, e.g. Allow -> Deny.
@@ -70,8 +648,85 @@ impl TrafficFilter {
         }
     }
 
+    /// Returns the name of the exclusion list in use, e.g. Allow List.
+    pub fn get_profile_name(&self) -> String {
+        Self::profile_name(self.get_filter_type())
+    }
+
+    /// Returns the name of the exclusion list used by a filter type, e.g. Allow List for Deny.
+    ///
+    /// # Arguments:
+    /// * `filter_type` - The TrafficFilterType using the exclusion list.
+    pub fn profile_name(filter_type: TrafficFilterType) -> String {
+        match filter_type {
+            TrafficFilterType::Allow => String::from("Deny List"),
+            TrafficFilterType::Deny => String::from("Allow List"),
+        }
+    }
+
+    /// Returns the schedule of the exclusion list in use.
+    pub fn get_schedule(&self) -> Option<TrafficSchedule> {
+        match self.get_filter_type() {
+            TrafficFilterType::Allow => self.filter_list.allow_schedule.clone(),
+            TrafficFilterType::Deny => self.filter_list.deny_schedule.clone(),
+        }
+    }
+
+    /// Sets the schedule of the exclusion list in use.
+    ///
+    /// # Arguments:
+    /// * `schedule` - When the exclusion list filters traffic, or None if it always does.
+    pub fn set_schedule(&mut self, schedule: Option<TrafficSchedule>) -> FilterDiff {
+        let list = self.get_filter_type();
+        let before = std::mem::replace(self.filter_list.schedule_mut(list), schedule.clone());
+
+        FilterDiff {
+            schedule: Some((list, before, schedule)),
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the exclusion list in use is scheduled to filter traffic at the given time.
+    ///
+    /// # Arguments:
+    /// * `now` - The local time to check.
+    pub fn is_scheduled(&self, now: DateTime<Local>) -> bool {
+        self.get_schedule()
+            .is_none_or(|schedule| schedule.is_active(now))
+    }
+
+    /// Returns whether the exclusion list in use only records the requests it would block.
+    pub fn get_audit(&self) -> bool {
+        match self.get_filter_type() {
+            TrafficFilterType::Allow => self.filter_list.allow_audit,
+            TrafficFilterType::Deny => self.filter_list.deny_audit,
+        }
+    }
+
+    /// Sets whether the exclusion list in use only records the requests it would block.
+    ///
+    /// # Arguments:
+    /// * `audit` - A bool value, whether blocked requests are forwarded anyway.
+    pub fn set_audit(&mut self, audit: bool) -> FilterDiff {
+        let list = self.get_filter_type();
+        let before = std::mem::replace(self.filter_list.audit_mut(list), audit);
+
+        FilterDiff {
+            audit: Some((list, before, audit)),
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether a blocked request should only be recorded, as its profile or rule is in audit mode.
+    ///
+    /// # Arguments:
+    /// * `rule` - The exclusion list item the request matched, if any.
+    pub fn is_audited(&self, rule: Option<&TrafficRule>) -> bool {
+        self.get_audit() || rule.is_some_and(|rule| rule.audit)
+    }
+
     /// Returns the current exclusion list.
-    pub fn get_filter_list(&self) -> Vec<String> {
+    pub fn get_filter_list(&self) -> Vec<TrafficRule> {
         match self.get_filter_type() {
             TrafficFilterType::Allow => self.filter_list.allow_exclusions.clone(),
             TrafficFilterType::Deny => self.filter_list.deny_exclusions.clone(),
@@ -79,21 +734,21 @@ impl TrafficFilter {
     }
 
     /// Returns the current exclusion list as a mutable reference.
-    pub fn get_filter_list_mut(&mut self) -> &mut Vec<String> {
-        match self.get_filter_type() {
-            TrafficFilterType::Allow => self.filter_list.allow_exclusions.as_mut(),
-            TrafficFilterType::Deny => self.filter_list.deny_exclusions.as_mut(),
-        }
+    ///
+    /// Changes to the rules are made through the methods that return what they changed, so they can be undone.
+    fn get_filter_list_mut(&mut self) -> &mut Vec<TrafficRule> {
+        self.filter_list.rules_mut(self.filter_type)
     }
 
     /// Sets the exclusion list you're currently using.
     ///
     /// # Arguments:
-    /// * `list` - A Vec<String> of URIs to set the current exclusion list to.
-    pub fn set_filter_list(&mut self, list: Vec<String>) {
-        match self.get_filter_type() {
-            TrafficFilterType::Allow => self.filter_list.allow_exclusions = list,
-            TrafficFilterType::Deny => self.filter_list.deny_exclusions = list,
+    /// * `list` - A Vec<TrafficRule> to set the current exclusion list to.
+    pub fn set_filter_list(&mut self, list: Vec<TrafficRule>) -> FilterDiff {
+        let filter_type = self.get_filter_type();
+        FilterDiff {
+            rules: vec![self.filter_list.set_rules(filter_type, list)],
+            ..Default::default()
         }
     }
 
@@ -101,11 +756,51 @@ impl TrafficFilter {
     ///     
     /// # Arguments:
     /// * `value` - A String to add to/remove from the current exclusion list.
-    pub fn update_filter_list(&mut self, value: String) {
-        if self.in_filter_list(&value) {
-            self.get_filter_list_mut().retain(|item| item != &value);
+    pub fn update_filter_list(&mut self, value: String) -> FilterDiff {
+        // A regex rule doesn't always match its own pattern, so compare the pattern as well
+        let is_pattern = self
+            .get_filter_list_mut()
+            .iter()
+            .any(|item| item.pattern == value);
+
+        if is_pattern || self.in_filter_list(&value) {
+            let indices: Vec<usize> = self
+                .get_filter_list_mut()
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.pattern == value)
+                .map(|(index, _)| index)
+                .collect();
+            self.remove_filter_list_items(&indices)
         } else {
-            self.get_filter_list_mut().push(value);
+            let index = self.get_filter_list_mut().len();
+            self.insert_filter_list_item(index, TrafficRule::new(value))
+        }
+    }
+
+    /// Inserts an item into the current exclusion list, unless it's already in it.
+    ///
+    /// # Arguments:
+    /// * `index` - The position to insert the item at, past the end adds it to the end.
+    /// * `rule` - The TrafficRule to insert.
+    pub fn insert_filter_list_item(&mut self, index: usize, mut rule: TrafficRule) -> FilterDiff {
+        rule.refresh_compiled();
+        let list = self.get_filter_type();
+        let filter_list = self.get_filter_list_mut();
+        if filter_list.iter().any(|item| item.is_duplicate_of(&rule)) {
+            return FilterDiff::default();
+        }
+
+        let index = index.min(filter_list.len());
+        filter_list.insert(index, rule.clone());
+
+        FilterDiff {
+            rules: vec![RuleListDiff {
+                list,
+                added: vec![(index, rule)],
+                ..Default::default()
+            }],
+            ..Default::default()
         }
     }
 
@@ -113,19 +808,355 @@ impl TrafficFilter {
     ///
     /// # Arguments:
     /// * `index` - A usize indicating the position of the value to update in the current exclusion list.
-    /// * `value` - A String to update the existing record in the current exclusion list to.
-    pub fn update_filter_list_item(&mut self, index: usize, value: String) {
-        self.get_filter_list_mut()[index] = value;
+    /// * `rule` - A TrafficRule to update the existing record in the current exclusion list to.
+    pub fn update_filter_list_item(&mut self, index: usize, mut rule: TrafficRule) -> FilterDiff {
+        let list = self.get_filter_type();
+
+        // The list may have changed, e.g. by an undo, while the item was being edited
+        let Some(item) = self.get_filter_list_mut().get_mut(index) else {
+            return FilterDiff::default();
+        };
+
+        // Requests may have matched the item while it was being edited
+        rule.hits = item.hits.clone();
+        rule.refresh_compiled();
+        let before = std::mem::replace(item, rule.clone());
+        if before == rule {
+            return FilterDiff::default();
+        }
+
+        FilterDiff {
+            rules: vec![RuleListDiff {
+                list,
+                removed: vec![(index, before)],
+                order: None,
+                added: vec![(index, rule)],
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the traffic filter with another state of it, and returns what changed.
+    ///
+    /// Only the rules between those both states start and end with are replaced, the others keep their hit counters.
+    ///
+    /// # Arguments:
+    /// * `state` - The TrafficFilter to go back to.
+    pub fn restore(&mut self, state: TrafficFilter) -> FilterDiff {
+        let TrafficFilter {
+            filter_enabled,
+            filter_type,
+            filter_list,
+        } = state;
+
+        let mut diff = FilterDiff {
+            enabled: Some((self.filter_enabled, filter_enabled)),
+            filter_type: Some((self.filter_type, filter_type)),
+            ..Default::default()
+        };
+        self.filter_enabled = filter_enabled;
+        self.filter_type = filter_type;
+
+        for (list, rules, schedule, audit) in [
+            (
+                TrafficFilterType::Allow,
+                filter_list.allow_exclusions,
+                filter_list.allow_schedule,
+                filter_list.allow_audit,
+            ),
+            (
+                TrafficFilterType::Deny,
+                filter_list.deny_exclusions,
+                filter_list.deny_schedule,
+                filter_list.deny_audit,
+            ),
+        ] {
+            diff.rules.push(self.filter_list.set_rules(list, rules));
+
+            // A diff holds one schedule and audit mode, which is enough for every change but this one
+            let schedule_before =
+                std::mem::replace(self.filter_list.schedule_mut(list), schedule.clone());
+            if schedule_before != schedule {
+                diff.schedule = Some((list, schedule_before, schedule));
+            }
+            let audit_before = std::mem::replace(self.filter_list.audit_mut(list), audit);
+            if audit_before != audit {
+                diff.audit = Some((list, audit_before, audit));
+            }
+        }
+
+        diff.rules.retain(|rules| !rules.is_empty());
+        diff
+    }
+
+    /// Applies a recorded change to the traffic filter, e.g. the inverse of one to undo it.
+    ///
+    /// # Arguments:
+    /// * `diff` - The FilterDiff to apply.
+    pub fn apply_diff(&mut self, diff: &FilterDiff) {
+        if let Some((_, enabled)) = diff.enabled {
+            self.filter_enabled = enabled;
+        }
+        if let Some((_, filter_type)) = diff.filter_type {
+            self.filter_type = filter_type;
+        }
+        if let Some((list, _, schedule)) = &diff.schedule {
+            *self.filter_list.schedule_mut(*list) = schedule.clone();
+        }
+        if let Some((list, _, audit)) = diff.audit {
+            *self.filter_list.audit_mut(list) = audit;
+        }
+        for rules in &diff.rules {
+            rules.apply(self.filter_list.rules_mut(rules.list));
+        }
+    }
+
+    /// Adds rules to the end of an exclusion list, skipping those already in it, and returns what was added.
+    fn push_rules(
+        &mut self,
+        list: TrafficFilterType,
+        rules: impl IntoIterator<Item = TrafficRule>,
+    ) -> RuleListDiff {
+        let filter_list = self.filter_list.rules_mut(list);
+        let mut added = Vec::new();
+
+        for mut rule in rules {
+            if !filter_list.iter().any(|item| item.is_duplicate_of(&rule)) {
+                rule.refresh_compiled();
+                added.push((filter_list.len(), rule.clone()));
+                filter_list.push(rule);
+            }
+        }
+
+        RuleListDiff {
+            list,
+            added,
+            ..Default::default()
+        }
+    }
+
+    /// Takes several items out of the current exclusion list, and returns them along with their positions.
+    fn take_filter_list_items(&mut self, indices: &[usize]) -> Vec<(usize, TrafficRule)> {
+        let mut removed = Vec::new();
+        let kept = std::mem::take(self.get_filter_list_mut())
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, rule)| match indices.contains(&index) {
+                true => {
+                    removed.push((index, rule));
+                    None
+                }
+                false => Some(rule),
+            })
+            .collect();
+
+        *self.get_filter_list_mut() = kept;
+        removed
+    }
+
+    /// Adds items to the current exclusion list, skipping those already in it, and returns what was added.
+    ///
+    /// # Arguments:
+    /// * `rules` - The TrafficRules to add to the end of the current exclusion list.
+    pub fn add_filter_list_items(&mut self, rules: Vec<TrafficRule>) -> FilterDiff {
+        let list = self.get_filter_type();
+        FilterDiff {
+            rules: vec![self.push_rules(list, rules)],
+            ..Default::default()
+        }
+    }
+
+    /// Removes several items from the current exclusion list.
+    ///
+    /// # Arguments:
+    /// * `indices` - The positions of the items to remove from the current exclusion list.
+    pub fn remove_filter_list_items(&mut self, indices: &[usize]) -> FilterDiff {
+        FilterDiff {
+            rules: vec![RuleListDiff {
+                list: self.get_filter_type(),
+                removed: self.take_filter_list_items(indices),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Moves several items from the current exclusion list to the other one, and returns what changed.
+    ///
+    /// Items already in the other exclusion list are only removed from the current one.
+    ///
+    /// # Arguments:
+    /// * `indices` - The positions of the items to move in the current exclusion list.
+    pub fn move_filter_list_items(&mut self, indices: &[usize]) -> FilterDiff {
+        let removed = self.take_filter_list_items(indices);
+        let moved = removed
+            .iter()
+            .map(|(_, rule)| rule.clone())
+            .collect::<Vec<_>>();
+        let added = self.push_rules(self.get_opposing_filter_type(), moved);
+
+        FilterDiff {
+            rules: vec![
+                RuleListDiff {
+                    list: self.get_filter_type(),
+                    removed,
+                    ..Default::default()
+                },
+                added,
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// Sorts the current exclusion list, which changes the order its items are matched in.
+    ///
+    /// # Arguments:
+    /// * `order` - The TrafficRuleOrder to sort the current exclusion list in.
+    pub fn sort_filter_list(&mut self, order: TrafficRuleOrder) -> FilterDiff {
+        let list = self.get_filter_type();
+        let filter_list = self.get_filter_list_mut();
+        let mut indices: Vec<usize> = (0..filter_list.len()).collect();
+
+        match order {
+            TrafficRuleOrder::Pattern => {
+                indices.sort_by_key(|index| filter_list[*index].pattern.to_lowercase());
+            }
+            TrafficRuleOrder::Hits => {
+                indices.sort_by_key(|index| std::cmp::Reverse(filter_list[*index].hits.count));
+            }
+            TrafficRuleOrder::LastHit => {
+                indices.sort_by_key(|index| std::cmp::Reverse(filter_list[*index].hits.last_hit));
+            }
+        }
+
+        if indices
+            .iter()
+            .enumerate()
+            .all(|(position, index)| position == *index)
+        {
+            return FilterDiff::default();
+        }
+
+        let diff = RuleListDiff {
+            list,
+            order: Some(indices),
+            ..Default::default()
+        };
+        diff.apply(filter_list);
+
+        FilterDiff {
+            rules: vec![diff],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the positions of the items in the current exclusion list that duplicate an earlier item.
+    pub fn get_duplicates(&self) -> Vec<usize> {
+        let filter_list = self.get_filter_list();
+
+        (0..filter_list.len())
+            .filter(|index| {
+                filter_list[..*index]
+                    .iter()
+                    .any(|item| item.is_duplicate_of(&filter_list[*index]))
+            })
+            .collect()
+    }
+
+    /// Removes the items in the current exclusion list that duplicate an earlier item, as they're never used.
+    pub fn remove_duplicates(&mut self) -> FilterDiff {
+        let duplicates = self.get_duplicates();
+        self.remove_filter_list_items(&duplicates)
+    }
+
+    /// Counts a request matched by an item in the current exclusion list.
+    ///
+    /// # Arguments:
+    /// * `rule` - The TrafficRule that matched the request.
+    /// * `uri` - The URI, or address, of the matched request.
+    /// * `now` - When the request was matched.
+    pub fn record_hit(&mut self, rule: &TrafficRule, uri: &str, now: DateTime<Local>) {
+        if let Some(item) = self
+            .get_filter_list_mut()
+            .iter_mut()
+            .find(|item| *item == rule)
+        {
+            item.hits.record(uri, now);
+        }
+    }
+
+    /// Clears the hit counters of every item in the current exclusion list.
+    pub fn reset_hits(&mut self) {
+        for item in self.get_filter_list_mut().iter_mut() {
+            item.hits = TrafficRuleHits::default();
+        }
     }
 
     /// Returns whether the provided URI is in the exclusion list.
     ///
     /// # Arguments:
     /// * `uri` - A str to check the current exclusion list for.
-    pub fn in_filter_list(&self, uri: &String) -> bool {
-        self.get_filter_list()
+    pub fn in_filter_list(&self, uri: &str) -> bool {
+        self.get_matching_rule(uri).is_some()
+    }
+
+    /// Returns the first item in the exclusion list that matches the provided URI.
+    ///
+    /// # Arguments:
+    /// * `uri` - A str to check the current exclusion list for.
+    pub fn get_matching_rule(&self, uri: &str) -> Option<TrafficRule> {
+        let filter_list = match self.get_filter_type() {
+            TrafficFilterType::Allow => &self.filter_list.allow_exclusions,
+            TrafficFilterType::Deny => &self.filter_list.deny_exclusions,
+        };
+
+        filter_list.iter().find(|item| item.matches(uri)).cloned()
+    }
+
+    /// Returns the first item in the exclusion list that matches the provided URI, and is active at the given time.
+    ///
+    /// # Arguments:
+    /// * `uri` - A str to check the current exclusion list for.
+    /// * `now` - The local time to check the rules' schedules against.
+    pub fn get_active_rule(&self, uri: &str, now: DateTime<Local>) -> Option<TrafficRule> {
+        let filter_list = match self.get_filter_type() {
+            TrafficFilterType::Allow => &self.filter_list.allow_exclusions,
+            TrafficFilterType::Deny => &self.filter_list.deny_exclusions,
+        };
+
+        filter_list
             .iter()
-            .any(|item| uri.contains(item) || item.contains(*&uri))
+            .find(|item| item.is_active(now) && item.matches(uri))
+            .cloned()
+    }
+
+    /// Returns whether a request should be blocked, along with the exclusion list item it matched.
+    ///
+    /// # Arguments:
+    /// * `uri` - The URI, or address, being requested.
+    /// * `now` - The local time to check the schedules against.
+    pub fn filter_request(&self, uri: &str, now: DateTime<Local>) -> (bool, Option<TrafficRule>) {
+        // Schedules are judged in local time, a profile outside its schedule doesn't filter anything
+        if !self.get_enabled() || !self.is_scheduled(now) {
+            return (false, None);
+        }
+
+        let rule = self.get_active_rule(uri, now);
+
+        // Requests matched by a rule with an action are never blocked
+        if rule
+            .as_ref()
+            .is_some_and(|rule| rule.action != TrafficRuleAction::Filter)
+        {
+            return (false, rule);
+        }
+
+        let is_excluded_address = rule.is_some();
+        let is_traffic_blocking = self.is_blocking();
+        let is_blocking_but_exluded = !is_excluded_address && is_traffic_blocking;
+        let is_allowing_but_excluded = is_excluded_address && !is_traffic_blocking;
+
+        (is_allowing_but_excluded || is_blocking_but_exluded, rule)
     }
 
     /// Returns whether we're blocking by exclusion, or allowing by exclusion.
@@ -0,0 +1,202 @@
+use crate::service::{
+    proxy::Proxy,
+    settings::{ProxyBlockStatus, DEFAULT_BLOCK_TEMPLATE},
+};
+use eframe::{
+    egui::{self, Layout, RichText, TextEdit},
+    emath::Align,
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the block page editor open.
+pub const BLOCK_PAGE_EDITOR_ID: &str = "block_page_editor";
+
+/// Shows the block page settings in a separate window, while it's open.
+pub fn block_page_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(BLOCK_PAGE_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut block_page = proxy.get_settings().block_page;
+    let previous = block_page.clone();
+
+    egui::Window::new("Block Page")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([480., 420.])
+        .show(ctx, |ui| {
+            egui::Grid::new("block_page_settings")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Respond With:");
+                    egui::ComboBox::from_id_source("block_page_status")
+                        .selected_text(block_page.status.to_string())
+                        .show_ui(ui, |ui| {
+                            for status in [
+                                ProxyBlockStatus::Forbidden,
+                                ProxyBlockStatus::UnavailableForLegalReasons,
+                                ProxyBlockStatus::NoContent,
+                                ProxyBlockStatus::Reset,
+                            ] {
+                                ui.selectable_value(
+                                    &mut block_page.status,
+                                    status,
+                                    status.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Exceptions:");
+                    ui.checkbox(
+                        &mut block_page.exception_link,
+                        "Offer a link to request an exception",
+                    );
+                    ui.end_row();
+                });
+
+            ui.separator();
+
+            if matches!(
+                block_page.status,
+                ProxyBlockStatus::NoContent | ProxyBlockStatus::Reset
+            ) {
+                ui.label(
+                    RichText::new("The page isn't shown when responding with this status.")
+                        .color(Color32::YELLOW),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("HTML Template")
+                    .on_hover_text("Clients that prefer JSON or plain text are sent the same details in that format.");
+
+                ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                    if ui
+                        .add_enabled(
+                            block_page.template != DEFAULT_BLOCK_TEMPLATE,
+                            egui::Button::new("Restore Default"),
+                        )
+                        .clicked()
+                    {
+                        block_page.template = String::from(DEFAULT_BLOCK_TEMPLATE);
+                    }
+                });
+            });
+
+            ui.label(
+                RichText::new("Placeholders: {url}, {rule}, {profile}, {time}, {exception_link}")
+                    .size(11.)
+                    .color(Color32::GRAY),
+            );
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    ui.add(
+                        TextEdit::multiline(&mut block_page.template)
+                            .code_editor()
+                            .desired_rows(16)
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        });
+
+    if block_page != previous {
+        proxy.set_block_page(block_page);
+    }
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
+
+/// Shows the exceptions requested from the block page, so they can be approved or dismissed.
+pub fn exception_queue(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let exceptions = proxy.get_exception_requests();
+    if exceptions.is_empty() {
+        return;
+    }
+
+    egui::CollapsingHeader::new(format!("Exception Requests ({})", exceptions.len()))
+        .default_open(true)
+        .show_unindented(ui, |ui| {
+            ui.group(|ui| {
+                egui::ScrollArea::vertical()
+                    .id_source("exception_queue_scrollarea")
+                    .max_height(120.)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        // Rows are removed after the loop so the indices stay valid while drawing
+                        let mut approved = None;
+                        let mut dismissed = None;
+
+                        for (index, exception) in exceptions.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                                    if ui.button("Dismiss").clicked() {
+                                        dismissed = Some(index);
+                                    }
+
+                                    if ui
+                                        .button("Approve")
+                                        .on_hover_text(
+                                            "Update the exclusion list to allow this URL.",
+                                        )
+                                        .clicked()
+                                    {
+                                        approved = Some(index);
+                                    }
+
+                                    ui.with_layout(Layout::left_to_right(Align::Min), |ui| {
+                                        ui.add(
+                                            egui::Label::new(
+                                                RichText::new(&exception.url).size(12.5),
+                                            )
+                                            .truncate(),
+                                        )
+                                        .on_hover_ui(
+                                            |ui| {
+                                                egui::Grid::new("exception_details")
+                                                    .num_columns(2)
+                                                    .show(ui, |ui| {
+                                                        for (name, value) in [
+                                                            ("URL:", exception.url.clone()),
+                                                            (
+                                                                "Rule:",
+                                                                exception.rule.clone().unwrap_or(
+                                                                    String::from("None"),
+                                                                ),
+                                                            ),
+                                                            ("Profile:", exception.profile.clone()),
+                                                            ("Client:", exception.client.clone()),
+                                                            (
+                                                                "Requested:",
+                                                                exception
+                                                                    .requested
+                                                                    .format("%Y-%m-%d %H:%M:%S")
+                                                                    .to_string(),
+                                                            ),
+                                                        ] {
+                                                            ui.label(name);
+                                                            ui.label(value);
+                                                            ui.end_row();
+                                                        }
+                                                    });
+                                            },
+                                        );
+                                    });
+                                });
+                            });
+                            ui.separator();
+                        }
+
+                        if let Some(index) = approved {
+                            proxy.approve_exception(index);
+                        } else if let Some(index) = dismissed {
+                            proxy.dismiss_exception(index);
+                        }
+                    });
+            });
+        });
+}
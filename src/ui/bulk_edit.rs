@@ -0,0 +1,334 @@
+use crate::service::{
+    proxy::Proxy,
+    traffic_filter::{parse_rule_lines, TrafficRule, TrafficRuleOrder},
+};
+use eframe::{
+    egui::{self, Key, Modifiers, RichText, TextEdit},
+    epaint::Color32,
+};
+use std::collections::BTreeSet;
+
+/// The orders offered when sorting the exclusion list.
+const SORT_ORDERS: [TrafficRuleOrder; 3] = [
+    TrafficRuleOrder::Pattern,
+    TrafficRuleOrder::Hits,
+    TrafficRuleOrder::LastHit,
+];
+
+/// The items selected in the exclusion list, kept in egui memory between frames.
+#[derive(Clone, Debug, Default)]
+pub struct ExclusionSelection {
+    rows: BTreeSet<usize>,
+    /// The row a shift click selects from.
+    anchor: Option<usize>,
+}
+
+impl ExclusionSelection {
+    /// Returns the selection of the exclusion list, dropping rows the list no longer has.
+    ///
+    /// # Arguments:
+    /// * `len` - The number of items in the exclusion list.
+    /// * `ctx` - The egui Context the selection is kept in.
+    pub fn load(len: usize, ctx: &egui::Context) -> Self {
+        let mut selection =
+            ctx.memory_mut(|m| m.data.get_temp::<Self>(Self::id()).unwrap_or_default());
+        selection.rows.retain(|row| *row < len);
+
+        selection
+    }
+
+    /// Keeps the selection in egui memory for the next frame.
+    pub fn store(self, ctx: &egui::Context) {
+        ctx.memory_mut(|m| m.data.insert_temp(Self::id(), self));
+    }
+
+    /// Updates the selection for a click on a row, extending it with shift and toggling the row with ctrl.
+    ///
+    /// # Arguments:
+    /// * `row` - The position of the clicked item in the exclusion list.
+    /// * `modifiers` - The keyboard modifiers held during the click.
+    pub fn click(&mut self, row: usize, modifiers: Modifiers) {
+        match (modifiers.shift, self.anchor) {
+            (true, Some(anchor)) => {
+                if !modifiers.command {
+                    self.rows.clear();
+                }
+                self.rows.extend(anchor.min(row)..=anchor.max(row));
+            }
+            _ if modifiers.command => {
+                if !self.rows.remove(&row) {
+                    self.rows.insert(row);
+                }
+                self.anchor = Some(row);
+            }
+            _ => {
+                let only_row = self.rows.len() == 1 && self.rows.contains(&row);
+                self.rows.clear();
+                if !only_row {
+                    self.rows.insert(row);
+                }
+                self.anchor = Some(row);
+            }
+        }
+    }
+
+    /// Returns whether an item in the exclusion list is selected.
+    pub fn contains(&self, row: usize) -> bool {
+        self.rows.contains(&row)
+    }
+
+    /// Returns the positions of the selected items, in order.
+    pub fn rows(&self) -> Vec<usize> {
+        self.rows.iter().copied().collect()
+    }
+
+    /// Removes every item from the selection.
+    pub fn clear(&mut self) {
+        self.rows.clear();
+        self.anchor = None;
+    }
+
+    /// Returns the identifier the selection is kept under in egui memory.
+    fn id() -> egui::Id {
+        egui::Id::new("exclusion_selection")
+    }
+}
+
+/// The text form of the exclusion list being edited, while the raw text mode is on.
+#[derive(Clone, Debug, Default)]
+struct RawTextEdit {
+    text: String,
+    /// The exclusion list the text was made from, so unchanged lines keep their settings.
+    original: Vec<TrafficRule>,
+}
+
+/// Shows the bulk actions of the exclusion list: selecting, removing, moving, sorting, deduplicating and pasting items.
+///
+/// # Arguments:
+/// * `proxy` - The Proxy to update the exclusion list of.
+/// * `selection` - The selected items of the exclusion list.
+/// * `ui` - The Ui to show the actions in.
+pub fn bulk_edit_toolbar(proxy: &mut Proxy, selection: &mut ExclusionSelection, ui: &mut egui::Ui) {
+    let traffic_filter = proxy.get_traffic_filter();
+    let exclusion_list = traffic_filter.get_filter_list();
+    let duplicates = traffic_filter.get_duplicates();
+    let selected = selection.rows();
+
+    ui.horizontal(|ui| {
+        if ui.button("Select All").clicked() {
+            selection.rows = (0..exclusion_list.len()).collect();
+        }
+
+        if !selected.is_empty() {
+            ui.label(
+                RichText::new(format!("{} selected", selected.len()))
+                    .color(Color32::LIGHT_BLUE)
+                    .size(11.),
+            );
+
+            if ui.button("Clear").clicked() {
+                selection.clear();
+            }
+
+            let delete_pressed = !ui.ctx().wants_keyboard_input()
+                && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Delete));
+            if ui
+                .button("Remove")
+                .on_hover_text("Remove the selected items. (Delete)")
+                .clicked()
+                || delete_pressed
+            {
+                proxy.remove_exclusion_rules(&selected);
+                selection.clear();
+            }
+
+            let opposing_profile = format!("{} List", traffic_filter.get_filter_type());
+            if ui
+                .button(format!("Move to {}", opposing_profile))
+                .on_hover_text(format!(
+                    "Move the selected items to the {}, skipping those already in it.",
+                    opposing_profile
+                ))
+                .clicked()
+            {
+                proxy.move_exclusion_rules(&selected);
+                selection.clear();
+            }
+        }
+
+        ui.separator();
+
+        ui.menu_button("Sort", |ui| {
+            ui.label(
+                RichText::new("Rules are matched in order, the first match is used.")
+                    .color(Color32::GRAY)
+                    .size(11.),
+            );
+
+            for order in SORT_ORDERS {
+                if ui.button(order.to_string()).clicked() {
+                    proxy.sort_exclusion_list(order);
+                    selection.clear();
+                    ui.close_menu();
+                }
+            }
+        });
+
+        if !duplicates.is_empty()
+            && ui
+                .button(format!("Remove Duplicates ({})", duplicates.len()))
+                .on_hover_text(
+                    "Items that match the same requests as an earlier item are never used.",
+                )
+                .clicked()
+        {
+            proxy.remove_duplicate_rules();
+            selection.clear();
+        }
+
+        paste_menu(proxy, ui);
+
+        if ui
+            .button("Edit as Text")
+            .on_hover_text("Edit the whole list as text, one pattern per line.")
+            .clicked()
+        {
+            ui.data_mut(|data| {
+                data.insert_temp(
+                    raw_text_id(),
+                    RawTextEdit {
+                        text: exclusion_list
+                            .iter()
+                            .map(TrafficRule::to_line)
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        original: exclusion_list.clone(),
+                    },
+                )
+            });
+            selection.clear();
+        }
+    });
+}
+
+/// Shows the exclusion list as text while the raw text mode is on, returning whether it is.
+///
+/// The text is validated as it's edited, and only replaces the exclusion list once it's applied.
+pub fn raw_text_editor(proxy: &mut Proxy, ui: &mut egui::Ui) -> bool {
+    let Some(mut raw_text) = ui.data_mut(|data| data.get_temp::<RawTextEdit>(raw_text_id())) else {
+        return false;
+    };
+
+    let (rules, issues) = parse_rule_lines(&raw_text.text, &raw_text.original);
+    let has_errors = issues.iter().any(|issue| issue.error);
+    let mut close = false;
+
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new(
+                "One pattern per line. Prefix regular expressions with regex:, # starts a comment.",
+            )
+            .color(Color32::GRAY)
+            .size(11.),
+        );
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.button("Cancel").clicked() {
+                close = true;
+            }
+
+            if ui
+                .add_enabled(
+                    !has_errors,
+                    egui::Button::new(format!("Apply ({} rules)", rules.len())),
+                )
+                .clicked()
+            {
+                proxy.set_exclusion_list(rules.clone());
+                close = true;
+            }
+        });
+    });
+
+    egui::ScrollArea::vertical()
+        .id_source("raw_text_scrollarea")
+        .max_height(ui.available_height() / 2.)
+        .show(ui, |ui| {
+            ui.add(
+                TextEdit::multiline(&mut raw_text.text)
+                    .desired_rows(12)
+                    .desired_width(f32::INFINITY)
+                    .code_editor(),
+            );
+        });
+
+    for issue in issues.iter() {
+        let color = match issue.error {
+            true => Color32::LIGHT_RED,
+            false => Color32::YELLOW,
+        };
+        ui.label(
+            RichText::new(format!("Line {}: {}", issue.line, issue.message))
+                .color(color)
+                .size(11.),
+        );
+    }
+
+    ui.data_mut(|data| match close {
+        true => data.remove::<RawTextEdit>(raw_text_id()),
+        false => data.insert_temp(raw_text_id(), raw_text),
+    });
+
+    true
+}
+
+/// Shows the menu that adds many patterns to the exclusion list at once, one per line.
+fn paste_menu(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let paste_id = egui::Id::new("exclusion_paste");
+    let mut text = ui.data_mut(|data| data.get_temp::<String>(paste_id).unwrap_or_default());
+
+    ui.menu_button("Paste Lines", |ui| {
+        ui.add(
+            TextEdit::multiline(&mut text)
+                .hint_text("example.com\nregex:^https://ads\\.")
+                .desired_rows(6)
+                .desired_width(320.)
+                .code_editor(),
+        );
+
+        let existing = proxy.get_traffic_filter().get_filter_list();
+        let (rules, issues) = parse_rule_lines(&text, &[]);
+        let new_rules = rules
+            .iter()
+            .filter(|rule| !existing.iter().any(|item| item.is_duplicate_of(rule)))
+            .count();
+
+        for issue in issues.iter().filter(|issue| issue.error) {
+            ui.label(
+                RichText::new(format!("Line {}: {}", issue.line, issue.message))
+                    .color(Color32::LIGHT_RED)
+                    .size(11.),
+            );
+        }
+
+        if ui
+            .add_enabled(
+                new_rules > 0,
+                egui::Button::new(format!("Add {} new rules", new_rules)),
+            )
+            .on_hover_text("Lines already in the list, or with errors, are skipped.")
+            .clicked()
+        {
+            proxy.add_exclusion_rules(rules);
+            text.clear();
+            ui.close_menu();
+        }
+    });
+
+    ui.data_mut(|data| data.insert_temp(paste_id, text));
+}
+
+/// Returns the identifier the raw text mode is kept under in egui memory.
+fn raw_text_id() -> egui::Id {
+    egui::Id::new("exclusion_raw_text")
+}
@@ -0,0 +1,153 @@
+use super::{request_inspector::format_size, request_list::truncate_text};
+use crate::service::proxy::Proxy;
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the cache window open.
+pub const CACHE_EDITOR_ID: &str = "cache_editor";
+
+/// Shows the cache settings and stored responses in a separate window, while it's open.
+pub fn cache_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(CACHE_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut cache = proxy.get_settings().cache;
+    let previous = cache.clone();
+
+    egui::Window::new("Cache")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([760., 360.])
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(
+                    "Responses to plain HTTP GET requests are stored following their Cache-Control headers, and revalidated once stale.",
+                )
+                .size(11.)
+                .color(Color32::GRAY),
+            );
+            ui.add_space(4.);
+
+            egui::Grid::new("cache_settings_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Enabled:");
+                    ui.checkbox(&mut cache.enabled, "");
+                    ui.end_row();
+
+                    ui.label("Directory:");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TextEdit::singleline(&mut cache.directory)
+                                .hint_text(previous.directory().display().to_string())
+                                .desired_width(360.),
+                        );
+
+                        if ui.button("…").clicked() {
+                            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                cache.directory = path.display().to_string();
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Size Limit:");
+                    ui.add(
+                        egui::DragValue::new(&mut cache.max_size)
+                            .range(1..=1_048_576)
+                            .suffix(" MB"),
+                    )
+                    .on_hover_text("The least recently used responses are removed to stay below it.");
+                    ui.end_row();
+                });
+
+            ui.separator();
+
+            let stats = proxy.get_cache_stats();
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} responses, {} of {} MB",
+                    stats.entries,
+                    format_size(stats.size),
+                    cache.max_size
+                ));
+                ui.separator();
+                ui.label(RichText::new(format!("{} hits", stats.hits)).color(Color32::LIGHT_GREEN));
+                ui.label(format!("{} misses", stats.misses));
+                ui.label(
+                    RichText::new(format!("{} revalidated", stats.revalidated))
+                        .color(Color32::LIGHT_BLUE),
+                );
+            });
+
+            ui.add_space(4.);
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    let entries = proxy.get_cache_entries();
+
+                    egui::Grid::new("cache_entries_grid")
+                        .num_columns(6)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["URL", "Status", "Size", "Stored", "Freshness", ""] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            for entry in entries.iter() {
+                                ui.label(truncate_text(&entry.url, 60))
+                                    .on_hover_text(&entry.url);
+                                ui.label(entry.status.to_string());
+                                ui.label(format_size(entry.size));
+                                ui.label(entry.stored.format("%Y-%m-%d %H:%M:%S").to_string());
+
+                                match entry.is_fresh() {
+                                    true => ui.label(
+                                        RichText::new(format!(
+                                            "Fresh for {}s",
+                                            entry.lifetime - entry.current_age()
+                                        ))
+                                        .color(Color32::LIGHT_GREEN),
+                                    ),
+                                    false => ui.label(RichText::new("Stale").color(Color32::YELLOW)),
+                                }
+                                .on_hover_text(
+                                    "Stale responses are revalidated with the upstream server before they're used.",
+                                );
+
+                                if ui.button("Remove").clicked() {
+                                    proxy.remove_cache_entry(&entry.id);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if entries.is_empty() {
+                        ui.label("No responses have been cached.");
+                    }
+                });
+
+            ui.separator();
+
+            if ui
+                .add_enabled(stats.entries > 0, egui::Button::new("Clear Cache"))
+                .clicked()
+            {
+                proxy.clear_cache();
+            }
+        });
+
+    if cache != previous {
+        proxy.set_cache_settings(cache);
+    }
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
@@ -0,0 +1,179 @@
+use crate::service::proxy::Proxy;
+use eframe::{
+    egui::{self, Key, KeyboardShortcut, Modifiers, RichText},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the change log open.
+pub const CHANGE_LOG_ID: &str = "change_log";
+
+/// Undoes the last traffic filter change.
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+
+/// Redoes the last traffic filter change that was undone.
+const REDO_SHORTCUTS: [KeyboardShortcut; 2] = [
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z),
+    KeyboardShortcut::new(Modifiers::COMMAND, Key::Y),
+];
+
+/// Shows the Undo and Redo buttons of the traffic filter, with what each would change on hover.
+///
+/// Also handles their keyboard shortcuts, unless a text field has focus and needs them instead.
+pub fn undo_redo_buttons(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let (next_undo, next_redo) = proxy.get_undo_redo();
+
+    let (undo_pressed, redo_pressed) = match ui.ctx().wants_keyboard_input() {
+        true => (false, false),
+        false => ui.input_mut(|input| {
+            // The redo shortcut includes the undo one, so it has to be consumed first
+            let redo = REDO_SHORTCUTS
+                .iter()
+                .any(|shortcut| input.consume_shortcut(shortcut));
+            (input.consume_shortcut(&UNDO_SHORTCUT), redo)
+        }),
+    };
+
+    let redo = ui
+        .add_enabled(next_redo.is_some(), egui::Button::new("Redo"))
+        .on_hover_text(format!(
+            "Redo: {}\n{}",
+            next_redo.as_deref().unwrap_or("Nothing to redo"),
+            ui.ctx().format_shortcut(&REDO_SHORTCUTS[0])
+        ));
+    if redo.clicked() || (redo_pressed && next_redo.is_some()) {
+        proxy.redo_filter_change();
+    }
+
+    let undo = ui
+        .add_enabled(next_undo.is_some(), egui::Button::new("Undo"))
+        .on_hover_text(format!(
+            "Undo: {}\n{}",
+            next_undo.as_deref().unwrap_or("Nothing to undo"),
+            ui.ctx().format_shortcut(&UNDO_SHORTCUT)
+        ));
+    if undo.clicked() || (undo_pressed && next_undo.is_some()) {
+        proxy.undo_filter_change();
+    }
+}
+
+/// Shows the change log of the traffic filter in a separate window, while it's open.
+///
+/// Lists every change newest first, and restores the filter as it was right after any of them.
+pub fn change_log(proxy: &mut Proxy, ctx: &egui::Context) {
+    let window_id = egui::Id::new(CHANGE_LOG_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(window_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let changes = proxy.get_change_log();
+    let mut restore = None;
+    let mut clear = false;
+
+    egui::Window::new("Change Log")
+        .id(window_id)
+        .open(&mut open)
+        .default_size([760., 360.])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} changes", changes.len()));
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui
+                        .add_enabled(!changes.is_empty(), egui::Button::new("Clear Log"))
+                        .on_hover_text(
+                            "Remove every change from the log, keeping the undo history.",
+                        )
+                        .clicked()
+                    {
+                        clear = true;
+                    }
+
+                    undo_redo_buttons(proxy, ui);
+                });
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .id_source("change_log_scrollarea")
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    egui::Grid::new("change_log_grid")
+                        .num_columns(6)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["Time", "Author", "Change", "Before", "After", ""] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            // Changes stored without their diffs can't be gone back past
+                            let restorable = changes
+                                .iter()
+                                .rposition(|change| change.diff.is_none())
+                                .unwrap_or(0);
+
+                            for (index, change) in changes.iter().enumerate().rev() {
+                                ui.label(
+                                    RichText::new(
+                                        change.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                                    )
+                                    .color(Color32::GRAY)
+                                    .size(11.),
+                                );
+                                ui.label(&change.author);
+                                ui.label(&change.description);
+                                change_value(&change.before, Color32::LIGHT_RED, ui);
+                                change_value(&change.after, Color32::LIGHT_GREEN, ui);
+
+                                if ui
+                                    .add_enabled(index >= restorable, egui::Button::new("Restore"))
+                                    .on_hover_text(
+                                        "Go back to the traffic filter as it was right after this change.",
+                                    )
+                                    .on_disabled_hover_text(
+                                        "A later change was logged by an older version, or too long ago to keep what it did, so this one can't be gone back to.",
+                                    )
+                                    .clicked()
+                                {
+                                    restore = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if changes.is_empty() {
+                        ui.label("The traffic filter hasn't been changed yet.");
+                    }
+                });
+        });
+
+    if let Some(index) = restore {
+        proxy.restore_filter_change(index);
+    }
+
+    if clear {
+        proxy.clear_change_log();
+    }
+
+    ctx.memory_mut(|m| m.data.insert_temp(window_id, open));
+}
+
+/// Shows the before or after value of a change, truncated to fit its column.
+///
+/// # Arguments:
+/// * `value` - The value to show, empty when there's nothing to show.
+/// * `color` - The color to show the value in.
+/// * `ui` - The Ui to show the value in.
+fn change_value(value: &str, color: Color32, ui: &mut egui::Ui) {
+    if value.is_empty() {
+        ui.label(RichText::new("-").color(Color32::GRAY));
+        return;
+    }
+
+    ui.add_sized(
+        [180., 18.],
+        egui::Label::new(RichText::new(value).color(color).size(11.)).truncate(),
+    )
+    .on_hover_text(value);
+}
@@ -38,18 +38,24 @@ impl MainWindow {
                 Err(_) => TrafficFilter::default(),
             };
 
+            let settings = previous_values.proxy.get_settings();
+            let exceptions = previous_values.proxy.get_exception_requests();
+            let filter_history = previous_values.proxy.filter_history.lock().unwrap().clone();
             let log_level = previous_values.proxy.logger.level();
 
             // Create new proxy to generate mutables
-            return Self {
-                // TODO: Restore previous values before creating a default (misaligned MUTEX variables)
-                proxy: Proxy::new(
-                    previous_values.proxy.port,
-                    previous_values.proxy.view,
-                    traffic_filter,
-                    log_level,
-                ),
-            };
+            // TODO: Restore previous values before creating a default (misaligned MUTEX variables)
+            let proxy = Proxy::new(
+                previous_values.proxy.port,
+                previous_values.proxy.view,
+                traffic_filter,
+                settings,
+                log_level,
+            );
+            *proxy.exceptions.lock().unwrap() = exceptions;
+            *proxy.filter_history.lock().unwrap() = filter_history;
+
+            return Self { proxy };
         }
 
         Default::default()
@@ -64,9 +70,9 @@ impl eframe::App for MainWindow {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         match self.proxy.view {
             ProxyView::Min => {
-                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(250., 160.)))
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(250., 180.)))
             }
-            ProxyView::Logs | ProxyView::Filter => {
+            ProxyView::Logs | ProxyView::Filter | ProxyView::Stats => {
                 ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(egui::vec2(650., 500.)))
             }
         }
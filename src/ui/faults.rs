@@ -0,0 +1,224 @@
+use crate::service::{
+    fault::{FaultKind, FaultRule},
+    proxy::Proxy,
+    traffic_filter::TrafficRuleKind,
+};
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the fault rules editor open.
+pub const FAULT_RULES_EDITOR_ID: &str = "fault_rules_editor";
+
+/// Shows the fault rules in a separate window, while it's open.
+pub fn fault_rules_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(FAULT_RULES_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut faults = proxy.get_settings().faults;
+
+    egui::Window::new("Fault Injection")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([820., 300.])
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(
+                    "Faults are injected into a share of the plain HTTP requests matching a rule, and marked in the request log.",
+                )
+                .size(11.)
+                .color(Color32::GRAY),
+            );
+            ui.add_space(4.);
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    let mut removed = None;
+
+                    egui::Grid::new("fault_rules_grid")
+                        .num_columns(9)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in [
+                                "",
+                                "Type",
+                                "URL Pattern",
+                                "Fault",
+                                "",
+                                "Chance (%)",
+                                "Limit",
+                                "Injected",
+                                "",
+                            ] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            // Only the edited rule is written back, the Proxy keeps counting faults meanwhile
+                            for (index, rule) in faults.iter_mut().enumerate() {
+                                let previous = rule.clone();
+                                if fault_rule_row(index, rule, ui) {
+                                    proxy.reset_fault_count(index);
+                                } else if *rule != previous {
+                                    proxy.update_fault_rule(index, rule.clone());
+                                }
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if let Some(index) = removed {
+                        proxy.remove_fault_rule(index);
+                        faults.remove(index);
+                    }
+
+                    if faults.is_empty() {
+                        ui.label("No fault rules have been added.");
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Add Rule").clicked() {
+                    proxy.add_fault_rule(FaultRule::default());
+                }
+
+                let errors = faults
+                    .iter()
+                    .filter(|rule| rule.enabled && rule.validate().is_err())
+                    .count();
+                if errors > 0 {
+                    ui.label(
+                        RichText::new(format!("{} rule(s) are invalid and will be skipped.", errors))
+                            .color(Color32::LIGHT_RED),
+                    );
+                }
+            });
+        });
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
+
+/// Shows the editable fields of a single fault rule in the fault rules grid.
+///
+/// Returns whether the count of injected faults should be reset.
+fn fault_rule_row(index: usize, rule: &mut FaultRule, ui: &mut egui::Ui) -> bool {
+    ui.checkbox(&mut rule.enabled, "")
+        .on_hover_text("Enable or disable this rule.");
+
+    egui::ComboBox::from_id_source(("fault_rule_kind", index))
+        .selected_text(rule.kind.to_string())
+        .width(80.)
+        .show_ui(ui, |ui| {
+            for kind in [TrafficRuleKind::Contains, TrafficRuleKind::Regex] {
+                ui.selectable_value(&mut rule.kind, kind, kind.to_string());
+            }
+        });
+
+    let validation = rule.validate();
+    let pattern_response = ui.add(
+        TextEdit::singleline(&mut rule.pattern)
+            .hint_text("api.example.com/")
+            .desired_width(160.)
+            .text_color_opt(validation.is_err().then_some(Color32::LIGHT_RED)),
+    );
+    if let Err(error) = validation {
+        pattern_response.on_hover_text(error);
+    }
+
+    egui::ComboBox::from_id_source(("fault_rule_fault", index))
+        .selected_text(rule.fault.to_string())
+        .width(120.)
+        .show_ui(ui, |ui| {
+            for fault in [
+                FaultKind::Status,
+                FaultKind::Truncate,
+                FaultKind::Delay,
+                FaultKind::Close,
+            ] {
+                ui.selectable_value(&mut rule.fault, fault, fault.to_string());
+            }
+        });
+
+    ui.horizontal(|ui| match rule.fault {
+        FaultKind::Status => {
+            egui::ComboBox::from_id_source(("fault_rule_status", index))
+                .selected_text(rule.status.to_string())
+                .width(60.)
+                .show_ui(ui, |ui| {
+                    for status in [500, 502, 503, 504, 429] {
+                        ui.selectable_value(&mut rule.status, status, status.to_string());
+                    }
+                });
+
+            ui.label("Retry-After:");
+            ui.add(
+                egui::DragValue::new(&mut rule.retry_after)
+                    .range(0..=86_400)
+                    .suffix("s"),
+            )
+            .on_hover_text("0 leaves the Retry-After header out.");
+        }
+        FaultKind::Truncate | FaultKind::Close => {
+            ui.label("After:");
+            ui.add(
+                egui::DragValue::new(&mut rule.bytes)
+                    .range(0..=u32::MAX as u64)
+                    .suffix(" bytes"),
+            );
+        }
+        FaultKind::Delay => {
+            ui.label("By:");
+            ui.add(
+                egui::DragValue::new(&mut rule.delay)
+                    .range(0..=600_000)
+                    .suffix("ms"),
+            );
+        }
+    });
+
+    ui.add(egui::DragValue::new(&mut rule.percentage).range(0..=100));
+
+    ui.horizontal(|ui| {
+        let mut limited = rule.limit.is_some();
+        if ui
+            .checkbox(&mut limited, "")
+            .on_hover_text("Stop injecting faults after a number of them.")
+            .changed()
+        {
+            rule.limit = limited.then_some(10);
+        }
+
+        if let Some(limit) = rule.limit.as_mut() {
+            ui.add(egui::DragValue::new(limit).range(1..=u32::MAX));
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let exhausted = rule.exhausted();
+        ui.label(
+            RichText::new(rule.injected.to_string()).color(match exhausted {
+                true => Color32::YELLOW,
+                false => Color32::LIGHT_GREEN,
+            }),
+        )
+        .on_hover_text(match exhausted {
+            true => "The limit has been reached, reset the count to inject more faults.",
+            false => "The number of faults injected by this rule.",
+        });
+
+        ui.add_enabled(rule.injected > 0, egui::Button::new("Reset").small())
+            .clicked()
+    })
+    .inner
+}
@@ -0,0 +1,149 @@
+use crate::service::{
+    header_rules::{HeaderDirection, HeaderOperation, HeaderRule},
+    proxy::Proxy,
+};
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the header rules editor open.
+pub const HEADER_RULES_EDITOR_ID: &str = "header_rules_editor";
+
+/// Shows the header rules in a separate window, while it's open.
+pub fn header_rules_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(HEADER_RULES_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut header_rules = proxy.get_settings().header_rules;
+    let previous = header_rules.clone();
+
+    egui::Window::new("Header Rules")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([620., 300.])
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(
+                    "Rules apply to plain HTTP requests, HTTPS tunnels are passed through unchanged.",
+                )
+                .size(11.)
+                .color(Color32::GRAY),
+            );
+            ui.add_space(4.);
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    let mut removed = None;
+
+                    egui::Grid::new("header_rules_grid")
+                        .num_columns(7)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["", "Host", "Direction", "Operation", "Header", "Value", ""]
+                            {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            for (index, rule) in header_rules.iter_mut().enumerate() {
+                                header_rule_row(index, rule, ui);
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if let Some(index) = removed {
+                        header_rules.remove(index);
+                    }
+
+                    if header_rules.is_empty() {
+                        ui.label("No header rules have been added.");
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Add Rule").clicked() {
+                    header_rules.push(HeaderRule::default());
+                }
+
+                let errors = header_rules
+                    .iter()
+                    .filter(|rule| rule.enabled && rule.validate().is_err())
+                    .count();
+                if errors > 0 {
+                    ui.label(
+                        RichText::new(format!("{} rule(s) are invalid and will be skipped.", errors))
+                            .color(Color32::LIGHT_RED),
+                    );
+                }
+            });
+        });
+
+    if header_rules != previous {
+        proxy.set_header_rules(header_rules);
+    }
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
+
+/// Shows the editable fields of a single header rule in the header rules grid.
+fn header_rule_row(index: usize, rule: &mut HeaderRule, ui: &mut egui::Ui) {
+    ui.checkbox(&mut rule.enabled, "")
+        .on_hover_text("Enable or disable this rule.");
+
+    ui.add(
+        TextEdit::singleline(&mut rule.host)
+            .hint_text("*.example.com")
+            .desired_width(120.),
+    )
+    .on_hover_text("example.com, *.example.com for it and its subdomains, or * for every host.");
+
+    egui::ComboBox::from_id_source(("header_rule_direction", index))
+        .selected_text(rule.direction.to_string())
+        .width(80.)
+        .show_ui(ui, |ui| {
+            for direction in [HeaderDirection::Request, HeaderDirection::Response] {
+                ui.selectable_value(&mut rule.direction, direction, direction.to_string());
+            }
+        });
+
+    egui::ComboBox::from_id_source(("header_rule_operation", index))
+        .selected_text(rule.operation.to_string())
+        .width(70.)
+        .show_ui(ui, |ui| {
+            for operation in [
+                HeaderOperation::Set,
+                HeaderOperation::Add,
+                HeaderOperation::Remove,
+            ] {
+                ui.selectable_value(&mut rule.operation, operation, operation.to_string());
+            }
+        });
+
+    let validation = rule.validate();
+    let name_response = ui.add(
+        TextEdit::singleline(&mut rule.name)
+            .hint_text("User-Agent")
+            .desired_width(110.)
+            .text_color_opt(validation.is_err().then_some(Color32::LIGHT_RED)),
+    );
+    if let Err(error) = validation {
+        name_response.on_hover_text(error);
+    }
+
+    ui.add_enabled(
+        rule.operation != HeaderOperation::Remove,
+        TextEdit::singleline(&mut rule.value).desired_width(150.),
+    );
+}
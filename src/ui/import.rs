@@ -0,0 +1,367 @@
+use crate::service::{
+    import::{
+        import_outcomes, parse_import, ImportFormat, ImportMode, ImportOutcome, ImportedList,
+        ImportedRule,
+    },
+    proxy::{Proxy, ProxyView},
+    traffic_filter::TrafficRuleKind,
+};
+use eframe::{
+    egui::{self, Align2, FontId, Id, LayerId, Order, RichText},
+    epaint::Color32,
+};
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+/// The identifier, in egui memory, of the files waiting to be imported.
+const IMPORT_PREVIEW_ID: &str = "import_preview";
+
+/// The most rows listed in the report of a file, the rest are only counted.
+const ISSUE_LIST_LIMIT: usize = 50;
+
+/// The items read from an imported file along with the report of its rows, or why it couldn't be read.
+type ParsedImport = Result<ImportedList, String>;
+
+/// A file waiting to be imported, along with the format it's read as.
+#[derive(Clone)]
+struct ImportFile {
+    name: String,
+    content: Arc<String>,
+    format: ImportFormat,
+    parsed: Arc<ParsedImport>,
+}
+
+/// The files waiting to be imported into the current exclusion list, kept in egui memory while they're previewed.
+#[derive(Clone, Default)]
+struct PendingImport {
+    files: Vec<ImportFile>,
+    mode: ImportMode,
+}
+
+/// Reads files to import into the current exclusion list, detecting their format and opening the import preview.
+///
+/// # Arguments:
+/// * `proxy` - The Proxy whose exclusion list the files are imported into.
+/// * `paths` - The paths of the files to import.
+/// * `ctx` - The egui Context the preview is shown in.
+pub fn open_import(proxy: &mut Proxy, paths: Vec<PathBuf>, ctx: &egui::Context) {
+    let filter_type = proxy.get_traffic_filter().get_filter_type();
+    let mut pending = ctx.memory_mut(|m| {
+        m.data
+            .get_temp::<PendingImport>(Id::new(IMPORT_PREVIEW_ID))
+            .unwrap_or_default()
+    });
+
+    for path in paths {
+        let content = match std::fs::read(&path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(error) => {
+                let message = format!("Couldn't read {} -> {}", path.display(), error);
+                proxy.logger.error(&message);
+                continue;
+            }
+        };
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        let format = ImportFormat::detect(&name, &content);
+
+        pending.files.push(ImportFile {
+            parsed: Arc::new(parse_import(&content, format, filter_type)),
+            name,
+            content: Arc::new(content),
+            format,
+        });
+    }
+
+    if !pending.files.is_empty() {
+        ctx.memory_mut(|m| m.data.insert_temp(Id::new(IMPORT_PREVIEW_ID), pending));
+    }
+}
+
+/// Accepts files dropped onto the window, showing where they'll go while they're dragged over it.
+pub fn file_drop(proxy: &mut Proxy, ctx: &egui::Context) {
+    let (hovering, dropped) = ctx.input(|input| {
+        (
+            !input.raw.hovered_files.is_empty(),
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect::<Vec<_>>(),
+        )
+    });
+
+    if hovering {
+        let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("file_drop")));
+        let screen_rect = ctx.screen_rect();
+
+        painter.rect_filled(screen_rect, 0., Color32::from_black_alpha(192));
+        painter.text(
+            screen_rect.center(),
+            Align2::CENTER_CENTER,
+            format!(
+                "Drop to import into the {}\nCSV, hosts files, blocklists, HAR captures and config exports",
+                proxy.get_traffic_filter().get_profile_name()
+            ),
+            FontId::proportional(16.),
+            Color32::WHITE,
+        );
+    }
+
+    if !dropped.is_empty() {
+        // The preview doesn't fit the minimised window
+        if proxy.view == ProxyView::Min {
+            proxy.view = ProxyView::Filter;
+        }
+
+        open_import(proxy, dropped, ctx);
+    }
+}
+
+/// Shows the files waiting to be imported, with what each would change, until they're imported or cancelled.
+pub fn import_preview(proxy: &mut Proxy, ctx: &egui::Context) {
+    let preview_id = Id::new(IMPORT_PREVIEW_ID);
+    let Some(mut pending) = ctx.memory_mut(|m| m.data.get_temp::<PendingImport>(preview_id)) else {
+        return;
+    };
+
+    let traffic_filter = proxy.get_traffic_filter();
+    let existing = traffic_filter.get_filter_list();
+
+    // Items found in several of the files are only previewed, and imported, once
+    let parsed = pending
+        .files
+        .iter()
+        .map(|file| Arc::clone(&file.parsed))
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let rules = parsed
+        .iter()
+        .filter_map(|parsed| parsed.as_ref().as_ref().ok())
+        .flat_map(|imported| {
+            imported.rules.iter().map(|rule| ImportedRule {
+                rule: rule.clone(),
+                patterns_only: imported.patterns_only,
+            })
+        })
+        .filter(|item| seen.insert((item.rule.pattern.clone(), item.rule.kind)))
+        .collect::<Vec<_>>();
+    let outcomes = import_outcomes(&existing, &rules);
+    let count = |outcome| outcomes.iter().filter(|item| **item == outcome).count();
+    let (new_rules, updated_rules) = (count(ImportOutcome::New), count(ImportOutcome::Update));
+
+    let mut open = true;
+    let mut import = false;
+    let mut removed = None;
+    let filter_type = traffic_filter.get_filter_type();
+
+    egui::Window::new("Import")
+        .id(preview_id)
+        .open(&mut open)
+        .default_size([620., 440.])
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "Importing into the {}, which has {} items.",
+                traffic_filter.get_profile_name(),
+                existing.len()
+            ));
+            ui.separator();
+
+            for (index, file) in pending.files.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(egui::Label::new(RichText::new(&file.name).strong()).truncate());
+
+                    let previous = file.format;
+                    egui::ComboBox::from_id_source(("import_format", index))
+                        .selected_text(file.format.to_string())
+                        .show_ui(ui, |ui| {
+                            for format in ImportFormat::ALL {
+                                ui.selectable_value(&mut file.format, format, format.to_string());
+                            }
+                        });
+
+                    if file.format != previous {
+                        file.parsed =
+                            Arc::new(parse_import(&file.content, file.format, filter_type));
+                    }
+
+                    match file.parsed.as_ref() {
+                        Ok(imported) => ui.label(format!("{} items", imported.rules.len())),
+                        Err(error) => ui.label(RichText::new(error).color(Color32::LIGHT_RED)),
+                    };
+
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                });
+
+                if let Ok(imported) = file.parsed.as_ref() {
+                    import_report(&file.name, imported, ui);
+                }
+            }
+
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .id_source("import_preview_scrollarea")
+                .auto_shrink([false, false])
+                .max_height(ui.available_height() - 60.)
+                .show_rows(ui, 18., rules.len(), |ui, row_range| {
+                    for index in row_range {
+                        let rule = &rules[index].rule;
+
+                        ui.horizontal(|ui| {
+                            let (outcome, color) = match (outcomes[index], pending.mode) {
+                                (ImportOutcome::New, _) => ("New", Color32::LIGHT_GREEN),
+                                (ImportOutcome::Update, ImportMode::Merge) => {
+                                    ("Update", Color32::LIGHT_BLUE)
+                                }
+                                (ImportOutcome::Update | ImportOutcome::Unchanged, _) => {
+                                    ("In list", Color32::GRAY)
+                                }
+                            };
+                            ui.label(RichText::new(outcome).color(color).size(11.));
+
+                            if rule.kind == TrafficRuleKind::Regex {
+                                ui.label(RichText::new("Regex").color(Color32::GRAY).size(11.));
+                            }
+
+                            ui.add(egui::Label::new(&rule.pattern).truncate());
+                        });
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                for (mode, hover_text) in [
+                    (
+                        ImportMode::Merge,
+                        "Add the new items, and update the items already in the list with the imported settings.",
+                    ),
+                    (
+                        ImportMode::AddNew,
+                        "Add the new items, leaving the items already in the list as they are.",
+                    ),
+                    (
+                        ImportMode::Replace,
+                        "Replace every item in the list with the imported items.",
+                    ),
+                ] {
+                    ui.radio_value(&mut pending.mode, mode, mode.to_string())
+                        .on_hover_text(hover_text);
+                }
+
+                let summary = match pending.mode {
+                    ImportMode::Merge => format!(
+                        "{} new items will be added, {} updated.",
+                        new_rules, updated_rules
+                    ),
+                    ImportMode::AddNew => format!("{} new items will be added.", new_rules),
+                    ImportMode::Replace => format!(
+                        "{} items will replace the {} in the list.",
+                        rules.len(),
+                        existing.len()
+                    ),
+                };
+                ui.label(RichText::new(summary).color(Color32::GRAY).size(11.));
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let enabled = match pending.mode {
+                        ImportMode::Merge => new_rules + updated_rules > 0,
+                        ImportMode::AddNew => new_rules > 0,
+                        ImportMode::Replace => !rules.is_empty(),
+                    };
+
+                    if ui
+                        .add_enabled(enabled, egui::Button::new("Import"))
+                        .clicked()
+                    {
+                        import = true;
+                    }
+                });
+            });
+        });
+
+    if import {
+        let source = pending
+            .files
+            .iter()
+            .map(|file| file.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        proxy.import_exclusion_list(&source, rules, pending.mode);
+    }
+
+    if let Some(index) = removed {
+        pending.files.remove(index);
+    }
+
+    ctx.memory_mut(|m| match open && !import && !pending.files.is_empty() {
+        true => m.data.insert_temp(preview_id, pending),
+        false => m.data.remove::<PendingImport>(preview_id),
+    });
+}
+
+/// Shows the validation report of an imported file, listing the rows that were skipped or are worth a warning.
+///
+/// # Arguments:
+/// * `name` - The file name of the imported file.
+/// * `imported` - The items read from the file, along with the report of its rows.
+/// * `ui` - The Ui to show the report in.
+fn import_report(name: &str, imported: &ImportedList, ui: &mut egui::Ui) {
+    let skipped = imported.issues.iter().filter(|issue| issue.error).count();
+    let summary = format!(
+        "{} rows skipped, {} duplicates removed, {} normalized",
+        skipped, imported.duplicates, imported.normalized
+    );
+
+    if imported.issues.is_empty() {
+        ui.label(RichText::new(summary).color(Color32::GRAY).size(11.))
+            .on_hover_text(
+                "URLs have their scheme and a trailing slash removed, and their host lowercased, as do host names.",
+            );
+        return;
+    }
+
+    egui::CollapsingHeader::new(RichText::new(summary).color(Color32::YELLOW).size(11.))
+        .id_source(("import_report", name))
+        .show(ui, |ui| {
+            let report = imported
+                .issues
+                .iter()
+                .map(|issue| format!("Row {}: {}", issue.line, issue.message))
+                .collect::<Vec<_>>();
+
+            if ui
+                .small_button("Copy Report")
+                .on_hover_text("Copy every row of the report, to fix the file with.")
+                .clicked()
+            {
+                ui.output_mut(|output| output.copied_text = report.join("\n"));
+            }
+
+            for (issue, line) in imported
+                .issues
+                .iter()
+                .zip(report.iter())
+                .take(ISSUE_LIST_LIMIT)
+            {
+                let color = match issue.error {
+                    true => Color32::LIGHT_RED,
+                    false => Color32::YELLOW,
+                };
+                ui.label(RichText::new(line).color(color).size(11.));
+            }
+
+            if report.len() > ISSUE_LIST_LIMIT {
+                ui.label(format!("and {} more...", report.len() - ISSUE_LIST_LIMIT));
+            }
+        });
+}
@@ -1,14 +1,35 @@
-use super::custom_widgets::toggle_ui;
+use super::{
+    block_page::{block_page_editor, exception_queue, BLOCK_PAGE_EDITOR_ID},
+    bulk_edit::{bulk_edit_toolbar, raw_text_editor, ExclusionSelection},
+    cache::{cache_editor, CACHE_EDITOR_ID},
+    change_log::{change_log, undo_redo_buttons, CHANGE_LOG_ID},
+    custom_widgets::toggle_ui,
+    faults::{fault_rules_editor, FAULT_RULES_EDITOR_ID},
+    header_rules::{header_rules_editor, HEADER_RULES_EDITOR_ID},
+    import::{file_drop, import_preview, open_import},
+    map_local::{map_local_editor, MAP_LOCAL_EDITOR_ID},
+    rate_limits::{rate_limit_usage, rate_limits_editor, RATE_LIMITS_EDITOR_ID},
+    request_inspector::request_inspector,
+    request_list::{request_list, WOULD_BLOCK_COLOR},
+    rule_hits::{rule_cleanup, rule_hits_columns, RULE_CLEANUP_ID},
+    rule_tester::{rule_impact, rule_tester, RULE_TESTER_ID},
+    schedule::{schedule_editor, schedule_label},
+    snooze::temporary_allow_list,
+    stats_panel::stats_panel,
+    throttle::{throttle_editor, throttle_switch, THROTTLE_EDITOR_ID},
+};
 use crate::service::{
+    pool::ProxyPoolStats,
     proxy::{
         Proxy, ProxyEvent, ProxyExclusionRow, ProxyExclusionUpdateKind, ProxyRequestLog, ProxyView,
     },
-    traffic_filter::TrafficFilterType,
-};
-use crate::utils::{
-    csv_handler::{read_from_csv, write_csv_from_vec},
-    logger::LogLevel,
+    settings::ProxyHttpVersion,
+    timeout::ProxyTimeout,
+    traffic_filter::{
+        TrafficFilterType, TrafficRule, TrafficRuleAction, TrafficRuleKind, TrafficRuleRecord,
+    },
 };
+use crate::utils::{csv_handler::write_csv_from_vec, logger::LogLevel};
 use colored::Colorize;
 use eframe::{
     egui::{self, vec2, CentralPanel, Layout, RichText, TextEdit},
@@ -34,6 +55,9 @@ pub fn main_body(proxy: &mut Proxy, ui: &mut egui::Ui) {
                 main_panel(proxy, ui);
             });
         });
+
+    file_drop(proxy, ui.ctx());
+    import_preview(proxy, ui.ctx());
 }
 
 struct StartAvailable {
@@ -45,9 +69,9 @@ struct StartAvailable {
 fn check_startup_capability(port: &String) -> StartAvailable {
     let mut error: Option<String> = None;
 
-    if port.len() > 5 || port.len() < 1 {
+    if port.len() > 5 || port.is_empty() {
         error = Some(String::from("Invalid Port Length."))
-    } else if let Err(_) = port.trim().parse::<u16>() {
+    } else if port.trim().parse::<u16>().is_err() {
         error = Some(String::from("Invalid Characters in Port."))
     } else if port == "0" {
         error = Some(String::from("Port cannot be 0."))
@@ -88,26 +112,32 @@ fn control_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
                         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
                             ui.add(egui::Label::new("Proxy Events: "));
                             ui.add(egui::Label::new(
-                                RichText::new(format!("{}", proxy.get_requests().len()))
+                                RichText::new(format!("{}", proxy.get_request_count()))
                                     .color(Color32::LIGHT_GREEN),
                             ));
                         });
 
+                        let pool_stats = proxy.get_pool_stats();
+                        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                            ui.add(egui::Label::new("Connections: "));
+                            ui.add(egui::Label::new(
+                                RichText::new(format!(
+                                    "{} active, {} idle",
+                                    pool_stats.active, pool_stats.idle
+                                ))
+                                .color(Color32::LIGHT_GREEN),
+                            ))
+                            .on_hover_ui(|ui| pool_stats_tooltip(&pool_stats, ui));
+                        });
+
+                        throttle_switch(proxy, ui);
+
                         if proxy.get_traffic_filter().get_enabled() {
                             ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
                                 ui.add(egui::Label::new("Events Blocked: "));
                                 ui.add(egui::Label::new(
-                                    RichText::new(format!(
-                                        "{}",
-                                        proxy
-                                            .get_requests()
-                                            .iter()
-                                            .filter(|proxy_request_item| proxy_request_item.blocked
-                                                != false)
-                                            .collect::<Vec<_>>()
-                                            .len()
-                                    ))
-                                    .color(Color32::LIGHT_GREEN),
+                                    RichText::new(format!("{}", proxy.get_blocked_request_count()))
+                                        .color(Color32::LIGHT_GREEN),
                                 ));
                             });
 
@@ -136,7 +166,7 @@ fn control_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
 
                         let startup = check_startup_capability(&proxy.port);
                         proxy.start_enabled = startup.allowed;
-                        proxy.port_error = startup.error.unwrap_or(String::default());
+                        proxy.port_error = startup.error.unwrap_or_default();
                     }
                     ProxyEvent::Terminating => {
                         proxy.start_enabled = false;
@@ -179,7 +209,7 @@ fn control_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
                             #[cfg(target_os = "windows")]
                             match proxy.view {
                                 ProxyView::Min => {}
-                                ProxyView::Logs | ProxyView::Filter => {
+                                ProxyView::Logs | ProxyView::Filter | ProxyView::Stats => {
                                     ui.ctx().send_viewport_cmd(egui::ViewportCommand::InnerSize(
                                         egui::vec2(650., 500.),
                                     ));
@@ -235,6 +265,33 @@ fn control_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
     );
 }
 
+/// Shows the upstream connection pool statistics.
+fn pool_stats_tooltip(stats: &ProxyPoolStats, ui: &mut egui::Ui) {
+    egui::Grid::new("pool_stats").num_columns(2).show(ui, |ui| {
+        for (name, value) in [
+            ("Opened:", stats.opened),
+            ("Reused:", stats.reused),
+            ("Expired:", stats.expired),
+            ("Failed Health Checks:", stats.unhealthy),
+        ] {
+            ui.label(name);
+            ui.label(RichText::new(value.to_string()).color(Color32::LIGHT_BLUE));
+            ui.end_row();
+        }
+    });
+
+    if !stats.hosts.is_empty() {
+        ui.separator();
+        egui::Grid::new("pool_hosts").num_columns(2).show(ui, |ui| {
+            for host in &stats.hosts {
+                ui.label(&host.host);
+                ui.label(format!("{} active, {} idle", host.active, host.idle));
+                ui.end_row();
+            }
+        });
+    }
+}
+
 // Right side panel
 fn main_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
     if proxy.view != ProxyView::Min {
@@ -242,10 +299,11 @@ fn main_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
             let mut current_value = proxy.view.clone();
             egui::ComboBox::new("view_options", "Select the View")
                 .truncate()
-                .selected_text(format!("{}", current_value.to_string()))
+                .selected_text(current_value.to_string())
                 .show_ui(ui, |ui| {
                     ui.selectable_value(&mut current_value, ProxyView::Logs, "Log View");
                     ui.selectable_value(&mut current_value, ProxyView::Filter, "Filter View");
+                    ui.selectable_value(&mut current_value, ProxyView::Stats, "Stats View");
                 });
             proxy.view = current_value;
 
@@ -256,6 +314,7 @@ fn main_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
             match proxy.view {
                 ProxyView::Logs => logs_panel(proxy, ui),
                 ProxyView::Filter => filter_panel(proxy, ui),
+                ProxyView::Stats => stats_panel(proxy, ui),
                 _ => {}
             }
         });
@@ -280,29 +339,197 @@ fn filter_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
 
             ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
                 ui.menu_button("Options", |ui| {
-                    if ui.button("Import Exclusion List").clicked() {
-                        if let Some(path) = rfd::FileDialog::new().pick_file() {
-                            match read_from_csv::<String, PathBuf>(path) {
-                                Ok(list) => {
-                                    proxy.set_exclusion_list(list);
+                    let mut capture_bodies = proxy.get_settings().capture_bodies;
+                    if ui
+                        .checkbox(&mut capture_bodies, "Capture Request Bodies")
+                        .clicked()
+                    {
+                        proxy.toggle_body_capture();
+                    }
+
+                    ui.menu_button("Connection Pool", |ui| {
+                        let settings = proxy.get_settings();
+                        let mut idle_timeout = settings.pool_idle_timeout;
+                        let mut max_connections = settings.pool_max_connections;
+
+                        let idle_changed = ui
+                            .horizontal(|ui| {
+                                ui.label("Idle Timeout:");
+                                ui.add(
+                                    egui::DragValue::new(&mut idle_timeout)
+                                        .range(1..=3600)
+                                        .suffix("s"),
+                                )
+                                .changed()
+                            })
+                            .inner;
+                        let max_changed = ui
+                            .horizontal(|ui| {
+                                ui.label("Max Connections per Host:");
+                                ui.add(egui::DragValue::new(&mut max_connections).range(1..=64))
+                                    .changed()
+                            })
+                            .inner;
+
+                        if idle_changed || max_changed {
+                            proxy.set_pool_limits(idle_timeout, max_connections);
+                        }
+                    });
+
+                    ui.menu_button("Timeouts", |ui| {
+                        let timeouts = proxy.get_settings().timeouts;
+
+                        egui::Grid::new("timeout_settings")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for timeout in [
+                                    ProxyTimeout::Connect,
+                                    ProxyTimeout::RequestHeaders,
+                                    ProxyTimeout::Response,
+                                    ProxyTimeout::Idle,
+                                    ProxyTimeout::TunnelIdle,
+                                    ProxyTimeout::TunnelLifetime,
+                                ] {
+                                    let mut seconds =
+                                        timeouts.get(timeout).unwrap_or_default().as_secs();
+
+                                    ui.label(format!("{}:", timeout));
+                                    if ui
+                                        .add(
+                                            egui::DragValue::new(&mut seconds)
+                                                .range(0..=86400)
+                                                .suffix("s"),
+                                        )
+                                        .on_hover_text(match timeout {
+                                            ProxyTimeout::Idle => "How long a client connection may wait between requests, tunnels aren't affected.\nSet to 0 to disable the timeout.",
+                                            ProxyTimeout::TunnelIdle => "How long a tunnel may go without traffic either way, websockets usually send pings well within it.\nSet to 0 to disable the timeout.",
+                                            ProxyTimeout::TunnelLifetime => "How long a tunnel may stay open, e.g. for a websocket.\nSet to 0 to disable the timeout.",
+                                            _ => "Set to 0 to disable the timeout.",
+                                        })
+                                        .changed()
+                                    {
+                                        proxy.set_timeout(timeout, seconds);
+                                    }
+                                    ui.end_row();
                                 }
-                                Err(error) => proxy.logger.error(&error.to_string()),
+                            });
+                    });
+
+                    ui.menu_button("Upstream HTTP Version", |ui| {
+                        let current = proxy.get_settings().http_version;
+                        for http_version in [
+                            ProxyHttpVersion::Auto,
+                            ProxyHttpVersion::Http1,
+                            ProxyHttpVersion::Http2,
+                        ] {
+                            if ui
+                                .radio(current == http_version, http_version.to_string())
+                                .clicked()
+                            {
+                                proxy.set_http_version(http_version);
+                                ui.close_menu();
                             }
                         }
+                    })
+                    .response
+                    .on_hover_text("Auto uses HTTP/2 when the client did, otherwise HTTP/1.1.");
+
+                    if ui.button("Header Rules").clicked() {
+                        ui.memory_mut(|m| {
+                            m.data
+                                .insert_temp(egui::Id::new(HEADER_RULES_EDITOR_ID), true)
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Network Conditions").clicked() {
+                        ui.memory_mut(|m| {
+                            m.data.insert_temp(egui::Id::new(THROTTLE_EDITOR_ID), true)
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Fault Injection").clicked() {
+                        ui.memory_mut(|m| {
+                            m.data
+                                .insert_temp(egui::Id::new(FAULT_RULES_EDITOR_ID), true)
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Rate Limits").clicked() {
+                        ui.memory_mut(|m| {
+                            m.data
+                                .insert_temp(egui::Id::new(RATE_LIMITS_EDITOR_ID), true)
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Cache").clicked() {
+                        ui.memory_mut(|m| m.data.insert_temp(egui::Id::new(CACHE_EDITOR_ID), true));
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Map Local").clicked() {
+                        ui.memory_mut(|m| {
+                            m.data.insert_temp(egui::Id::new(MAP_LOCAL_EDITOR_ID), true)
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Block Page").clicked() {
+                        ui.memory_mut(|m| {
+                            m.data
+                                .insert_temp(egui::Id::new(BLOCK_PAGE_EDITOR_ID), true)
+                        });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Rule Tester").clicked() {
+                        ui.memory_mut(|m| m.data.insert_temp(egui::Id::new(RULE_TESTER_ID), true));
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Unused Rules").clicked() {
+                        ui.memory_mut(|m| m.data.insert_temp(egui::Id::new(RULE_CLEANUP_ID), true));
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Change Log").clicked() {
+                        ui.memory_mut(|m| m.data.insert_temp(egui::Id::new(CHANGE_LOG_ID), true));
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .button("Import Exclusion List")
+                        .on_hover_text("Files can also be dropped onto the window.")
+                        .clicked()
+                    {
+                        if let Some(paths) = rfd::FileDialog::new().pick_files() {
+                            open_import(proxy, paths, ui.ctx());
+                        }
+                        ui.close_menu();
                     }
 
                     if ui.button("Export Exclusion List").clicked() {
                         if let Some(path) = rfd::FileDialog::new().save_file() {
-                            match write_csv_from_vec::<String, PathBuf>(
+                            match write_csv_from_vec::<TrafficRuleRecord, PathBuf>(
                                 path.clone(),
-                                vec!["REQUEST"],
-                                proxy.get_traffic_filter().get_filter_list(),
+                                TrafficRuleRecord::HEADERS.to_vec(),
+                                proxy
+                                    .get_traffic_filter()
+                                    .get_filter_list()
+                                    .into_iter()
+                                    .map(TrafficRuleRecord::from)
+                                    .collect(),
                             ) {
                                 Ok(_) => {
                                     let message = format!(
                                         "{} -> {}",
                                         "Exported Exclusions to file",
-                                        path.display().to_string()
+                                        path.display()
                                     );
                                     proxy.logger.debug(&message);
                                 }
@@ -318,6 +545,42 @@ fn filter_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
                         }
                     }
 
+                    if ui
+                        .button("Export Filter Config")
+                        .on_hover_text("Save both exclusion lists and their settings as JSON.")
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("JSON", &["json"])
+                            .save_file()
+                        {
+                            let config = serde_json::to_string_pretty(&proxy.get_traffic_filter())
+                                .map_err(|error| error.to_string())
+                                .and_then(|config| {
+                                    std::fs::write(&path, config).map_err(|error| error.to_string())
+                                });
+
+                            match config {
+                                Ok(_) => {
+                                    let message = format!(
+                                        "{} -> {}",
+                                        "Exported Filter Config to file",
+                                        path.display()
+                                    );
+                                    proxy.logger.debug(&message);
+                                }
+                                Err(error) => {
+                                    let message = format!(
+                                        "{} -> {}",
+                                        "There was an error during the export".red(),
+                                        error.red()
+                                    );
+                                    proxy.logger.debug(&message);
+                                }
+                            }
+                        }
+                    }
+
                     if ui.button("Export Request List").clicked() {
                         if let Some(path) = rfd::FileDialog::new().save_file() {
                             match write_csv_from_vec::<ProxyRequestLog, PathBuf>(
@@ -336,8 +599,7 @@ fn filter_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
                                 Err(error) => {
                                     let message = format!(
                                         "{} -> {}",
-                                        "There was an error during the export",
-                                        error.to_string()
+                                        "There was an error during the export", error
                                     );
                                     proxy.logger.debug(&message);
                                 }
@@ -359,203 +621,158 @@ fn filter_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
                     proxy.switch_exclusion_list();
                 }
                 ui.label("Allow Incoming");
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    let mut schedule = proxy.get_traffic_filter().get_schedule();
+                    let previous = schedule.clone();
+
+                    ui.menu_button("Schedule", |ui| {
+                        ui.label(
+                            RichText::new(format!(
+                                "The {} only filters traffic while its schedule is active.",
+                                proxy.get_traffic_filter().get_profile_name()
+                            ))
+                            .size(11.)
+                            .color(Color32::GRAY),
+                        );
+                        schedule_editor("profile_schedule", &mut schedule, ui);
+                    });
+
+                    if let Some(schedule) = &schedule {
+                        schedule_label(schedule, ui);
+                    }
+
+                    if schedule != previous {
+                        proxy.set_filter_schedule(schedule);
+                    }
+
+                    let mut audit = proxy.get_traffic_filter().get_audit();
+                    if ui
+                        .checkbox(&mut audit, "Audit")
+                        .on_hover_text(format!(
+                            "Only record the requests the {} would block, and forward them anyway.",
+                            proxy.get_traffic_filter().get_profile_name()
+                        ))
+                        .changed()
+                    {
+                        proxy.set_filter_audit(audit);
+                    }
+
+                    undo_redo_buttons(proxy, ui);
+                });
             });
 
-            egui::CollapsingHeader::new(format!(
-                "{} List",
-                proxy
-                    .get_traffic_filter()
-                    .get_opposing_filter_type()
-                    .to_string()
-            ))
-            .default_open(false)
-            .show_unindented(ui, |ui| {
-                ui.group(|ui| {
-                    ui.push_id("request_exclusion_list_scrollarea", |ui| {
-                        let exclusion_list = proxy.get_traffic_filter().get_filter_list();
-                        let num_rows = exclusion_list.len();
-
-                        egui::ScrollArea::new([true, true])
-                            .auto_shrink([false, false])
-                            .max_height(if request_logs_open {
-                                ui.available_height() / 3.
-                            } else {
-                                ui.available_height() - 20.
-                            })
-                            .show_rows(ui, 18.0, num_rows, |ui, row_range| {
-                                for row in row_range {
-                                    if let Some(uri) = exclusion_list.get(row) {
-                                        ui.horizontal(|ui| {
-                                            if proxy.selected_exclusion_row.updating
-                                                && row == proxy.selected_exclusion_row.index
-                                            {
-                                                ui.with_layout(
-                                                    Layout::right_to_left(Align::Min),
-                                                    |ui| {
-                                                        if ui.button("Save").clicked() {
-                                                            proxy.update_exclusion_list(
-                                                                ProxyExclusionUpdateKind::Edit,
-                                                            );
-                                                        }
-
-                                                        let single_line_edit =
-                                                            egui::TextEdit::singleline(
-                                                                &mut proxy
-                                                                    .selected_exclusion_row
-                                                                    .value,
-                                                            )
-                                                            .min_size(vec2(
-                                                                ui.available_width(),
-                                                                18.,
-                                                            ));
-
-                                                        ui.add(single_line_edit);
-                                                    },
-                                                );
-                                            } else {
-                                                ui.with_layout(
-                                                    Layout::right_to_left(Align::Min),
-                                                    |ui| {
-                                                        if ui.button("Remove").clicked() {
-                                                            let message = format!(
-                                                                "{} -> {}",
-                                                                "Deleting item", uri
-                                                            );
-                                                            proxy.logger.debug(&message);
+            egui::CollapsingHeader::new(proxy.get_traffic_filter().get_profile_name())
+                .default_open(false)
+                .show_unindented(ui, |ui| {
+                    ui.group(|ui| {
+                        if raw_text_editor(proxy, ui) {
+                            return;
+                        }
+
+                        let mut selection = ExclusionSelection::load(
+                            proxy.get_traffic_filter().get_filter_list().len(),
+                            ui.ctx(),
+                        );
+                        bulk_edit_toolbar(proxy, &mut selection, ui);
+
+                        ui.push_id("request_exclusion_list_scrollarea", |ui| {
+                            let exclusion_list = proxy.get_traffic_filter().get_filter_list();
+                            let duplicates = proxy.get_traffic_filter().get_duplicates();
+                            let num_rows = exclusion_list.len();
 
-                                                            proxy.selected_value = uri.to_string();
-                                                            proxy.update_exclusion_list(
+                            egui::ScrollArea::new([true, true])
+                                .auto_shrink([false, false])
+                                .max_height(if request_logs_open {
+                                    ui.available_height() / 3.
+                                } else {
+                                    ui.available_height() - 20.
+                                })
+                                .show_rows(ui, 18.0, num_rows, |ui, row_range| {
+                                    for row in row_range {
+                                        if let Some(uri) = exclusion_list.get(row) {
+                                            ui.horizontal(|ui| {
+                                                if proxy.selected_exclusion_row.updating
+                                                    && row == proxy.selected_exclusion_row.index
+                                                {
+                                                    exclusion_rule_editor(proxy, ui);
+                                                } else {
+                                                    ui.with_layout(
+                                                        Layout::right_to_left(Align::Min),
+                                                        |ui| {
+                                                            if ui.button("Remove").clicked() {
+                                                                let message = format!(
+                                                                    "{} -> {}",
+                                                                    "Deleting item", uri.pattern
+                                                                );
+                                                                proxy.logger.debug(&message);
+
+                                                                proxy.selected_value =
+                                                                    uri.pattern.clone();
+                                                                proxy.update_exclusion_list(
                                                                 ProxyExclusionUpdateKind::Remove,
                                                             );
-                                                        };
-
-                                                        if ui.button("Edit").clicked() {
-                                                            proxy.selected_exclusion_row =
-                                                                ProxyExclusionRow {
-                                                                    updating: true,
-                                                                    index: row,
-                                                                    value: uri.to_string(),
-                                                                }
-                                                        }
-
-                                                        ui.with_layout(
-                                                            Layout::left_to_right(Align::Min),
-                                                            |ui| {
-                                                                ui.add(
-                                                                    egui::Label::new(
-                                                                        RichText::new(uri)
-                                                                            .size(12.5),
-                                                                    )
-                                                                    .truncate(),
+                                                            };
+
+                                                            if ui.button("Edit").clicked() {
+                                                                proxy.selected_exclusion_row =
+                                                                    ProxyExclusionRow {
+                                                                        updating: true,
+                                                                        index: row,
+                                                                        value: uri.clone(),
+                                                                    }
+                                                            }
+
+                                                            rule_hits_columns(uri, ui);
+
+                                                            let label = ui
+                                                                .with_layout(
+                                                                    Layout::left_to_right(
+                                                                        Align::Min,
+                                                                    ),
+                                                                    |ui| {
+                                                                        exclusion_rule_label(
+                                                                            uri,
+                                                                            selection.contains(row),
+                                                                            duplicates
+                                                                                .contains(&row),
+                                                                            ui,
+                                                                        )
+                                                                    },
                                                                 )
-                                                                .on_hover_text_at_pointer(uri);
-                                                            },
-                                                        );
-                                                    },
-                                                );
-                                            }
-                                        });
-                                        ui.separator();
+                                                                .inner;
+
+                                                            if label.clicked() {
+                                                                selection.click(
+                                                                    row,
+                                                                    ui.input(|i| i.modifiers),
+                                                                );
+                                                            }
+                                                        },
+                                                    );
+                                                }
+                                            });
+                                            ui.separator();
+                                        }
                                     }
-                                }
-                            });
+                                });
+                        });
+
+                        selection.store(ui.ctx());
                     });
                 });
-            });
+
+            exception_queue(proxy, ui);
+            temporary_allow_list(proxy, ui);
         }
 
+        rate_limit_usage(proxy, ui);
+
         let request_logs_dropdown = egui::CollapsingHeader::new("Request Logs")
             .default_open(false)
             .show_unindented(ui, |ui| {
                 ui.group(|ui| {
-                    ui.push_id("request_logs_scrollarea", |ui| {
-                        let request_list = proxy.get_requests();
-                        let num_rows = request_list.len();
-
-                        egui::ScrollArea::new([true, true])
-                            .auto_shrink([false, false])
-                            .max_height(ui.available_height())
-                            .show_rows(ui, 18.0, num_rows, |ui, row_range| {
-                                for row in row_range {
-                                    match request_list.get(row) {
-                                        Some(proxy_request_log) => ui.horizontal(|ui| {
-                                            let method = proxy_request_log.method.clone();
-                                            let request = proxy_request_log.request.clone();
-                                            let blocked = proxy_request_log.blocked;
-
-                                            let mut uri_truncated = request.clone();
-                                            if uri_truncated.len() > 35 {
-                                                uri_truncated.truncate(35);
-                                                uri_truncated += "...";
-                                            }
-
-                                            ui.with_layout(
-                                                Layout::left_to_right(Align::Center),
-                                                |ui| {
-                                                    ui.horizontal(|ui| {
-                                                        ui.with_layout(
-                                                            Layout::left_to_right(Align::Max),
-                                                            |ui| {
-                                                                ui.label(
-                                                                    RichText::new(method)
-                                                                        .color(Color32::LIGHT_BLUE)
-                                                                        .size(13.),
-                                                                );
-                                                                ui.label(uri_truncated)
-                                                                    .on_hover_text_at_pointer(
-                                                                        &request,
-                                                                    );
-                                                            },
-                                                        );
-                                                    });
-                                                },
-                                            );
-
-                                            ui.with_layout(
-                                                Layout::right_to_left(Align::Center),
-                                                |ui| {
-                                                    let exclusion_values = if blocked {
-                                                        (
-                                                            "Unblock",
-                                                            "Blocked",
-                                                            Color32::LIGHT_RED,
-                                                            ProxyExclusionUpdateKind::Remove,
-                                                        )
-                                                    } else {
-                                                        (
-                                                            "Block",
-                                                            "Allowed",
-                                                            Color32::LIGHT_GREEN,
-                                                            ProxyExclusionUpdateKind::Add,
-                                                        )
-                                                    };
-
-                                                    if ui.button(exclusion_values.0).clicked() {
-                                                        proxy.selected_value = request.to_string();
-                                                        proxy.update_exclusion_list(
-                                                            exclusion_values.3,
-                                                        );
-                                                    }
-
-                                                    ui.label(
-                                                        RichText::new(format!(
-                                                            "{}",
-                                                            exclusion_values.1
-                                                        ))
-                                                        .color(exclusion_values.2),
-                                                    );
-                                                },
-                                            );
-                                        }),
-                                        _ => ui.horizontal(|ui| {
-                                            ui.label("No values Found");
-                                        }),
-                                    };
-
-                                    ui.add(egui::Separator::default());
-                                }
-                            });
-                    });
+                    request_list(proxy, ui);
                 });
             });
 
@@ -563,9 +780,217 @@ fn filter_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
             let open = m.data.get_temp_mut_or_default::<bool>(request_logs_id);
             *open = request_logs_dropdown.fully_open();
         });
+
+        request_inspector(proxy, ui.ctx());
+        block_page_editor(proxy, ui.ctx());
+        header_rules_editor(proxy, ui.ctx());
+        map_local_editor(proxy, ui.ctx());
+        throttle_editor(proxy, ui.ctx());
+        fault_rules_editor(proxy, ui.ctx());
+        cache_editor(proxy, ui.ctx());
+        rate_limits_editor(proxy, ui.ctx());
+        rule_cleanup(proxy, ui.ctx());
+        rule_tester(proxy, ui.ctx());
+        change_log(proxy, ui.ctx());
     });
 }
 
+/// Shows an exclusion list item, along with how it matches and its action.
+///
+/// # Arguments:
+/// * `rule` - The exclusion list item to show.
+/// * `selected` - Whether the item is selected for a bulk action.
+/// * `duplicate` - Whether the item matches the same requests as an earlier item.
+/// * `ui` - The Ui to show the item in.
+fn exclusion_rule_label(
+    rule: &TrafficRule,
+    selected: bool,
+    duplicate: bool,
+    ui: &mut egui::Ui,
+) -> egui::Response {
+    if duplicate {
+        ui.label(RichText::new("Duplicate").color(Color32::YELLOW).size(11.))
+            .on_hover_text_at_pointer(
+                "An earlier item matches the same requests, so this one is never used.",
+            );
+    }
+
+    if let Some(schedule) = &rule.schedule {
+        schedule_label(schedule, ui);
+    }
+
+    if rule.kind == TrafficRuleKind::Regex {
+        ui.label(RichText::new("Regex").color(Color32::GRAY).size(11.));
+    }
+
+    if rule.audit {
+        ui.label(RichText::new("Audit").color(WOULD_BLOCK_COLOR).size(11.))
+            .on_hover_text_at_pointer("Requests this rule would block are forwarded anyway.");
+    }
+
+    let mut hover_text = rule.pattern.clone();
+    if rule.action != TrafficRuleAction::Filter {
+        let action = match rule.action {
+            TrafficRuleAction::Respond => format!("{} ({})", rule.action, rule.status),
+            TrafficRuleAction::Allow => rule.action.to_string(),
+            _ => format!("{} -> {}", rule.action, rule.target),
+        };
+
+        ui.label(RichText::new(&action).color(Color32::LIGHT_BLUE).size(11.));
+        hover_text = format!("{}\n{}", hover_text, action);
+    }
+
+    let mut pattern = RichText::new(&rule.pattern).size(12.5);
+    if selected {
+        pattern = pattern
+            .color(ui.visuals().selection.stroke.color)
+            .background_color(ui.visuals().selection.bg_fill);
+    }
+
+    ui.add(
+        egui::Label::new(pattern)
+            .truncate()
+            .sense(egui::Sense::click()),
+    )
+    .on_hover_text_at_pointer(format!(
+        "{}\n\nClick to select, Ctrl or Shift click to select several.",
+        hover_text
+    ))
+}
+
+/// Shows the editor for the exclusion list item being updated.
+fn exclusion_rule_editor(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let is_blocking = proxy.get_traffic_filter().is_blocking();
+    let rule = &mut proxy.selected_exclusion_row.value;
+    let validation = rule.validate();
+
+    let save_clicked = ui
+        .vertical(|ui| {
+            let save_clicked = ui
+                .with_layout(Layout::right_to_left(Align::Min), |ui| {
+                    let save_clicked = ui
+                        .add_enabled(validation.is_ok(), egui::Button::new("Save"))
+                        .clicked();
+
+                    ui.add(
+                        egui::TextEdit::singleline(&mut rule.pattern)
+                            .hint_text("Pattern")
+                            .min_size(vec2(ui.available_width(), 18.)),
+                    );
+
+                    save_clicked
+                })
+                .inner;
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("exclusion_rule_kind")
+                    .selected_text(rule.kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in [TrafficRuleKind::Contains, TrafficRuleKind::Regex] {
+                            ui.selectable_value(&mut rule.kind, kind, kind.to_string());
+                        }
+                    });
+
+                egui::ComboBox::from_id_source("exclusion_rule_action")
+                    .selected_text(rule.action.to_string())
+                    .show_ui(ui, |ui| {
+                        for action in [
+                            TrafficRuleAction::Filter,
+                            TrafficRuleAction::Redirect,
+                            TrafficRuleAction::Rewrite,
+                            TrafficRuleAction::Respond,
+                            TrafficRuleAction::Allow,
+                        ] {
+                            if ui
+                                .selectable_value(&mut rule.action, action, action.to_string())
+                                .clicked()
+                            {
+                                rule.status = match action {
+                                    TrafficRuleAction::Respond => 200,
+                                    _ => 302,
+                                };
+                            }
+                        }
+                    });
+
+                match rule.action {
+                    TrafficRuleAction::Redirect => {
+                        egui::ComboBox::from_id_source("exclusion_rule_status")
+                            .selected_text(rule.status.to_string())
+                            .show_ui(ui, |ui| {
+                                for status in [301, 302, 307, 308] {
+                                    ui.selectable_value(
+                                        &mut rule.status,
+                                        status,
+                                        status.to_string(),
+                                    );
+                                }
+                            });
+                    }
+                    TrafficRuleAction::Respond => {
+                        ui.label("Status:");
+                        ui.add(egui::DragValue::new(&mut rule.status).range(100..=599));
+                        ui.add(
+                            egui::TextEdit::singleline(&mut rule.content_type)
+                                .hint_text("Content type")
+                                .desired_width(ui.available_width()),
+                        );
+                    }
+                    TrafficRuleAction::Filter
+                    | TrafficRuleAction::Rewrite
+                    | TrafficRuleAction::Allow => {}
+                }
+
+                ui.checkbox(&mut rule.audit, "Audit").on_hover_text(
+                    "Only record the requests this rule would block, and forward them anyway.",
+                );
+            });
+
+            let target_hint = match rule.action {
+                TrafficRuleAction::Filter | TrafficRuleAction::Allow => None,
+                TrafficRuleAction::Redirect => Some("Redirect to URL, e.g. https://example.com/$1"),
+                TrafficRuleAction::Rewrite => Some("Rewrite to URL, host:port or /path"),
+                TrafficRuleAction::Respond => Some("Response body"),
+            };
+
+            if let Some(target_hint) = target_hint {
+                let target_edit = match rule.action {
+                    TrafficRuleAction::Respond => egui::TextEdit::multiline(&mut rule.target)
+                        .desired_rows(3)
+                        .code_editor(),
+                    _ => egui::TextEdit::singleline(&mut rule.target),
+                };
+
+                ui.add(
+                    target_edit
+                        .hint_text(target_hint)
+                        .desired_width(ui.available_width()),
+                )
+                .on_hover_text("Regex rules may use their capture groups, e.g. $1 or ${name}.");
+            }
+
+            schedule_editor("exclusion_rule_schedule", &mut rule.schedule, ui);
+            rule_impact(
+                "exclusion_rule_impact",
+                rule,
+                &proxy.requests,
+                is_blocking,
+                ui,
+            );
+
+            if let Err(error) = &validation {
+                ui.label(RichText::new(error).color(Color32::LIGHT_RED).size(11.));
+            }
+
+            save_clicked
+        })
+        .inner;
+
+    if save_clicked {
+        proxy.update_exclusion_list(ProxyExclusionUpdateKind::Edit);
+    }
+}
+
 fn logs_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
     ui.vertical(|ui| {
         ui.horizontal(|ui| {
@@ -0,0 +1,158 @@
+use crate::service::{map_local::MapLocalRule, proxy::Proxy, traffic_filter::TrafficRuleKind};
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the map local editor open.
+pub const MAP_LOCAL_EDITOR_ID: &str = "map_local_editor";
+
+/// Shows the map local rules in a separate window, while it's open.
+pub fn map_local_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(MAP_LOCAL_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut map_local = proxy.get_settings().map_local;
+    let previous = map_local.clone();
+
+    egui::Window::new("Map Local")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([760., 300.])
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(
+                    "Matching requests are answered from disk instead of upstream. A directory serves the rest of the URL's path, or index.html.",
+                )
+                .size(11.)
+                .color(Color32::GRAY),
+            );
+            ui.add_space(4.);
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    let mut removed = None;
+
+                    egui::Grid::new("map_local_grid")
+                        .num_columns(7)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["", "Type", "URL Pattern", "Local Path", "Status", "Headers", ""]
+                            {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            for (index, rule) in map_local.iter_mut().enumerate() {
+                                map_local_row(index, rule, ui);
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if let Some(index) = removed {
+                        map_local.remove(index);
+                    }
+
+                    if map_local.is_empty() {
+                        ui.label("No map local rules have been added.");
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Add Rule").clicked() {
+                    map_local.push(MapLocalRule::default());
+                }
+
+                let errors = map_local
+                    .iter()
+                    .filter(|rule| rule.enabled && rule.validate().is_err())
+                    .count();
+                if errors > 0 {
+                    ui.label(
+                        RichText::new(format!("{} rule(s) are invalid.", errors))
+                            .color(Color32::LIGHT_RED),
+                    );
+                }
+            });
+        });
+
+    if map_local != previous {
+        proxy.set_map_local_rules(map_local);
+    }
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
+
+/// Shows the editable fields of a single map local rule in the map local grid.
+fn map_local_row(index: usize, rule: &mut MapLocalRule, ui: &mut egui::Ui) {
+    ui.checkbox(&mut rule.enabled, "")
+        .on_hover_text("Enable or disable this rule.");
+
+    egui::ComboBox::from_id_source(("map_local_kind", index))
+        .selected_text(match rule.kind {
+            TrafficRuleKind::Contains => "Contains",
+            TrafficRuleKind::Regex => "Regex",
+        })
+        .width(80.)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut rule.kind, TrafficRuleKind::Contains, "Contains");
+            ui.selectable_value(&mut rule.kind, TrafficRuleKind::Regex, "Regex");
+        });
+
+    let validation = rule.validate();
+    let pattern_response = ui.add(
+        TextEdit::singleline(&mut rule.pattern)
+            .hint_text("http://example.com/static/")
+            .desired_width(180.)
+            .text_color_opt(validation.is_err().then_some(Color32::LIGHT_RED)),
+    );
+    if let Err(error) = &validation {
+        pattern_response.on_hover_text(error);
+    }
+
+    ui.horizontal(|ui| {
+        ui.add(
+            TextEdit::singleline(&mut rule.path)
+                .hint_text("/path/to/file or directory")
+                .desired_width(160.),
+        )
+        .on_hover_text("Regex rules can use capture groups in the path, e.g. $1.");
+
+        ui.menu_button("…", |ui| {
+            if ui.button("Choose File").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    rule.path = path.display().to_string();
+                }
+                ui.close_menu();
+            }
+
+            if ui.button("Choose Directory").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    rule.path = path.display().to_string();
+                }
+                ui.close_menu();
+            }
+        });
+    });
+
+    ui.add(egui::DragValue::new(&mut rule.status).range(100..=599));
+
+    ui.add(
+        TextEdit::multiline(&mut rule.headers)
+            .hint_text("Name: Value")
+            .desired_rows(1)
+            .desired_width(150.),
+    )
+    .on_hover_text("One header per line. The Content-Type is guessed from the file's extension unless set here.");
+}
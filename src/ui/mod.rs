@@ -1,3 +1,20 @@
+pub(crate) mod block_page;
+pub(crate) mod bulk_edit;
+pub(crate) mod cache;
+pub(crate) mod change_log;
 pub(crate) mod custom_widgets;
 pub(crate) mod default_window;
+pub(crate) mod faults;
+pub(crate) mod header_rules;
+pub(crate) mod import;
 pub(crate) mod main_body;
+pub(crate) mod map_local;
+pub(crate) mod rate_limits;
+pub(crate) mod request_inspector;
+pub(crate) mod request_list;
+pub(crate) mod rule_hits;
+pub(crate) mod rule_tester;
+pub(crate) mod schedule;
+pub(crate) mod snooze;
+pub(crate) mod stats_panel;
+pub(crate) mod throttle;
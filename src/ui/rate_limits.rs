@@ -0,0 +1,263 @@
+use super::request_inspector::format_size;
+use crate::service::{
+    proxy::Proxy,
+    rate_limit::{QuotaUnit, QuotaWindow, RateLimitAction, RateLimitRule},
+};
+use eframe::{
+    egui::{self, Layout, RichText, TextEdit},
+    emath::Align,
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the rate limits editor open.
+pub const RATE_LIMITS_EDITOR_ID: &str = "rate_limits_editor";
+
+/// Shows the quota usage of each rate limit rule, in the Filter view.
+pub fn rate_limit_usage(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let rate_limits = proxy.get_settings().rate_limits;
+    if rate_limits.is_empty() {
+        return;
+    }
+
+    egui::CollapsingHeader::new(format!("Rate Limits ({})", rate_limits.len()))
+        .default_open(false)
+        .show_unindented(ui, |ui| {
+            ui.group(|ui| {
+                egui::Grid::new("rate_limit_usage_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (index, rule) in rate_limits.iter().enumerate() {
+                            ui.label(RichText::new(&rule.host).color(match rule.enabled {
+                                true => Color32::LIGHT_GRAY,
+                                false => Color32::GRAY,
+                            }))
+                            .on_hover_text(describe_limits(rule));
+
+                            let used = rule.quota_used();
+                            let usage = describe_usage(rule);
+                            match rule.quota {
+                                0 => ui.label(usage),
+                                quota => ui.add(
+                                    egui::ProgressBar::new((used as f32 / quota as f32).min(1.))
+                                        .desired_width(220.)
+                                        .fill(match rule.quota_exceeded() {
+                                            true => Color32::DARK_RED,
+                                            false => Color32::DARK_GREEN,
+                                        })
+                                        .text(usage),
+                                ),
+                            };
+
+                            ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                                if ui
+                                    .button("Reset")
+                                    .on_hover_text("Clear the usage counted in the current window.")
+                                    .clicked()
+                                {
+                                    proxy.reset_rate_limit_usage(index);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+
+                if ui.button("Edit Rate Limits").clicked() {
+                    ui.memory_mut(|m| {
+                        m.data
+                            .insert_temp(egui::Id::new(RATE_LIMITS_EDITOR_ID), true)
+                    });
+                }
+            });
+        });
+}
+
+/// Shows the rate limit rules in a separate window, while it's open.
+pub fn rate_limits_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(RATE_LIMITS_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut rate_limits = proxy.get_settings().rate_limits;
+
+    egui::Window::new("Rate Limits")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([860., 300.])
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(
+                    "The first rule matching a host applies to its requests and tunnels. A rate or quota of 0 disables it, quotas reset every hour or day in local time.",
+                )
+                .size(11.)
+                .color(Color32::GRAY),
+            );
+            ui.add_space(4.);
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    let mut removed = None;
+
+                    egui::Grid::new("rate_limits_grid")
+                        .num_columns(8)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in [
+                                "",
+                                "Host",
+                                "Rate (req/s)",
+                                "Burst",
+                                "Quota",
+                                "Over Limit",
+                                "Usage",
+                                "",
+                            ] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            // Only the edited rule is written back, the Proxy keeps counting usage meanwhile
+                            for (index, rule) in rate_limits.iter_mut().enumerate() {
+                                let previous = rule.clone();
+                                if rate_limit_row(index, rule, ui) {
+                                    proxy.reset_rate_limit_usage(index);
+                                } else if *rule != previous {
+                                    proxy.update_rate_limit(index, rule.clone());
+                                }
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if let Some(index) = removed {
+                        proxy.remove_rate_limit(index);
+                        rate_limits.remove(index);
+                    }
+
+                    if rate_limits.is_empty() {
+                        ui.label("No rate limits have been added.");
+                    }
+                });
+
+            ui.separator();
+
+            if ui.button("Add Rule").clicked() {
+                proxy.add_rate_limit(RateLimitRule::default());
+            }
+        });
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
+
+/// Shows the editable fields of a single rate limit rule in the rate limits grid.
+///
+/// Returns whether the rule's usage should be reset.
+fn rate_limit_row(index: usize, rule: &mut RateLimitRule, ui: &mut egui::Ui) -> bool {
+    ui.checkbox(&mut rule.enabled, "")
+        .on_hover_text("Enable or disable this rule.");
+
+    ui.add(
+        TextEdit::singleline(&mut rule.host)
+            .hint_text("*.example.com")
+            .desired_width(140.),
+    )
+    .on_hover_text("example.com, *.example.com for it and its subdomains, or * for every host.");
+
+    ui.add(egui::DragValue::new(&mut rule.rate).range(0..=100_000));
+    ui.add(egui::DragValue::new(&mut rule.burst).range(1..=100_000))
+        .on_hover_text("The number of requests allowed at once before the rate applies.");
+
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut rule.quota).range(0..=u32::MAX as u64));
+
+        egui::ComboBox::from_id_source(("rate_limit_unit", index))
+            .selected_text(rule.unit.to_string())
+            .width(80.)
+            .show_ui(ui, |ui| {
+                for unit in [QuotaUnit::Requests, QuotaUnit::Megabytes] {
+                    ui.selectable_value(&mut rule.unit, unit, unit.to_string());
+                }
+            });
+
+        egui::ComboBox::from_id_source(("rate_limit_window", index))
+            .selected_text(rule.window.to_string())
+            .width(70.)
+            .show_ui(ui, |ui| {
+                for window in [QuotaWindow::Hourly, QuotaWindow::Daily] {
+                    ui.selectable_value(&mut rule.window, window, window.to_string());
+                }
+            });
+    });
+
+    egui::ComboBox::from_id_source(("rate_limit_action", index))
+        .selected_text(rule.action.to_string())
+        .width(150.)
+        .show_ui(ui, |ui| {
+            for action in [RateLimitAction::TooManyRequests, RateLimitAction::Block] {
+                ui.selectable_value(&mut rule.action, action, action.to_string());
+            }
+        });
+
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new(describe_usage(rule)).color(match rule.quota_exceeded() {
+                true => Color32::LIGHT_RED,
+                false => Color32::LIGHT_GREEN,
+            }),
+        );
+
+        ui.add(egui::Button::new("Reset").small()).clicked()
+    })
+    .inner
+}
+
+/// Returns a readable description of a rule's usage in the current window.
+fn describe_usage(rule: &RateLimitRule) -> String {
+    let usage = rule.current_usage();
+    let window = match rule.window {
+        QuotaWindow::Hourly => "this hour",
+        QuotaWindow::Daily => "today",
+    };
+
+    match (rule.quota, rule.unit) {
+        (0, _) => format!(
+            "{} requests, {} {}",
+            usage.requests,
+            format_size(usage.bytes),
+            window
+        ),
+        (quota, QuotaUnit::Requests) => {
+            format!("{} / {} requests {}", usage.requests, quota, window)
+        }
+        (quota, QuotaUnit::Megabytes) => {
+            format!("{} / {} MB {}", format_size(usage.bytes), quota, window)
+        }
+    }
+}
+
+/// Returns a readable description of a rule's limits.
+fn describe_limits(rule: &RateLimitRule) -> String {
+    let rate = match rule.rate {
+        0 => String::from("No rate limit"),
+        rate => format!("{} requests per second, burst {}", rate, rule.burst),
+    };
+
+    let quota = match rule.quota {
+        0 => String::from("no quota"),
+        quota => format!(
+            "{} {} {}",
+            quota,
+            rule.unit.to_string().to_lowercase(),
+            rule.window.to_string().to_lowercase()
+        ),
+    };
+
+    format!("{}, {}. Over the limit: {}", rate, quota, rule.action)
+}
@@ -0,0 +1,770 @@
+use super::request_list::WOULD_BLOCK_COLOR;
+use crate::service::{
+    capture::{ProxyBodyLog, BODY_CAPTURE_LIMIT},
+    proxy::{Proxy, ProxyRequestLog},
+    replay::{ProxyReplayRequest, ProxyReplayStatus},
+};
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+use hyper::body::Bytes;
+use std::{sync::Arc, time::Duration};
+
+/// HTML elements that never contain children, so aren't indented when pretty printing.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// The largest number of bytes shown in a hex preview.
+const HEX_PREVIEW_LIMIT: usize = 16 * 1024;
+
+/// The body of an edited request, only editable as text when it's valid UTF-8.
+#[derive(Clone)]
+enum ReplayDraftBody {
+    Text(String),
+    Binary(Bytes),
+}
+
+/// Contains an edited copy of a request, waiting to be resent.
+#[derive(Clone)]
+struct ReplayDraft {
+    method: String,
+    uri: String,
+    headers: String,
+    body: ReplayDraftBody,
+    error: Option<String>,
+}
+
+impl ReplayDraft {
+    /// Creates a draft from the request being resent.
+    fn new(request: &ProxyReplayRequest) -> Self {
+        Self {
+            method: request.method.clone(),
+            uri: request.uri.clone(),
+            headers: request
+                .headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}\n", name, value))
+                .collect(),
+            body: match std::str::from_utf8(&request.body) {
+                Ok(text) => ReplayDraftBody::Text(text.to_string()),
+                Err(_) => ReplayDraftBody::Binary(request.body.clone()),
+            },
+            error: None,
+        }
+    }
+
+    /// Converts the draft to a request, returning an error for any malformed header lines.
+    fn to_request(&self) -> Result<ProxyReplayRequest, String> {
+        let headers = self
+            .headers
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.split_once(':')
+                    .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                    .ok_or(format!(
+                        "Headers must be written as 'Name: Value': {}",
+                        line
+                    ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ProxyReplayRequest {
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            headers,
+            body: match &self.body {
+                ReplayDraftBody::Text(text) => Bytes::from(text.clone()),
+                ReplayDraftBody::Binary(body) => body.clone(),
+            },
+        })
+    }
+}
+
+/// Shows the details of the selected request in a separate window.
+pub fn request_inspector(proxy: &mut Proxy, ctx: &egui::Context) {
+    let Some(id) = proxy.selected_request else {
+        return;
+    };
+
+    let Some(request) = proxy.get_request(id) else {
+        proxy.selected_request = None;
+        return;
+    };
+
+    let mut open = true;
+    egui::Window::new("Request Details")
+        .id(egui::Id::new("request_inspector"))
+        .open(&mut open)
+        .default_size([480., 420.])
+        .vscroll(true)
+        .show(ctx, |ui| {
+            request_overview(&request, ui);
+
+            ui.add_space(5.);
+
+            replay_controls(proxy, &request, ui);
+            replay_comparison(proxy, &request, ui);
+
+            egui::CollapsingHeader::new("Request Headers")
+                .default_open(true)
+                .show(ui, |ui| {
+                    header_grid("request_headers", &request.request_headers, ui)
+                });
+
+            egui::CollapsingHeader::new("Response Headers")
+                .default_open(true)
+                .show(ui, |ui| match &request.response {
+                    Some(response) => header_grid("response_headers", &response.headers, ui),
+                    None => {
+                        ui.label("Waiting for a response.");
+                    }
+                });
+
+            if !request.header_changes.is_empty() {
+                egui::CollapsingHeader::new("Header Changes")
+                    .default_open(true)
+                    .show(ui, |ui| header_changes(&request, ui));
+            }
+
+            egui::CollapsingHeader::new("Request Body").show(ui, |ui| {
+                body_preview(
+                    (id, "request"),
+                    request.request_body.as_ref(),
+                    &request.request_headers,
+                    ui,
+                )
+            });
+
+            egui::CollapsingHeader::new("Response Body").show(ui, |ui| {
+                let headers = request
+                    .response
+                    .as_ref()
+                    .map(|response| response.headers.clone())
+                    .unwrap_or_default();
+
+                body_preview(
+                    (id, "response"),
+                    request.response_body.as_ref(),
+                    &headers,
+                    ui,
+                )
+            });
+        });
+
+    if !open {
+        proxy.selected_request = None;
+    }
+}
+
+/// Shows the general information regarding a request.
+fn request_overview(request: &ProxyRequestLog, ui: &mut egui::Ui) {
+    egui::Grid::new("request_overview")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("URI:");
+            ui.add(egui::Label::new(&request.request).wrap());
+            ui.end_row();
+
+            ui.label("Method:");
+            ui.label(RichText::new(&request.method).color(Color32::LIGHT_BLUE));
+            ui.end_row();
+
+            ui.label("Client:");
+            ui.label(&request.client);
+            ui.end_row();
+
+            ui.label("Protocol:");
+            match &request.response {
+                Some(response) => ui.label(format!("{} -> {}", request.version, response.version)),
+                None => ui.label(&request.version),
+            };
+            ui.end_row();
+
+            ui.label("Time:");
+            ui.label(
+                request
+                    .timestamp
+                    .format("%Y-%m-%d %H:%M:%S%.3f")
+                    .to_string(),
+            );
+            ui.end_row();
+
+            ui.label("Outcome:");
+            if request.blocked {
+                ui.label(RichText::new("Blocked").color(Color32::LIGHT_RED));
+            } else if request.would_block {
+                ui.label(RichText::new("Would Block").color(WOULD_BLOCK_COLOR))
+                    .on_hover_text("Forwarded, as its profile or rule is in audit mode.");
+            } else {
+                ui.label(RichText::new("Allowed").color(Color32::LIGHT_GREEN));
+            }
+            ui.end_row();
+
+            ui.label("Rule:");
+            ui.label(request.rule.as_deref().unwrap_or("No matching rule"));
+            ui.end_row();
+
+            if let Some(action) = &request.action {
+                ui.label("Action:");
+                ui.add(egui::Label::new(RichText::new(action).color(Color32::LIGHT_BLUE)).wrap());
+                ui.end_row();
+            }
+
+            if let Some(path) = &request.mapped_local {
+                ui.label("Mapped Locally:");
+                ui.add(egui::Label::new(RichText::new(path).color(Color32::LIGHT_GREEN)).wrap());
+                ui.end_row();
+            }
+
+            if let Some(reason) = &request.rate_limit {
+                ui.label("Rate Limited:");
+                ui.label(RichText::new(reason).color(Color32::GOLD));
+                ui.end_row();
+            }
+
+            if let Some(status) = request.cache {
+                ui.label("Cache:");
+                ui.label(status.to_string());
+                ui.end_row();
+            }
+
+            if let Some(fault) = &request.fault {
+                ui.label("Injected Fault:");
+                ui.label(RichText::new(fault).color(Color32::LIGHT_RED));
+                ui.end_row();
+            }
+
+            if let Some(profile) = &request.throttle {
+                ui.label("Network Conditions:");
+                ui.label(RichText::new(profile).color(Color32::YELLOW));
+                ui.end_row();
+            }
+
+            if let Some(server_name) = &request.server_name {
+                ui.label("Server Name (SNI):");
+                ui.horizontal(|ui| {
+                    ui.label(server_name);
+                    if request.server_name_mismatch() {
+                        ui.label(
+                            RichText::new("Doesn't match CONNECT host").color(Color32::YELLOW),
+                        )
+                        .on_hover_text(format!(
+                            "The tunnel was opened to {}, but the client asked for {}.",
+                            request.host, server_name
+                        ));
+                    }
+                });
+                ui.end_row();
+            }
+
+            ui.label("Status:");
+            match &request.response {
+                Some(response) => ui.label(response.status.to_string()),
+                None => ui.label("Pending"),
+            };
+            ui.end_row();
+
+            ui.label("Time to Headers:");
+            match &request.response {
+                Some(response) => ui.label(format_duration(response.time_to_headers)),
+                None => ui.label("Pending"),
+            };
+            ui.end_row();
+
+            ui.label("Total Time:");
+            match request.duration {
+                Some(duration) => ui.label(format_duration(duration)),
+                None => ui.label("Pending"),
+            };
+            ui.end_row();
+
+            ui.label("Request Size:");
+            match &request.request_body {
+                Some(body) => ui.label(format_size(body.size)),
+                None => ui.label("Pending"),
+            };
+            ui.end_row();
+
+            ui.label("Response Size:");
+            match &request.response_body {
+                Some(body) => ui.label(format_size(body.size)),
+                None => ui.label("Pending"),
+            };
+            ui.end_row();
+
+            if let Some(error) = &request.error {
+                ui.label("Error:");
+                ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+                ui.end_row();
+            }
+        });
+}
+
+/// Shows the buttons and editor used to resend a request.
+fn replay_controls(proxy: &mut Proxy, request: &ProxyRequestLog, ui: &mut egui::Ui) {
+    let draft_id = egui::Id::new(("replay_draft", request.id));
+    let mut draft = ui.data_mut(|data| data.get_temp::<ReplayDraft>(draft_id));
+    let replayable = request.method != "CONNECT";
+
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(replayable, egui::Button::new("Replay"))
+            .on_disabled_hover_text("CONNECT tunnels can't be resent.")
+            .clicked()
+        {
+            if let Err(message) =
+                proxy.replay_request(request.id, ProxyReplayRequest::from(request))
+            {
+                proxy.logger.warning(&message);
+            }
+        }
+
+        if ui
+            .add_enabled(
+                replayable && draft.is_none(),
+                egui::Button::new("Edit & Resend"),
+            )
+            .on_disabled_hover_text("CONNECT tunnels can't be resent.")
+            .clicked()
+        {
+            draft = Some(ReplayDraft::new(&ProxyReplayRequest::from(request)));
+        }
+    });
+
+    if let Some(body) = request.request_body.as_ref().filter(|body| body.size > 0) {
+        let warning = match &body.preview {
+            None => Some(String::from(
+                "Body capture was disabled, so the request will be resent without its body.",
+            )),
+            Some(_) if body.truncated => Some(format!(
+                "Only the first {} of the body was captured, so the body will be resent incomplete.",
+                format_size(BODY_CAPTURE_LIMIT as u64)
+            )),
+            Some(_) => None,
+        };
+
+        if let Some(warning) = warning.filter(|_| replayable) {
+            ui.label(RichText::new(warning).size(11.).color(Color32::YELLOW));
+        }
+    }
+
+    let mut close_draft = false;
+    if let Some(draft) = &mut draft {
+        ui.group(|ui| {
+            egui::Grid::new("replay_draft")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Method:");
+                    ui.add(TextEdit::singleline(&mut draft.method).desired_width(80.));
+                    ui.end_row();
+
+                    ui.label("URL:");
+                    ui.add(TextEdit::singleline(&mut draft.uri).desired_width(f32::INFINITY));
+                    ui.end_row();
+                });
+
+            ui.label("Headers:");
+            ui.add(
+                TextEdit::multiline(&mut draft.headers)
+                    .code_editor()
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY),
+            );
+
+            ui.label("Body:");
+            match &mut draft.body {
+                ReplayDraftBody::Text(text) => {
+                    ui.add(
+                        TextEdit::multiline(text)
+                            .code_editor()
+                            .desired_rows(4)
+                            .desired_width(f32::INFINITY),
+                    );
+                }
+                ReplayDraftBody::Binary(body) => {
+                    ui.label(
+                        RichText::new(format!(
+                            "{} of binary data, which can't be edited and is resent as it was.",
+                            format_size(body.len() as u64)
+                        ))
+                        .size(11.)
+                        .color(Color32::GRAY),
+                    );
+                }
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Send").clicked() {
+                    match draft
+                        .to_request()
+                        .and_then(|edited| proxy.replay_request(request.id, edited))
+                    {
+                        Ok(_) => close_draft = true,
+                        Err(message) => draft.error = Some(message),
+                    }
+                }
+
+                if ui.button("Cancel").clicked() {
+                    close_draft = true;
+                }
+            });
+
+            if let Some(error) = &draft.error {
+                ui.label(RichText::new(error).size(11.).color(Color32::LIGHT_RED));
+            }
+        });
+    }
+
+    ui.data_mut(|data| match draft.filter(|_| !close_draft) {
+        Some(draft) => data.insert_temp(draft_id, draft),
+        None => data.remove::<ReplayDraft>(draft_id),
+    });
+}
+
+/// Shows the response to a resent request next to the original response.
+fn replay_comparison(proxy: &Proxy, request: &ProxyRequestLog, ui: &mut egui::Ui) {
+    let Some(replay) = proxy
+        .get_replay()
+        .filter(|replay| replay.request_id == request.id)
+    else {
+        return;
+    };
+
+    egui::CollapsingHeader::new("Replay")
+        .default_open(true)
+        .show(ui, |ui| {
+            ui.add(
+                egui::Label::new(
+                    RichText::new(format!("{} {}", replay.request.method, replay.request.uri))
+                        .color(Color32::LIGHT_BLUE),
+                )
+                .wrap(),
+            );
+
+            ui.columns(2, |columns| {
+                let ui = &mut columns[0];
+                ui.label(RichText::new("Original").strong());
+                response_summary(
+                    "original_summary",
+                    request.response.as_ref().map(|response| response.status),
+                    request.duration,
+                    request.response_body.as_ref(),
+                    ui,
+                );
+                body_preview(
+                    (request.id, "original"),
+                    request.response_body.as_ref(),
+                    &request
+                        .response
+                        .as_ref()
+                        .map(|response| response.headers.clone())
+                        .unwrap_or_default(),
+                    ui,
+                );
+
+                let ui = &mut columns[1];
+                ui.label(RichText::new("Replay").strong());
+                match &replay.status {
+                    ProxyReplayStatus::Sending => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Sending...");
+                        });
+                    }
+                    ProxyReplayStatus::Failed(message) => {
+                        ui.label(RichText::new(message).color(Color32::LIGHT_RED));
+                    }
+                    ProxyReplayStatus::Complete(response) => {
+                        response_summary(
+                            "replay_summary",
+                            Some(response.response.status),
+                            Some(response.duration),
+                            Some(&response.body),
+                            ui,
+                        );
+                        body_preview(
+                            (request.id, "replay"),
+                            Some(&response.body),
+                            &response.response.headers,
+                            ui,
+                        );
+
+                        egui::CollapsingHeader::new("Replay Headers").show(ui, |ui| {
+                            header_grid("replay_headers", &response.response.headers, ui)
+                        });
+                    }
+                }
+            });
+        });
+}
+
+/// Shows the status, timing and size of a response.
+fn response_summary(
+    id: &str,
+    status: Option<u16>,
+    duration: Option<Duration>,
+    body: Option<&ProxyBodyLog>,
+    ui: &mut egui::Ui,
+) {
+    egui::Grid::new(id).num_columns(2).show(ui, |ui| {
+        ui.label("Status:");
+        ui.label(status.map_or(String::from("Pending"), |status| status.to_string()));
+        ui.end_row();
+
+        ui.label("Total Time:");
+        ui.label(duration.map_or(String::from("Pending"), format_duration));
+        ui.end_row();
+
+        ui.label("Size:");
+        ui.label(body.map_or(String::from("Pending"), |body| format_size(body.size)));
+        ui.end_row();
+    });
+}
+
+/// Shows a list of headers as a grid.
+fn header_grid(id: &str, headers: &[(String, String)], ui: &mut egui::Ui) {
+    if headers.is_empty() {
+        ui.label("No headers.");
+        return;
+    }
+
+    egui::Grid::new(id)
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (name, value) in headers {
+                ui.label(RichText::new(name).color(Color32::LIGHT_BLUE));
+                ui.add(egui::Label::new(value).wrap());
+                ui.end_row();
+            }
+        });
+}
+
+/// Shows the changes header rules made to a request and its response.
+fn header_changes(request: &ProxyRequestLog, ui: &mut egui::Ui) {
+    ui.label(
+        RichText::new("Request headers are shown as the client sent them, before these changes.")
+            .size(11.)
+            .color(Color32::GRAY),
+    );
+
+    egui::Grid::new("header_changes")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for change in &request.header_changes {
+                ui.label(RichText::new(change.direction.to_string()).color(Color32::LIGHT_BLUE));
+                ui.add(egui::Label::new(change.to_string()).wrap());
+                ui.end_row();
+            }
+        });
+}
+
+/// Shows a formatted preview of a captured body.
+///
+/// # Arguments:
+/// * `key` - A unique key for the body, used to cache the formatted preview.
+/// * `body` - The captured body, if the body has finished.
+/// * `headers` - The headers sent with the body.
+fn body_preview(
+    key: (usize, &str),
+    body: Option<&ProxyBodyLog>,
+    headers: &[(String, String)],
+    ui: &mut egui::Ui,
+) {
+    let Some(body) = body else {
+        ui.label("Waiting for the body to finish.");
+        return;
+    };
+
+    let Some(preview) = &body.preview else {
+        ui.label("Body capture was disabled for this request.");
+        return;
+    };
+
+    if preview.is_empty() {
+        ui.label("Empty body.");
+        return;
+    }
+
+    if body.truncated {
+        ui.label(
+            RichText::new(format!(
+                "Showing the first {} of {}.",
+                format_size(preview.len() as u64),
+                format_size(body.size)
+            ))
+            .color(Color32::YELLOW),
+        );
+    }
+
+    // Formatting large bodies is slow, so keep the result between frames
+    let cache_id = egui::Id::new(("body_preview", key, body.size));
+    let formatted = ui.data_mut(|data| data.get_temp::<Arc<String>>(cache_id));
+    let formatted = formatted.unwrap_or_else(|| {
+        let formatted = Arc::new(format_body(
+            preview,
+            header_value(headers, "content-type"),
+            body.truncated,
+        ));
+        ui.data_mut(|data| data.insert_temp(cache_id, formatted.clone()));
+        formatted
+    });
+
+    egui::ScrollArea::both()
+        .id_source(cache_id)
+        .max_height(250.)
+        .show(ui, |ui| {
+            ui.add(
+                TextEdit::multiline(&mut formatted.as_str())
+                    .code_editor()
+                    .desired_width(f32::INFINITY),
+            );
+        });
+}
+
+/// Returns the value of a header, if present.
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Formats a body for display, pretty printing JSON and HTML and showing binary data as hex.
+///
+/// # Arguments:
+/// * `body` - The body to format.
+/// * `content_type` - The Content-Type the body was sent with.
+/// * `truncated` - Whether the body was cut short when it was captured.
+fn format_body(body: &Bytes, content_type: Option<&str>, truncated: bool) -> String {
+    let content_type = content_type.unwrap_or_default().to_lowercase();
+
+    let text = match std::str::from_utf8(body) {
+        Ok(text) => text,
+        // A truncated body may have been cut part way through a character, which isn't binary data
+        Err(error) if truncated && error.error_len().is_none() => {
+            std::str::from_utf8(&body[..error.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return format_hex(body),
+    };
+
+    if content_type.contains("json") || text.trim_start().starts_with(['{', '[']) {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(text) {
+            return serde_json::to_string_pretty(&value).unwrap_or(text.to_string());
+        }
+    }
+
+    if content_type.contains("html") || content_type.contains("xml") {
+        return format_markup(text);
+    }
+
+    text.to_string()
+}
+
+/// Pretty prints HTML/XML, placing each tag on its own line and indenting nested elements.
+fn format_markup(text: &str) -> String {
+    let mut formatted = String::new();
+    let mut depth: usize = 0;
+    let mut rest = text;
+
+    let mut push_line = |line: &str, depth: usize| {
+        if !line.is_empty() {
+            formatted.push_str(&"  ".repeat(depth));
+            formatted.push_str(line);
+            formatted.push('\n');
+        }
+    };
+
+    while let Some(start) = rest.find('<') {
+        push_line(rest[..start].trim(), depth);
+
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..start + end + 1];
+        rest = &rest[start + end + 1..];
+
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if tag.starts_with("</") {
+            depth = depth.saturating_sub(1);
+            push_line(tag, depth);
+        } else if tag.starts_with("<!")
+            || tag.starts_with("<?")
+            || tag.ends_with("/>")
+            || VOID_ELEMENTS.contains(&name.as_str())
+        {
+            push_line(tag, depth);
+        } else {
+            push_line(tag, depth);
+            depth += 1;
+        }
+    }
+
+    push_line(rest.trim(), depth);
+
+    formatted
+}
+
+/// Formats binary data as a hex dump, with an offset column and printable characters.
+fn format_hex(body: &Bytes) -> String {
+    let mut formatted = String::new();
+
+    for (line, chunk) in body[..body.len().min(HEX_PREVIEW_LIMIT)]
+        .chunks(16)
+        .enumerate()
+    {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let printable: String = chunk
+            .iter()
+            .map(|byte| {
+                if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        formatted.push_str(&format!(
+            "{:08x}  {:<47}  {}\n",
+            line * 16,
+            hex.join(" "),
+            printable
+        ));
+    }
+
+    if body.len() > HEX_PREVIEW_LIMIT {
+        formatted.push_str(&format!(
+            "... {} more bytes\n",
+            body.len() - HEX_PREVIEW_LIMIT
+        ));
+    }
+
+    formatted
+}
+
+/// Formats a Duration as milliseconds.
+pub fn format_duration(duration: Duration) -> String {
+    format!("{} ms", duration.as_millis())
+}
+
+/// Formats a number of bytes to a readable size, e.g. 1.5 KB.
+pub fn format_size(size: u64) -> String {
+    match size {
+        size if size >= 1024 * 1024 => format!("{:.1} MB", size as f64 / (1024. * 1024.)),
+        size if size >= 1024 => format!("{:.1} KB", size as f64 / 1024.),
+        size => format!("{} B", size),
+    }
+}
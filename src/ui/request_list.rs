@@ -0,0 +1,630 @@
+use super::snooze::snooze_button;
+use crate::service::{
+    cache::ProxyCacheStatus,
+    proxy::{Proxy, ProxyExclusionUpdateKind, ProxyRequestLog},
+};
+use eframe::{
+    egui::{self, Layout, RichText, TextEdit},
+    emath::Align,
+    epaint::{Color32, Vec2},
+};
+use regex::{Regex, RegexBuilder};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+
+const ROW_HEIGHT: f32 = 18.0;
+
+/// The colour of requests that would have been blocked, if their profile or rule wasn't in audit mode.
+pub const WOULD_BLOCK_COLOR: Color32 = Color32::from_rgb(255, 165, 0);
+
+/// The enum that controls which request outcomes are shown in the request list.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum RequestOutcome {
+    #[default]
+    Any,
+    Allowed,
+    Blocked,
+    WouldBlock,
+}
+
+impl std::fmt::Display for RequestOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestOutcome::Any => write!(f, "Any Outcome"),
+            RequestOutcome::Allowed => write!(f, "Allowed"),
+            RequestOutcome::Blocked => write!(f, "Blocked"),
+            RequestOutcome::WouldBlock => write!(f, "Would Block"),
+        }
+    }
+}
+
+/// The enum that controls the order of the request list.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum RequestSort {
+    #[default]
+    Time,
+    Host,
+    Count,
+}
+
+impl std::fmt::Display for RequestSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestSort::Time => write!(f, "Time"),
+            RequestSort::Host => write!(f, "Host"),
+            RequestSort::Count => write!(f, "Count"),
+        }
+    }
+}
+
+/// Contains the search, filter, sort and grouping options of the request list.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct RequestListOptions {
+    pub search: String,
+    pub regex: bool,
+    pub method: Option<String>,
+    pub outcome: RequestOutcome,
+    pub sort: RequestSort,
+    pub descending: bool,
+    pub group_by_host: bool,
+}
+
+/// The matcher built from the search text of the request list.
+enum RequestSearch {
+    Everything,
+    Text(String),
+    Pattern(Regex),
+}
+
+impl RequestSearch {
+    /// Creates a matcher from the given options, returning an error for an invalid pattern.
+    ///
+    /// # Arguments:
+    /// * `options` - The current RequestListOptions.
+    fn new(options: &RequestListOptions) -> Result<Self, regex::Error> {
+        if options.search.is_empty() {
+            return Ok(RequestSearch::Everything);
+        }
+
+        if options.regex {
+            RegexBuilder::new(&options.search)
+                .case_insensitive(true)
+                .build()
+                .map(RequestSearch::Pattern)
+        } else {
+            Ok(RequestSearch::Text(options.search.to_lowercase()))
+        }
+    }
+
+    /// Returns whether the given request matches the search.
+    ///
+    /// # Arguments:
+    /// * `request` - The ProxyRequestLog to check.
+    fn is_match(&self, request: &ProxyRequestLog) -> bool {
+        match self {
+            RequestSearch::Everything => true,
+            RequestSearch::Text(text) => request.request.to_lowercase().contains(text),
+            RequestSearch::Pattern(pattern) => pattern.is_match(&request.request),
+        }
+    }
+}
+
+/// Contains the requests made to a single host.
+#[derive(Clone, Debug)]
+struct RequestGroup {
+    host: String,
+    count: usize,
+    blocked: usize,
+    would_block: usize,
+    latest: usize,
+}
+
+/// The computed rows of the request list, kept between frames so only new requests trigger work.
+#[derive(Default)]
+struct RequestListView {
+    options: RequestListOptions,
+    search: Option<RequestSearch>,
+    request_count: usize,
+    outcome_changes: usize,
+    /// The positions of the shown requests, in the order of the sort before it's reversed.
+    rows: Vec<usize>,
+    host_counts: HashMap<String, usize>,
+    groups: Vec<RequestGroup>,
+    group_positions: HashMap<String, usize>,
+    /// The positions of the groups, in the order of the sort before it's reversed.
+    group_order: Vec<usize>,
+    methods: BTreeSet<String>,
+    error: Option<String>,
+}
+
+impl RequestListView {
+    /// Creates an empty view for the given options.
+    ///
+    /// # Arguments:
+    /// * `options` - The RequestListOptions to apply.
+    /// * `outcome_changes` - The number of recorded requests whose outcome has changed so far.
+    fn new(options: &RequestListOptions, outcome_changes: usize) -> Self {
+        let (search, error) = match RequestSearch::new(options) {
+            Ok(search) => (Some(search), None),
+            Err(error) => (None, Some(error.to_string())),
+        };
+
+        Self {
+            options: options.clone(),
+            search,
+            outcome_changes,
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Brings the view up to date, starting over only when the options change or recorded requests changed outcome.
+    ///
+    /// # Arguments:
+    /// * `requests` - The requests made through the Proxy.
+    /// * `options` - The RequestListOptions to apply.
+    /// * `outcome_changes` - The number of recorded requests whose outcome has changed so far.
+    fn update(
+        &mut self,
+        requests: &[ProxyRequestLog],
+        options: &RequestListOptions,
+        outcome_changes: usize,
+    ) {
+        // Only the outcome filter and the group counts depend on the outcome of recorded requests
+        let outcome_changed = self.outcome_changes != outcome_changes
+            && (options.outcome != RequestOutcome::Any || options.group_by_host);
+
+        if self.options != *options || outcome_changed || self.request_count > requests.len() {
+            *self = Self::new(options, outcome_changes);
+        }
+
+        if self.request_count < requests.len() {
+            self.add(requests);
+        }
+    }
+
+    /// Filters the requests recorded since the last update, adding them to the rows or groups they belong in.
+    ///
+    /// # Arguments:
+    /// * `requests` - The requests made through the Proxy.
+    fn add(&mut self, requests: &[ProxyRequestLog]) {
+        let start = self.request_count;
+        self.request_count = requests.len();
+
+        let mut added = false;
+        for (index, request) in requests.iter().enumerate().skip(start) {
+            if !self.methods.contains(&request.method) {
+                self.methods.insert(request.method.clone());
+            }
+
+            if !self.is_match(request) {
+                continue;
+            }
+
+            added = true;
+            if self.options.group_by_host {
+                self.add_to_group(index, request);
+                continue;
+            }
+
+            *self.host_counts.entry(request.host.clone()).or_default() += 1;
+            match self.options.sort {
+                // Requests are recorded in order, so they're already sorted by time
+                RequestSort::Time | RequestSort::Count => self.rows.push(index),
+                RequestSort::Host => {
+                    let position = self
+                        .rows
+                        .partition_point(|row| requests[*row].host <= request.host);
+                    self.rows.insert(position, index);
+                }
+            }
+        }
+
+        if !added {
+            return;
+        }
+
+        // The counts of every row of a host change along with it, so these are sorted again
+        if self.options.group_by_host {
+            let groups = &self.groups;
+            self.group_order = (0..groups.len()).collect();
+            match self.options.sort {
+                RequestSort::Time => self.group_order.sort_by_key(|group| groups[*group].latest),
+                RequestSort::Host => self
+                    .group_order
+                    .sort_by(|a, b| groups[*a].host.cmp(&groups[*b].host)),
+                RequestSort::Count => self
+                    .group_order
+                    .sort_by_key(|group| (groups[*group].count, groups[*group].latest)),
+            }
+        } else if self.options.sort == RequestSort::Count {
+            let host_counts = &self.host_counts;
+            self.rows
+                .sort_unstable_by_key(|row| (host_counts[requests[*row].host.as_str()], *row));
+        }
+    }
+
+    /// Returns whether a request passes the method, outcome and search filters.
+    fn is_match(&self, request: &ProxyRequestLog) -> bool {
+        let Some(search) = &self.search else {
+            return false;
+        };
+
+        let method_matches = self
+            .options
+            .method
+            .as_ref()
+            .is_none_or(|method| &request.method == method);
+        let outcome_matches = match self.options.outcome {
+            RequestOutcome::Any => true,
+            RequestOutcome::Allowed => !request.blocked,
+            RequestOutcome::Blocked => request.blocked,
+            RequestOutcome::WouldBlock => request.would_block,
+        };
+
+        method_matches && outcome_matches && search.is_match(request)
+    }
+
+    /// Counts a shown request in the group of its host, starting the group if it's the first.
+    fn add_to_group(&mut self, index: usize, request: &ProxyRequestLog) {
+        let position = match self.group_positions.get(&request.host) {
+            Some(position) => *position,
+            None => {
+                self.groups.push(RequestGroup {
+                    host: request.host.clone(),
+                    count: 0,
+                    blocked: 0,
+                    would_block: 0,
+                    latest: index,
+                });
+                self.group_positions
+                    .insert(request.host.clone(), self.groups.len() - 1);
+                self.groups.len() - 1
+            }
+        };
+
+        let group = &mut self.groups[position];
+        group.count += 1;
+        group.blocked += usize::from(request.blocked);
+        group.would_block += usize::from(request.would_block);
+        group.latest = index;
+    }
+
+    /// Returns the number of rows to show.
+    fn len(&self) -> usize {
+        if self.options.group_by_host {
+            self.group_order.len()
+        } else {
+            self.rows.len()
+        }
+    }
+
+    /// Returns where a shown row is in the sorted rows or groups, counting from the end when descending.
+    fn position(&self, row: usize) -> usize {
+        if self.options.descending {
+            self.len() - 1 - row
+        } else {
+            row
+        }
+    }
+}
+
+/// Returns the text cut to the given number of characters, followed by an ellipsis when it was cut.
+///
+/// # Arguments:
+/// * `text` - The text to cut.
+/// * `max_chars` - The most characters to keep.
+pub fn truncate_text(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Shows the request list, along with its search, filter, sort and grouping options.
+pub fn request_list(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let options_id = egui::Id::new("request_list_options");
+    let view_id = egui::Id::new("request_list_view");
+
+    let mut options = ui.data_mut(|data| {
+        data.get_persisted::<RequestListOptions>(options_id)
+            .unwrap_or_default()
+    });
+    let view = ui.data_mut(|data| {
+        data.get_temp_mut_or_default::<Arc<Mutex<RequestListView>>>(view_id)
+            .clone()
+    });
+    let mut view = view.lock().unwrap();
+
+    let methods = view.methods.iter().cloned().collect::<Vec<_>>();
+    request_list_options(&mut options, &methods, ui);
+
+    view.update(
+        &proxy.requests.lock().unwrap(),
+        &options,
+        proxy.get_outcome_changes(),
+    );
+
+    ui.horizontal(|ui| {
+        if let Some(error) = &view.error {
+            ui.label(
+                RichText::new(format!("Invalid search pattern: {}", error))
+                    .size(11.0)
+                    .color(Color32::LIGHT_RED),
+            );
+        } else {
+            ui.label(
+                RichText::new(format!(
+                    "Showing {} {} from {} requests",
+                    view.len(),
+                    if options.group_by_host {
+                        "hosts"
+                    } else {
+                        "requests"
+                    },
+                    view.request_count
+                ))
+                .size(11.0),
+            );
+        }
+    });
+
+    ui.push_id("request_logs_scrollarea", |ui| {
+        egui::ScrollArea::new([true, true])
+            .auto_shrink([false, false])
+            .max_height(ui.available_height())
+            .show_rows(ui, ROW_HEIGHT, view.len(), |ui, row_range| {
+                if options.group_by_host {
+                    let groups: Vec<&RequestGroup> = row_range
+                        .map(|row| &view.groups[view.group_order[view.position(row)]])
+                        .collect();
+                    let latest: Vec<usize> = groups.iter().map(|group| group.latest).collect();
+                    let latest = proxy.get_requests_by_index(&latest);
+
+                    for (group, request) in groups.into_iter().zip(latest) {
+                        request_group_row(proxy, group, &request, ui);
+                    }
+                } else {
+                    let rows: Vec<usize> =
+                        row_range.map(|row| view.rows[view.position(row)]).collect();
+
+                    for request in proxy.get_requests_by_index(&rows) {
+                        request_row(proxy, &request, ui);
+                    }
+                }
+            });
+    });
+
+    ui.data_mut(|data| data.insert_persisted(options_id, options));
+}
+
+/// Shows the search, filter, sort and grouping options of the request list.
+///
+/// # Arguments:
+/// * `options` - The RequestListOptions to edit.
+/// * `methods` - The request methods seen so far.
+fn request_list_options(options: &mut RequestListOptions, methods: &[String], ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut options.regex, "Regex");
+        ui.add(
+            TextEdit::singleline(&mut options.search)
+                .hint_text(if options.regex {
+                    "Search requests by pattern, e.g. ^CONNECT"
+                } else {
+                    "Search requests, e.g. google.com"
+                })
+                .min_size(Vec2 {
+                    x: ui.available_width(),
+                    y: 18.,
+                }),
+        );
+    });
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::new("request_list_method", "")
+            .selected_text(options.method.clone().unwrap_or(String::from("Any Method")))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut options.method, None, "Any Method");
+                for method in methods {
+                    ui.selectable_value(&mut options.method, Some(method.clone()), method);
+                }
+            });
+
+        egui::ComboBox::new("request_list_outcome", "")
+            .selected_text(options.outcome.to_string())
+            .show_ui(ui, |ui| {
+                for outcome in [
+                    RequestOutcome::Any,
+                    RequestOutcome::Allowed,
+                    RequestOutcome::Blocked,
+                    RequestOutcome::WouldBlock,
+                ] {
+                    ui.selectable_value(&mut options.outcome, outcome, outcome.to_string());
+                }
+            });
+
+        egui::ComboBox::new("request_list_sort", "")
+            .selected_text(format!("Sort by {}", options.sort))
+            .show_ui(ui, |ui| {
+                for sort in [RequestSort::Time, RequestSort::Host, RequestSort::Count] {
+                    ui.selectable_value(&mut options.sort, sort, sort.to_string());
+                }
+            });
+
+        ui.checkbox(&mut options.descending, "Descending");
+        ui.checkbox(&mut options.group_by_host, "Group by Host");
+    });
+}
+
+/// Shows the Block/Unblock button and outcome label of a request list row.
+///
+/// # Arguments:
+/// * `proxy` - The Proxy to update the exclusion list of.
+/// * `value` - The value to add to/remove from the exclusion list.
+/// * `blocked` - Whether the value is currently blocked.
+/// * `would_block` - Whether the value would be blocked, if it wasn't audited.
+fn exclusion_button(
+    proxy: &mut Proxy,
+    value: &str,
+    blocked: bool,
+    would_block: bool,
+    ui: &mut egui::Ui,
+) {
+    let exclusion_values = if blocked {
+        (
+            "Unblock",
+            "Blocked",
+            Color32::LIGHT_RED,
+            ProxyExclusionUpdateKind::Remove,
+        )
+    } else if would_block {
+        (
+            "Block",
+            "Would Block",
+            WOULD_BLOCK_COLOR,
+            ProxyExclusionUpdateKind::Add,
+        )
+    } else {
+        (
+            "Block",
+            "Allowed",
+            Color32::LIGHT_GREEN,
+            ProxyExclusionUpdateKind::Add,
+        )
+    };
+
+    if ui.button(exclusion_values.0).clicked() {
+        proxy.selected_value = value.to_string();
+        proxy.update_exclusion_list(exclusion_values.3);
+    }
+
+    ui.label(RichText::new(exclusion_values.1).color(exclusion_values.2));
+}
+
+/// Shows a single request in the request list.
+fn request_row(proxy: &mut Proxy, request: &ProxyRequestLog, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.set_height(ROW_HEIGHT);
+
+        let uri_truncated = truncate_text(&request.request, 35);
+
+        ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+            ui.label(
+                RichText::new(request.timestamp.format("%H:%M:%S").to_string())
+                    .color(Color32::GRAY)
+                    .size(11.),
+            );
+            ui.label(
+                RichText::new(&request.method)
+                    .color(Color32::LIGHT_BLUE)
+                    .size(13.),
+            );
+            let selected = proxy.selected_request == Some(request.id);
+            if ui
+                .selectable_label(selected, uri_truncated)
+                .on_hover_text_at_pointer(&request.request)
+                .clicked()
+            {
+                proxy.selected_request = Some(request.id);
+            }
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                exclusion_button(
+                    proxy,
+                    &request.request,
+                    request.blocked,
+                    request.would_block,
+                    ui,
+                );
+
+                if request.blocked {
+                    snooze_button(proxy, &request.host, ui);
+                }
+
+                if let Some(action) = &request.action {
+                    ui.label(RichText::new("ACTION").color(Color32::LIGHT_BLUE).size(11.))
+                        .on_hover_text_at_pointer(action);
+                }
+
+                if let Some(reason) = &request.rate_limit {
+                    ui.label(RichText::new("LIMIT").color(Color32::GOLD).size(11.))
+                        .on_hover_text_at_pointer(format!("Rate limited: {}", reason));
+                }
+
+                if let Some(status) = request.cache {
+                    let color = match status {
+                        ProxyCacheStatus::Hit => Color32::LIGHT_GREEN,
+                        ProxyCacheStatus::Miss => Color32::GRAY,
+                        ProxyCacheStatus::Revalidated => Color32::LIGHT_BLUE,
+                    };
+                    ui.label(
+                        RichText::new(status.to_string().to_uppercase())
+                            .color(color)
+                            .size(11.),
+                    )
+                    .on_hover_text_at_pointer(format!("Cache: {}", status));
+                }
+
+                if let Some(fault) = &request.fault {
+                    ui.label(RichText::new("FAULT").color(Color32::LIGHT_RED).size(11.))
+                        .on_hover_text_at_pointer(format!("Injected fault: {}", fault));
+                }
+
+                if let Some(path) = &request.mapped_local {
+                    ui.label(RichText::new("LOCAL").color(Color32::LIGHT_GREEN).size(11.))
+                        .on_hover_text_at_pointer(format!("Mapped locally to: {}", path));
+                }
+
+                if request.server_name_mismatch() {
+                    ui.label(RichText::new("SNI").color(Color32::YELLOW).size(11.))
+                        .on_hover_text_at_pointer(format!(
+                            "Server name: {}",
+                            request.server_name.as_deref().unwrap_or_default()
+                        ));
+                }
+            });
+        });
+    });
+}
+
+/// Shows the requests made to a single host in the request list.
+///
+/// # Arguments:
+/// * `proxy` - The Proxy to update the exclusion list of.
+/// * `group` - The RequestGroup to show.
+/// * `latest` - The most recent request in the group, whose outcome is shown.
+/// * `ui` - The Ui to show the row in.
+fn request_group_row(
+    proxy: &mut Proxy,
+    group: &RequestGroup,
+    latest: &ProxyRequestLog,
+    ui: &mut egui::Ui,
+) {
+    ui.horizontal(|ui| {
+        ui.set_height(ROW_HEIGHT);
+
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            exclusion_button(proxy, &group.host, latest.blocked, latest.would_block, ui);
+
+            if latest.blocked {
+                snooze_button(proxy, &group.host, ui);
+            }
+
+            ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                ui.label(
+                    RichText::new(format!("{}x", group.count))
+                        .color(Color32::LIGHT_BLUE)
+                        .size(13.),
+                );
+                ui.add(egui::Label::new(&group.host).truncate())
+                    .on_hover_text_at_pointer(format!(
+                        "{} requests, {} blocked, {} would be blocked",
+                        group.count, group.blocked, group.would_block
+                    ));
+            });
+        });
+    });
+}
@@ -0,0 +1,175 @@
+use crate::service::{proxy::Proxy, traffic_filter::TrafficRule};
+use chrono::{DateTime, Local};
+use eframe::{
+    egui::{self, RichText},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the unused rules window open.
+pub const RULE_CLEANUP_ID: &str = "rule_cleanup";
+
+/// The number of days without a hit after which a rule is shown as unused, until it's changed.
+const DEFAULT_UNUSED_DAYS: i64 = 30;
+
+/// Shows the hit count and last hit of an exclusion list item, as columns of its row.
+///
+/// Expects a right to left layout, so the last hit is shown rightmost.
+pub fn rule_hits_columns(rule: &TrafficRule, ui: &mut egui::Ui) {
+    let now = Local::now();
+    let hover_text = match rule.hits.last_hit {
+        Some(last_hit) => format!(
+            "Matched {} requests\nLast: {}\n{}",
+            rule.hits.count,
+            last_hit.format("%Y-%m-%d %H:%M:%S"),
+            rule.hits.last_uri
+        ),
+        None => String::from("Hasn't matched a request yet"),
+    };
+
+    ui.add_sized(
+        [60., 18.],
+        egui::Label::new(
+            RichText::new(format_last_hit(rule.hits.last_hit, now))
+                .color(Color32::GRAY)
+                .size(11.),
+        ),
+    )
+    .on_hover_text_at_pointer(&hover_text);
+
+    ui.add_sized(
+        [45., 18.],
+        egui::Label::new(
+            RichText::new(rule.hits.count.to_string())
+                .color(match rule.hits.count {
+                    0 => Color32::GRAY,
+                    _ => Color32::LIGHT_BLUE,
+                })
+                .size(11.),
+        ),
+    )
+    .on_hover_text_at_pointer(&hover_text);
+}
+
+/// Shows the exclusion list items that haven't matched a request in a number of days, so they can be removed.
+pub fn rule_cleanup(proxy: &mut Proxy, ctx: &egui::Context) {
+    let window_id = egui::Id::new(RULE_CLEANUP_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(window_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let days_id = window_id.with("days");
+    let mut days = ctx.memory_mut(|m| {
+        m.data
+            .get_temp::<i64>(days_id)
+            .unwrap_or(DEFAULT_UNUSED_DAYS)
+    });
+
+    let traffic_filter = proxy.get_traffic_filter();
+    let exclusion_list = traffic_filter.get_filter_list();
+    let now = Local::now();
+    let mut removed = Vec::new();
+
+    egui::Window::new("Unused Rules")
+        .id(window_id)
+        .open(&mut open)
+        .default_size([520., 320.])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Rules in the {} with no hits in the last",
+                    traffic_filter.get_profile_name()
+                ));
+                ui.add(egui::DragValue::new(&mut days).range(1..=3650));
+                ui.label("days");
+            });
+            ui.add_space(4.);
+
+            let unused = exclusion_list
+                .iter()
+                .filter(|rule| rule.is_unused(days, now))
+                .collect::<Vec<_>>();
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    egui::Grid::new("rule_cleanup_grid")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["Rule", "Hits", "Last Hit", ""] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            for rule in unused.iter() {
+                                ui.add(egui::Label::new(&rule.pattern).truncate());
+                                ui.label(rule.hits.count.to_string());
+                                ui.label(format_last_hit(rule.hits.last_hit, now));
+
+                                if ui.button("Remove").clicked() {
+                                    removed.push((*rule).clone());
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if unused.is_empty() {
+                        ui.label("Every rule has matched a request recently.");
+                    }
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !unused.is_empty(),
+                        egui::Button::new(format!("Remove All ({})", unused.len())),
+                    )
+                    .clicked()
+                {
+                    removed.extend(unused.iter().map(|rule| (*rule).clone()));
+                }
+
+                if ui
+                    .button("Reset Hit Counters")
+                    .on_hover_text("Start counting the hits of every rule in this list again.")
+                    .clicked()
+                {
+                    proxy.reset_rule_hits();
+                }
+            });
+        });
+
+    if !removed.is_empty() {
+        let message = format!("Removed {} unused rules", removed.len());
+        proxy.set_exclusion_list(
+            exclusion_list
+                .into_iter()
+                .filter(|rule| !removed.contains(rule))
+                .collect(),
+        );
+        proxy.logger.info(&message);
+    }
+
+    ctx.memory_mut(|m| {
+        m.data.insert_temp(window_id, open);
+        m.data.insert_temp(days_id, days);
+    });
+}
+
+/// Returns how long ago a rule last matched a request, e.g. 5m ago, or never.
+fn format_last_hit(last_hit: Option<DateTime<Local>>, now: DateTime<Local>) -> String {
+    let Some(last_hit) = last_hit else {
+        return String::from("never");
+    };
+
+    match (now - last_hit).num_seconds() {
+        ..60 => String::from("just now"),
+        seconds @ ..3600 => format!("{}m ago", seconds / 60),
+        seconds @ ..86400 => format!("{}h ago", seconds / 3600),
+        seconds => format!("{}d ago", seconds / 86400),
+    }
+}
@@ -0,0 +1,356 @@
+use super::{request_list::WOULD_BLOCK_COLOR, schedule::schedule_label};
+use crate::service::{
+    proxy::{Proxy, ProxyRequestLog},
+    traffic_filter::{TrafficFilter, TrafficRule, TrafficRuleAction, TrafficRuleKind},
+};
+use chrono::{DateTime, Local};
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+use hyper::Uri;
+use std::sync::{Arc, Mutex};
+
+/// The identifier of the flag, in egui memory, that keeps the rule tester open.
+pub const RULE_TESTER_ID: &str = "rule_tester";
+
+/// The most past requests listed when hovering over a rule's impact.
+const IMPACT_LIST_LIMIT: usize = 20;
+
+/// The past requests matched by a rule, cached until the rule or the request history changes.
+#[derive(Clone, Default)]
+struct RuleImpact {
+    pattern: String,
+    kind: TrafficRuleKind,
+    request_count: usize,
+    matched: Arc<Vec<usize>>,
+    blocked: usize,
+    would_block: usize,
+}
+
+/// Shows how many past requests a rule matches, listing the most recent of them on hover.
+///
+/// # Arguments:
+/// * `id` - A unique identifier for the cached result.
+/// * `rule` - The rule to check the request history against.
+/// * `requests` - The requests made through the Proxy.
+/// * `is_blocking` - Whether the exclusion list in use allows the requests its rules match.
+/// * `ui` - The Ui to show the result in.
+pub fn rule_impact(
+    id: &str,
+    rule: &TrafficRule,
+    requests: &Mutex<Vec<ProxyRequestLog>>,
+    is_blocking: bool,
+    ui: &mut egui::Ui,
+) {
+    if rule.pattern.is_empty() || rule.validate().is_err() {
+        return;
+    }
+
+    let impact_id = egui::Id::new(id);
+    let mut impact = ui.data_mut(|data| data.get_temp::<RuleImpact>(impact_id).unwrap_or_default());
+
+    let requests = requests.lock().unwrap();
+    if impact.pattern != rule.pattern
+        || impact.kind != rule.kind
+        || impact.request_count != requests.len()
+    {
+        let matched: Vec<usize> = requests
+            .iter()
+            .enumerate()
+            .filter(|(_, request)| rule.matches(&request.request))
+            .map(|(index, _)| index)
+            .collect();
+
+        impact = RuleImpact {
+            pattern: rule.pattern.clone(),
+            kind: rule.kind,
+            request_count: requests.len(),
+            blocked: matched
+                .iter()
+                .filter(|index| requests[**index].blocked)
+                .count(),
+            would_block: matched
+                .iter()
+                .filter(|index| requests[**index].would_block)
+                .count(),
+            matched: Arc::new(matched),
+        };
+        ui.data_mut(|data| data.insert_temp(impact_id, impact.clone()));
+    }
+
+    let effect = match rule.action {
+        TrafficRuleAction::Filter if is_blocking => "allowed",
+        TrafficRuleAction::Filter => "blocked",
+        TrafficRuleAction::Allow => "allowed",
+        _ => "handled by its action",
+    };
+    let text = match impact.matched.len() {
+        0 => String::from("Would not have affected any past requests"),
+        count => format!(
+            "Would have affected {} of {} past requests, which are {} by it",
+            count, impact.request_count, effect
+        ),
+    };
+
+    let response = ui.label(RichText::new(text).color(Color32::GRAY).size(11.));
+    if impact.matched.is_empty() {
+        return;
+    }
+
+    response.on_hover_ui(|ui| {
+        ui.label(format!(
+            "{} of them were blocked, {} would have been.",
+            impact.blocked, impact.would_block
+        ));
+        ui.separator();
+
+        for index in impact.matched.iter().rev().take(IMPACT_LIST_LIMIT) {
+            let request = &requests[*index];
+            let color = match (request.blocked, request.would_block) {
+                (true, _) => Color32::LIGHT_RED,
+                (false, true) => WOULD_BLOCK_COLOR,
+                (false, false) => Color32::LIGHT_GREEN,
+            };
+
+            ui.label(
+                RichText::new(format!("{} {}", request.method, request.request))
+                    .color(color)
+                    .size(11.),
+            );
+        }
+
+        if impact.matched.len() > IMPACT_LIST_LIMIT {
+            ui.label(format!(
+                "and {} more...",
+                impact.matched.len() - IMPACT_LIST_LIMIT
+            ));
+        }
+    });
+}
+
+/// Shows the rule tester in a separate window, while it's open.
+///
+/// Decides pasted URLs with the current traffic filter, and checks new rules against the request history.
+pub fn rule_tester(proxy: &mut Proxy, ctx: &egui::Context) {
+    let tester_id = egui::Id::new(RULE_TESTER_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(tester_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let urls_id = tester_id.with("urls");
+    let rule_id = tester_id.with("rule");
+    let mut urls = ctx.memory_mut(|m| m.data.get_temp::<String>(urls_id).unwrap_or_default());
+    let (mut pattern, mut kind) = ctx.memory_mut(|m| {
+        m.data
+            .get_temp::<(String, TrafficRuleKind)>(rule_id)
+            .unwrap_or_default()
+    });
+
+    let traffic_filter = proxy.get_traffic_filter();
+    let now = Local::now();
+
+    egui::Window::new("Rule Tester")
+        .id(tester_id)
+        .open(&mut open)
+        .default_size([720., 420.])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Profile: {}", traffic_filter.get_profile_name()));
+
+                if !traffic_filter.get_enabled() {
+                    ui.label(
+                        RichText::new("Filtering is disabled")
+                            .color(Color32::LIGHT_RED)
+                            .size(11.),
+                    );
+                }
+
+                if let Some(schedule) = traffic_filter.get_schedule() {
+                    ui.label(format!("Schedule: {}", schedule.describe()));
+                    schedule_label(&schedule, ui);
+                }
+
+                if traffic_filter.get_audit() {
+                    ui.label(RichText::new("Audit").color(WOULD_BLOCK_COLOR).size(11.));
+                }
+            });
+
+            ui.add(
+                TextEdit::multiline(&mut urls)
+                    .hint_text("Paste URLs, or host:port addresses, one per line")
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY)
+                    .code_editor(),
+            );
+
+            egui::ScrollArea::vertical()
+                .id_source("rule_tester_results")
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 90.)
+                .show(ui, |ui| {
+                    egui::Grid::new("rule_tester_grid")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["URL", "Decision", "Rule", "Schedule"] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            for url in urls.lines().map(str::trim).filter(|url| !url.is_empty()) {
+                                decision_row(proxy, &traffic_filter, url, now, ui);
+                            }
+                        });
+                });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Try a rule:");
+                ui.add(
+                    TextEdit::singleline(&mut pattern)
+                        .hint_text("Pattern")
+                        .desired_width(260.),
+                );
+
+                egui::ComboBox::from_id_source("rule_tester_kind")
+                    .selected_text(kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in [TrafficRuleKind::Contains, TrafficRuleKind::Regex] {
+                            ui.selectable_value(&mut kind, option, option.to_string());
+                        }
+                    });
+
+                let mut candidate = TrafficRule::new(pattern.clone());
+                candidate.kind = kind;
+                let validation = candidate.validate();
+
+                if ui
+                    .add_enabled(
+                        !pattern.is_empty() && validation.is_ok(),
+                        egui::Button::new(format!("Add to {}", traffic_filter.get_profile_name())),
+                    )
+                    .clicked()
+                {
+                    let mut exclusion_list = traffic_filter.get_filter_list();
+                    exclusion_list.push(candidate.clone());
+                    proxy.set_exclusion_list(exclusion_list);
+                    pattern.clear();
+                }
+
+                if let Err(error) = validation {
+                    ui.label(RichText::new(error).color(Color32::LIGHT_RED).size(11.));
+                }
+            });
+
+            let mut candidate = TrafficRule::new(pattern.clone());
+            candidate.kind = kind;
+            rule_impact(
+                "rule_tester_impact",
+                &candidate,
+                &proxy.requests,
+                traffic_filter.is_blocking(),
+                ui,
+            );
+        });
+
+    ctx.memory_mut(|m| {
+        m.data.insert_temp(tester_id, open);
+        m.data.insert_temp(urls_id, urls);
+        m.data.insert_temp(rule_id, (pattern, kind));
+    });
+}
+
+/// Shows the decision the traffic filter makes for a single URL, as a row of the rule tester grid.
+///
+/// # Arguments:
+/// * `proxy` - The Proxy to check the temporary allows of.
+/// * `traffic_filter` - The TrafficFilter to decide the URL with.
+/// * `url` - The URL, or host:port address, to decide.
+/// * `now` - The local time to check the schedules against.
+/// * `ui` - The Ui to show the row in.
+fn decision_row(
+    proxy: &Proxy,
+    traffic_filter: &TrafficFilter,
+    url: &str,
+    now: DateTime<Local>,
+    ui: &mut egui::Ui,
+) {
+    let (blocked, rule) = traffic_filter.filter_request(url, now);
+    let host = url
+        .parse::<Uri>()
+        .ok()
+        .and_then(|uri| uri.host().map(String::from))
+        .unwrap_or_default();
+
+    let (decision, color, reason) = match &rule {
+        _ if !traffic_filter.get_enabled() => ("Allowed", Color32::GRAY, "Filtering is disabled."),
+        _ if !traffic_filter.is_scheduled(now) => (
+            "Allowed",
+            Color32::GRAY,
+            "The profile is outside its schedule.",
+        ),
+        Some(rule) if rule.action == TrafficRuleAction::Allow => (
+            "Allowed",
+            Color32::LIGHT_GREEN,
+            "The matching rule always allows it.",
+        ),
+        Some(rule) if rule.action != TrafficRuleAction::Filter => (
+            "Action",
+            Color32::LIGHT_BLUE,
+            "The matching rule's action handles it.",
+        ),
+        _ if blocked && proxy.temporary_allows.is_allowed(&host, now) => (
+            "Allowed",
+            Color32::LIGHT_GREEN,
+            "Its host is temporarily allowed.",
+        ),
+        _ if blocked && traffic_filter.is_audited(rule.as_ref()) => (
+            "Would Block",
+            WOULD_BLOCK_COLOR,
+            "Forwarded, as its profile or rule is in audit mode.",
+        ),
+        _ if blocked => ("Blocked", Color32::LIGHT_RED, ""),
+        _ => ("Allowed", Color32::LIGHT_GREEN, ""),
+    };
+
+    ui.add(egui::Label::new(url).truncate());
+
+    let response = ui.label(RichText::new(decision).color(color));
+    if !reason.is_empty() {
+        response.on_hover_text(reason);
+    }
+
+    // A profile that isn't filtering doesn't apply its rules, though they may still match
+    let matching = rule
+        .clone()
+        .or_else(|| traffic_filter.get_active_rule(url, now));
+
+    match (&rule, &matching) {
+        (Some(rule), _) => {
+            let description = rule
+                .describe_action(url)
+                .unwrap_or_else(|| rule.kind.to_string());
+            ui.label(&rule.pattern).on_hover_text(description);
+        }
+        (None, Some(matching)) => {
+            ui.label(RichText::new(&matching.pattern).color(Color32::GRAY))
+                .on_hover_text("Matches, but the profile isn't filtering.");
+        }
+        (None, None) => {
+            let text = match traffic_filter.get_matching_rule(url) {
+                Some(inactive) => format!("{} (outside its schedule)", inactive.pattern),
+                None => String::from("No matching rule"),
+            };
+            ui.label(RichText::new(text).color(Color32::GRAY));
+        }
+    }
+
+    match matching.as_ref().and_then(|rule| rule.schedule.as_ref()) {
+        Some(schedule) => ui.label(schedule.describe()),
+        None => ui.label(RichText::new("Always").color(Color32::GRAY)),
+    };
+    ui.end_row();
+}
@@ -0,0 +1,142 @@
+use crate::service::schedule::{TrafficSchedule, WEEKDAYS};
+use chrono::{Duration, Local, NaiveTime, Timelike};
+use eframe::{
+    egui::{self, RichText},
+    epaint::Color32,
+};
+use egui_extras::DatePickerButton;
+
+/// How often the Filter view is redrawn while schedules are shown, so their state stays current.
+const SCHEDULE_REFRESH_SECONDS: u64 = 30;
+
+/// Shows whether a schedule is active right now, with its description on hover.
+pub fn schedule_label(schedule: &TrafficSchedule, ui: &mut egui::Ui) {
+    let now = Local::now();
+    let (text, color) = match (schedule.is_active(now), schedule.is_expired(now)) {
+        (true, _) => ("Active", Color32::LIGHT_GREEN),
+        (false, true) => ("Expired", Color32::LIGHT_RED),
+        (false, false) => ("Inactive", Color32::GRAY),
+    };
+
+    ui.label(RichText::new(text).color(color).size(11.))
+        .on_hover_text_at_pointer(format!("Scheduled: {}", schedule.describe()));
+
+    ui.ctx()
+        .request_repaint_after(std::time::Duration::from_secs(SCHEDULE_REFRESH_SECONDS));
+}
+
+/// Shows the editable fields of an optional schedule.
+///
+/// # Arguments:
+/// * `id` - A unique identifier for the editor's widgets.
+/// * `schedule` - The schedule to edit, None when it always applies.
+/// * `ui` - The Ui to show the editor in.
+pub fn schedule_editor(id: &str, schedule: &mut Option<TrafficSchedule>, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        let mut scheduled = schedule.is_some();
+        if ui
+            .checkbox(&mut scheduled, "Schedule")
+            .on_hover_text("Only apply at certain times, judged in local time.")
+            .changed()
+        {
+            *schedule = scheduled.then(TrafficSchedule::default);
+        }
+
+        if ui.button("Working Hours").clicked() {
+            *schedule = Some(TrafficSchedule::working_hours());
+        }
+
+        for (label, minutes) in [("Next 30 Minutes", 30), ("Next Hour", 60)] {
+            if ui.button(label).clicked() {
+                *schedule = Some(TrafficSchedule::for_minutes(minutes));
+            }
+        }
+    });
+
+    let Some(schedule) = schedule.as_mut() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        for (day, active) in WEEKDAYS.iter().zip(schedule.days.iter_mut()) {
+            ui.toggle_value(active, *day);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let mut limited = schedule.hours.is_some();
+        if ui.checkbox(&mut limited, "Between").changed() {
+            schedule.hours = match limited {
+                true => TrafficSchedule::working_hours().hours,
+                false => None,
+            };
+        }
+
+        if let Some((start, end)) = schedule.hours.as_mut() {
+            time_edit(start, ui);
+            ui.label("and");
+            time_edit(end, ui);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let mut limited = schedule.dates.is_some();
+        if ui.checkbox(&mut limited, "From").changed() {
+            let today = Local::now().date_naive();
+            schedule.dates = limited.then(|| (today, today + Duration::days(7)));
+        }
+
+        if let Some((from, until)) = schedule.dates.as_mut() {
+            ui.add(DatePickerButton::new(from).id_source(&format!("{}_from", id)));
+            ui.label("to");
+            ui.add(DatePickerButton::new(until).id_source(&format!("{}_until", id)));
+
+            if *until < *from {
+                *until = *from;
+            }
+        }
+    });
+
+    if let Some(expires) = schedule.expires {
+        ui.horizontal(|ui| {
+            ui.label(format!("Until {}", expires.format("%Y-%m-%d %H:%M")));
+
+            if ui.button("+30 Minutes").clicked() {
+                let start = expires.max(Local::now());
+                schedule.expires = Some(start + Duration::minutes(30));
+            }
+
+            if ui.button("Clear").clicked() {
+                schedule.expires = None;
+            }
+        });
+    }
+
+    ui.label(
+        RichText::new(schedule.describe())
+            .color(Color32::GRAY)
+            .size(11.),
+    );
+}
+
+/// Shows the hour and minute of a time of day.
+fn time_edit(time: &mut NaiveTime, ui: &mut egui::Ui) {
+    let mut hour = time.hour();
+    let mut minute = time.minute();
+
+    ui.add(
+        egui::DragValue::new(&mut hour)
+            .range(0..=23)
+            .custom_formatter(|value, _| format!("{:02}", value as u32)),
+    );
+    ui.label(":");
+    ui.add(
+        egui::DragValue::new(&mut minute)
+            .range(0..=59)
+            .custom_formatter(|value, _| format!("{:02}", value as u32)),
+    );
+
+    if let Some(changed) = NaiveTime::from_hms_opt(hour, minute, 0) {
+        *time = changed;
+    }
+}
@@ -0,0 +1,102 @@
+use crate::service::{proxy::Proxy, snooze::SnoozeDuration};
+use chrono::{Duration, Local};
+use eframe::{
+    egui::{self, Layout, RichText},
+    emath::Align,
+    epaint::Color32,
+};
+
+/// The durations offered when temporarily allowing a blocked host.
+const SNOOZE_DURATIONS: [SnoozeDuration; 3] = [
+    SnoozeDuration::FiveMinutes,
+    SnoozeDuration::OneHour,
+    SnoozeDuration::UntilRestart,
+];
+
+/// Shows the menu that temporarily allows a blocked host, next to a blocked request.
+///
+/// # Arguments:
+/// * `proxy` - The Proxy to allow the host in.
+/// * `host` - The host of the blocked request.
+/// * `ui` - The Ui to show the menu in.
+pub fn snooze_button(proxy: &mut Proxy, host: &str, ui: &mut egui::Ui) {
+    if host.is_empty() {
+        return;
+    }
+
+    ui.menu_button("Allow for", |ui| {
+        for duration in SNOOZE_DURATIONS {
+            if ui.button(duration.to_string()).clicked() {
+                proxy.allow_temporarily(host, duration);
+                ui.close_menu();
+            }
+        }
+    })
+    .response
+    .on_hover_text(format!(
+        "Let {} through for a while, without changing the exclusion list.",
+        host
+    ));
+}
+
+/// Shows the hosts temporarily allowed through the traffic filter, with the time each has left.
+pub fn temporary_allow_list(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let allows = proxy.get_temporary_allows();
+    if allows.is_empty() {
+        return;
+    }
+
+    let now = Local::now();
+
+    egui::CollapsingHeader::new(format!("Temporarily Allowed ({})", allows.len()))
+        .default_open(true)
+        .show_unindented(ui, |ui| {
+            ui.group(|ui| {
+                egui::Grid::new("temporary_allow_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for allow in allows.iter() {
+                            ui.label(RichText::new(&allow.host).color(Color32::LIGHT_GREEN));
+
+                            let remaining = match allow.remaining(now) {
+                                Some(remaining) => format_countdown(remaining),
+                                None => String::from("until restart"),
+                            };
+                            ui.label(RichText::new(remaining).color(Color32::GRAY).size(11.))
+                                .on_hover_text(format!("Allowed for {}", allow.duration));
+
+                            ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                                if ui
+                                    .button("Revoke")
+                                    .on_hover_text("Block the host again now.")
+                                    .clicked()
+                                {
+                                    proxy.revoke_temporary_allow(&allow.host);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    // Keep the countdowns ticking, and drop each allow from the list as it expires
+    ui.ctx()
+        .request_repaint_after(std::time::Duration::from_secs(1));
+}
+
+/// Returns the time left as a countdown, e.g. 4:59 left or 1:00:00 left.
+fn format_countdown(remaining: Duration) -> String {
+    let seconds = remaining.num_seconds();
+
+    match seconds >= 3600 {
+        true => format!(
+            "{}:{:02}:{:02} left",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        ),
+        false => format!("{}:{:02} left", seconds / 60, seconds % 60),
+    }
+}
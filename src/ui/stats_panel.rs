@@ -0,0 +1,358 @@
+use super::request_inspector::format_size;
+use crate::service::{
+    proxy::Proxy,
+    stats::{ProxyStats, ProxyStatsWindow},
+    timeout::ProxyTimeoutCounts,
+};
+use eframe::{
+    egui::{self, pos2, vec2, Layout, Rect, RichText, Sense},
+    emath::Align,
+    epaint::Color32,
+};
+
+/// How often, in seconds, the statistics are recalculated.
+const STATS_REFRESH_SECONDS: f64 = 1.;
+
+/// The statistics shown in the last frame, kept so they aren't recalculated every frame.
+#[derive(Clone)]
+struct CachedStats {
+    window: ProxyStatsWindow,
+    calculated_at: f64,
+    stats: ProxyStats,
+}
+
+pub fn stats_panel(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let window_id = egui::Id::new("stats_window");
+    let cache_id = egui::Id::new("stats_cache");
+
+    let mut window = ui.data_mut(|data| {
+        data.get_persisted::<ProxyStatsWindow>(window_id)
+            .unwrap_or_default()
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Show traffic from:");
+        egui::ComboBox::new("stats_window_options", "")
+            .selected_text(window.to_string())
+            .show_ui(ui, |ui| {
+                for option in [
+                    ProxyStatsWindow::FiveMinutes,
+                    ProxyStatsWindow::Hour,
+                    ProxyStatsWindow::Session,
+                ] {
+                    ui.selectable_value(&mut window, option, option.to_string());
+                }
+            });
+    });
+
+    let now = ui.input(|input| input.time);
+    let cached = ui.data_mut(|data| data.get_temp::<CachedStats>(cache_id));
+    let stats = match cached {
+        Some(cached)
+            if cached.window == window && now - cached.calculated_at < STATS_REFRESH_SECONDS =>
+        {
+            cached.stats
+        }
+        _ => {
+            let stats = proxy.get_stats(window);
+            ui.data_mut(|data| {
+                data.insert_temp(
+                    cache_id,
+                    CachedStats {
+                        window,
+                        calculated_at: now,
+                        stats: stats.clone(),
+                    },
+                )
+            });
+            stats
+        }
+    };
+
+    ui.data_mut(|data| data.insert_persisted(window_id, window));
+    ui.ctx()
+        .request_repaint_after(std::time::Duration::from_secs_f64(STATS_REFRESH_SECONDS));
+
+    ui.add_space(5.);
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            ui.group(|ui| {
+                egui::Grid::new("stats_summary")
+                    .num_columns(4)
+                    .spacing([20., 4.])
+                    .show(ui, |ui| {
+                        stat_label(
+                            ui,
+                            "Requests:",
+                            stats.total.to_string(),
+                            Color32::LIGHT_BLUE,
+                        );
+                        stat_label(
+                            ui,
+                            "Peak Rate:",
+                            format!("{:.1}/s", stats.peak_rate()),
+                            Color32::LIGHT_BLUE,
+                        );
+                        ui.end_row();
+
+                        stat_label(
+                            ui,
+                            "Allowed:",
+                            stats.allowed.to_string(),
+                            Color32::LIGHT_GREEN,
+                        );
+                        stat_label(
+                            ui,
+                            "Blocked:",
+                            stats.blocked.to_string(),
+                            Color32::LIGHT_RED,
+                        );
+                        ui.end_row();
+
+                        stat_label(
+                            ui,
+                            "Error Rate:",
+                            format!("{:.1}%", stats.error_rate()),
+                            Color32::YELLOW,
+                        );
+                        stat_label(ui, "Failed:", stats.failed.to_string(), Color32::YELLOW);
+                        ui.end_row();
+
+                        stat_label(
+                            ui,
+                            "4xx Responses:",
+                            stats.client_errors.to_string(),
+                            Color32::YELLOW,
+                        );
+                        stat_label(
+                            ui,
+                            "5xx Responses:",
+                            stats.server_errors.to_string(),
+                            Color32::YELLOW,
+                        );
+                        ui.end_row();
+                    });
+            });
+
+            ui.add_space(5.);
+
+            ui.group(|ui| {
+                ui.label(RichText::new("Requests per Second").strong());
+                requests_chart(&stats, ui);
+            });
+
+            ui.add_space(5.);
+
+            ui.columns(2, |columns| {
+                host_list(
+                    &mut columns[0],
+                    "Top Hosts",
+                    stats
+                        .top_hosts
+                        .iter()
+                        .map(|(host, count)| (host, count.to_string())),
+                );
+                host_list(
+                    &mut columns[1],
+                    "Top Hosts by Size",
+                    stats
+                        .top_hosts_by_bytes
+                        .iter()
+                        .map(|(host, bytes)| (host, format_size(*bytes))),
+                );
+            });
+
+            ui.add_space(5.);
+
+            ui.columns(2, |columns| {
+                host_list(
+                    &mut columns[0],
+                    "Top Blocked Hosts",
+                    stats
+                        .top_blocked_hosts
+                        .iter()
+                        .map(|(host, count)| (host, count.to_string())),
+                );
+                host_list(
+                    &mut columns[1],
+                    "Methods",
+                    stats
+                        .methods
+                        .iter()
+                        .map(|(method, count)| (method, count.to_string())),
+                );
+            });
+
+            ui.add_space(5.);
+
+            timeout_list(&proxy.get_timeout_stats(), ui);
+        });
+}
+
+/// Shows a single statistic as a name and a coloured value.
+fn stat_label(ui: &mut egui::Ui, name: &str, value: String, color: Color32) {
+    ui.label(name);
+    ui.label(RichText::new(value).color(color));
+}
+
+/// Shows a list of hosts, or other names, along with their values.
+fn host_list<'a>(
+    ui: &mut egui::Ui,
+    title: &str,
+    entries: impl ExactSizeIterator<Item = (&'a String, String)>,
+) {
+    ui.group(|ui| {
+        ui.set_width(ui.available_width());
+        ui.label(RichText::new(title).strong());
+
+        if entries.len() == 0 {
+            ui.label(RichText::new("No requests yet.").color(Color32::GRAY));
+            return;
+        }
+
+        egui::Grid::new(title)
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (name, value) in entries {
+                    ui.add(egui::Label::new(name).truncate())
+                        .on_hover_text_at_pointer(name);
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        ui.label(RichText::new(value).color(Color32::LIGHT_BLUE));
+                    });
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Shows the number of each kind of timeout that has fired, per host.
+fn timeout_list(hosts: &[(String, ProxyTimeoutCounts)], ui: &mut egui::Ui) {
+    ui.group(|ui| {
+        ui.set_width(ui.available_width());
+        ui.label(RichText::new("Timeouts by Host").strong());
+
+        if hosts.is_empty() {
+            ui.label(RichText::new("No timeouts yet.").color(Color32::GRAY));
+            return;
+        }
+
+        egui::Grid::new("timeout_hosts")
+            .num_columns(7)
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in [
+                    "Host",
+                    "Connect",
+                    "Headers",
+                    "Response",
+                    "Idle",
+                    "Tunnel Idle",
+                    "Lifetime",
+                ] {
+                    ui.label(RichText::new(heading).color(Color32::GRAY));
+                }
+                ui.end_row();
+
+                for (host, counts) in hosts.iter().take(10) {
+                    ui.add(egui::Label::new(host).truncate())
+                        .on_hover_text_at_pointer(host);
+
+                    for count in [
+                        counts.connect,
+                        counts.request_headers,
+                        counts.response,
+                        counts.idle,
+                        counts.tunnel_idle,
+                        counts.tunnel_lifetime,
+                    ] {
+                        ui.label(RichText::new(count.to_string()).color(match count {
+                            0 => Color32::GRAY,
+                            _ => Color32::YELLOW,
+                        }));
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Draws the allowed and blocked requests over time as a stacked bar chart.
+fn requests_chart(stats: &ProxyStats, ui: &mut egui::Ui) {
+    let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), 100.), Sense::hover());
+    let painter = ui.painter_at(rect);
+
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+
+    let peak = stats
+        .buckets
+        .iter()
+        .map(|bucket| bucket.allowed + bucket.blocked)
+        .max()
+        .unwrap_or_default()
+        .max(1) as f32;
+    let bar_width = rect.width() / stats.buckets.len().max(1) as f32;
+
+    for (index, bucket) in stats.buckets.iter().enumerate() {
+        let left = rect.left() + index as f32 * bar_width + 0.5;
+        let right = left + bar_width - 1.;
+        let allowed_height = bucket.allowed as f32 / peak * rect.height();
+        let blocked_height = bucket.blocked as f32 / peak * rect.height();
+
+        painter.rect_filled(
+            Rect::from_min_max(
+                pos2(left, rect.bottom() - allowed_height),
+                pos2(right, rect.bottom()),
+            ),
+            0.,
+            Color32::LIGHT_GREEN,
+        );
+        painter.rect_filled(
+            Rect::from_min_max(
+                pos2(left, rect.bottom() - allowed_height - blocked_height),
+                pos2(right, rect.bottom() - allowed_height),
+            ),
+            0.,
+            Color32::LIGHT_RED,
+        );
+    }
+
+    if let Some(position) = response.hover_pos() {
+        let index = ((position.x - rect.left()) / bar_width) as usize;
+
+        if let Some(bucket) = stats.buckets.get(index) {
+            let seconds_ago = (stats.buckets.len() - index) as f64 * stats.bucket_seconds;
+
+            response.on_hover_ui_at_pointer(|ui| {
+                ui.label(format!(
+                    "{:.2} requests/s, {:.0}s ago",
+                    (bucket.allowed + bucket.blocked) as f64 / stats.bucket_seconds,
+                    seconds_ago
+                ));
+                ui.label(
+                    RichText::new(format!("{} allowed", bucket.allowed))
+                        .color(Color32::LIGHT_GREEN),
+                );
+                ui.label(
+                    RichText::new(format!("{} blocked", bucket.blocked)).color(Color32::LIGHT_RED),
+                );
+            });
+        }
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new(format!(
+                "{:.0}s ago",
+                stats.buckets.len() as f64 * stats.bucket_seconds
+            ))
+            .size(11.)
+            .color(Color32::GRAY),
+        );
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            ui.label(RichText::new("Now").size(11.).color(Color32::GRAY));
+        });
+    });
+}
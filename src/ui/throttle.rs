@@ -0,0 +1,194 @@
+use crate::service::{
+    proxy::Proxy,
+    throttle::{ThrottleProfile, ThrottleRule},
+};
+use eframe::{
+    egui::{self, RichText, TextEdit},
+    epaint::Color32,
+};
+
+/// The identifier of the flag, in egui memory, that keeps the network conditions editor open.
+pub const THROTTLE_EDITOR_ID: &str = "throttle_editor";
+
+/// The label shown when no profile is applied.
+const NO_THROTTLE: &str = "Normal";
+
+/// Shows a switch for the network conditions applied to every host without a rule.
+pub fn throttle_switch(proxy: &mut Proxy, ui: &mut egui::Ui) {
+    let mut throttle = proxy.get_settings().throttle;
+    let previous = throttle.clone();
+
+    ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+        ui.add(egui::Label::new("Network: "));
+
+        let selected = throttle
+            .profile
+            .as_ref()
+            .map_or(NO_THROTTLE, |profile| profile.name.as_str());
+
+        egui::ComboBox::from_id_source("throttle_switch")
+            .selected_text(RichText::new(selected).color(match throttle.profile {
+                Some(_) => Color32::YELLOW,
+                None => Color32::LIGHT_GREEN,
+            }))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut throttle.profile, None, NO_THROTTLE);
+
+                for preset in ThrottleProfile::presets() {
+                    let name = preset.name.clone();
+                    ui.selectable_value(&mut throttle.profile, Some(preset), name);
+                }
+
+                ui.separator();
+                if ui.button("Customize...").clicked() {
+                    ui.memory_mut(|m| m.data.insert_temp(egui::Id::new(THROTTLE_EDITOR_ID), true));
+                }
+            })
+            .response
+            .on_hover_text(
+                "Simulate a slower or less reliable network, per host rules take priority.",
+            );
+    });
+
+    if throttle != previous {
+        proxy.set_throttle(throttle);
+    }
+}
+
+/// Shows the network conditions in a separate window, while it's open.
+pub fn throttle_editor(proxy: &mut Proxy, ctx: &egui::Context) {
+    let editor_id = egui::Id::new(THROTTLE_EDITOR_ID);
+    let mut open = ctx.memory_mut(|m| m.data.get_temp::<bool>(editor_id).unwrap_or_default());
+    if !open {
+        return;
+    }
+
+    let mut throttle = proxy.get_settings().throttle;
+    let previous = throttle.clone();
+
+    egui::Window::new("Network Conditions")
+        .id(editor_id)
+        .open(&mut open)
+        .default_size([760., 300.])
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(
+                    "Latency is added before each request is forwarded, or tunnel is opened. Bandwidth is in kbit/s, 0 for no limit.",
+                )
+                .size(11.)
+                .color(Color32::GRAY),
+            );
+            ui.add_space(4.);
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, true])
+                .max_height(ui.available_height() - 30.)
+                .show(ui, |ui| {
+                    let mut removed = None;
+
+                    egui::Grid::new("throttle_grid")
+                        .num_columns(10)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in [
+                                "",
+                                "Host",
+                                "Profile",
+                                "Latency (ms)",
+                                "Jitter (ms)",
+                                "Download",
+                                "Upload",
+                                "Failures (%)",
+                                "Resets (%)",
+                                "",
+                            ] {
+                                ui.label(RichText::new(heading).strong());
+                            }
+                            ui.end_row();
+
+                            // The profile for every other host is shown first, so it reads as the fallback
+                            let mut enabled = throttle.profile.is_some();
+                            ui.checkbox(&mut enabled, "")
+                                .on_hover_text("Throttle every host without a matching rule.");
+                            ui.label(RichText::new("Other hosts").italics());
+                            match (enabled, &mut throttle.profile) {
+                                (true, Some(profile)) => throttle_profile_row("*", profile, ui),
+                                (true, profile) => *profile = Some(ThrottleProfile::default()),
+                                (false, profile) => *profile = None,
+                            }
+                            ui.end_row();
+
+                            for (index, rule) in throttle.rules.iter_mut().enumerate() {
+                                throttle_rule_row(index, rule, ui);
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if let Some(index) = removed {
+                        throttle.rules.remove(index);
+                    }
+                });
+
+            ui.separator();
+
+            if ui.button("Add Rule").clicked() {
+                throttle.rules.push(ThrottleRule::default());
+            }
+        });
+
+    if throttle != previous {
+        proxy.set_throttle(throttle);
+    }
+
+    ctx.memory_mut(|m| m.data.insert_temp(editor_id, open));
+}
+
+/// Shows the editable fields of a single throttle rule in the network conditions grid.
+fn throttle_rule_row(index: usize, rule: &mut ThrottleRule, ui: &mut egui::Ui) {
+    ui.checkbox(&mut rule.enabled, "")
+        .on_hover_text("Enable or disable this rule.");
+
+    ui.add(
+        TextEdit::singleline(&mut rule.host)
+            .hint_text("*.example.com")
+            .desired_width(120.),
+    )
+    .on_hover_text("example.com, *.example.com for it and its subdomains, or * for every host.");
+
+    throttle_profile_row(&index.to_string(), &mut rule.profile, ui);
+}
+
+/// Shows the editable fields of a throttle profile, along with the presets it can be set to.
+fn throttle_profile_row(id: &str, profile: &mut ThrottleProfile, ui: &mut egui::Ui) {
+    let presets = ThrottleProfile::presets();
+
+    egui::ComboBox::from_id_source(("throttle_profile", id))
+        .selected_text(&profile.name)
+        .width(90.)
+        .show_ui(ui, |ui| {
+            for preset in presets.iter() {
+                if ui
+                    .selectable_label(profile == preset, &preset.name)
+                    .clicked()
+                {
+                    *profile = preset.clone();
+                }
+            }
+        });
+
+    ui.add(egui::DragValue::new(&mut profile.latency).range(0..=60_000));
+    ui.add(egui::DragValue::new(&mut profile.jitter).range(0..=60_000));
+    ui.add(egui::DragValue::new(&mut profile.download).range(0..=1_000_000));
+    ui.add(egui::DragValue::new(&mut profile.upload).range(0..=1_000_000));
+    ui.add(egui::DragValue::new(&mut profile.failure_rate).range(0..=100));
+    ui.add(egui::DragValue::new(&mut profile.reset_rate).range(0..=100));
+
+    // A preset that has been edited is no longer that preset
+    if !presets.contains(profile) {
+        profile.name = ThrottleProfile::default().name;
+    }
+}
@@ -1,34 +1,14 @@
-use serde::{de::DeserializeOwned, Serialize};
+use serde::Serialize;
 use std::fs::File;
 
-pub fn read_from_csv<CSVRecord, P: AsRef<std::path::Path>>(
-    file_path: P,
-) -> Result<Vec<CSVRecord>, csv::Error>
-where
-    CSVRecord: DeserializeOwned,
-{
-    let file = File::open(file_path)?;
-    let reader = std::io::BufReader::new(file);
-
-    let mut records: Vec<CSVRecord> = Vec::new();
-    let mut csv = csv::Reader::from_reader(reader);
-
-    for result in csv.deserialize() {
-        let row: CSVRecord = result?;
-        records.push(row);
-    }
-
-    Ok(records)
-}
-
-pub fn write_csv_from_vec<CSVRecord, P: AsRef<std::path::Path>>(
+pub fn write_csv_from_vec<CSVRecord, P>(
     file_path: P,
     headers: Vec<&str>,
     records: Vec<CSVRecord>,
 ) -> Result<(), csv::Error>
 where
     CSVRecord: Serialize,
-    P: Clone,
+    P: AsRef<std::path::Path> + Clone,
 {
     File::create(file_path.clone())?;
     let mut writer = csv::Writer::from_path(file_path)?;
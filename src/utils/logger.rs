@@ -12,14 +12,14 @@ pub enum LogLevel {
     Global = -1,
 }
 
-impl ToString for LogLevel {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LogLevel::Debug => String::from("DEBUG"),
-            LogLevel::Info => String::from("INFO"),
-            LogLevel::Warning => String::from("WARNING"),
-            LogLevel::Error => String::from("ERROR"),
-            LogLevel::Global => String::from("GLOBAL"),
+            LogLevel::Debug => write!(f, "DEBUG"),
+            LogLevel::Info => write!(f, "INFO"),
+            LogLevel::Warning => write!(f, "WARNING"),
+            LogLevel::Error => write!(f, "ERROR"),
+            LogLevel::Global => write!(f, "GLOBAL"),
         }
     }
 }
@@ -82,7 +82,7 @@ impl Logger {
             println!("{}", log);
 
             self.logs.lock().unwrap().push(Log {
-                level: level,
+                level,
                 message: message.to_string(),
                 timestamp,
             });
@@ -120,7 +120,7 @@ impl Logger {
     pub fn set_level(&mut self, value: LogLevel) {
         *self.level.lock().unwrap() = value.clone();
 
-        let message = format!("Log level has been set to: {}", value.to_string());
+        let message = format!("Log level has been set to: {}", value);
         self.log(LogLevel::Global, &message);
     }
 }